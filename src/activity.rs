@@ -0,0 +1,307 @@
+use chrono::prelude::*;
+use chrono::Duration;
+use std::collections::{HashMap, VecDeque};
+use tui::{
+    style::{Color, Modifier, Style},
+    text::{Span, Spans},
+    widgets::{Block, BorderType, Borders, Paragraph, Sparkline, Wrap},
+};
+
+use crate::chat::{Channel, ChatMessage, MessageKind};
+
+// per-minute counts older than this are dropped
+const HISTORY_MINUTES: i64 = 120;
+/// how far back "unique chatters" looks
+pub const WINDOW_MINUTES: i64 = 10;
+const TOP_LEN: usize = 3;
+// how many chatters and emotes are counted; past twice this the least used are dropped
+const MAX_COUNTED: usize = 1000;
+
+/// Chat activity for one channel, updated a message at a time so nothing
+/// has to rescan the scrollback on every frame.
+#[derive(Default)]
+pub struct Activity {
+    // (minutes since the epoch, messages), oldest first
+    minutes: VecDeque<(i64, u64)>,
+    last_seen: HashMap<String, DateTime<Utc>>,
+    messages_by: HashMap<String, u64>,
+    emotes: HashMap<String, u64>,
+}
+
+impl Activity {
+    pub fn record(&mut self, msg: &ChatMessage) {
+        if msg.kind != MessageKind::Chat {
+            return;
+        }
+        let minute = msg.sent_at.timestamp().div_euclid(60);
+        // late messages can land in an earlier minute, keep the buckets in order
+        match self.minutes.iter().position(|(m, _)| *m >= minute) {
+            Some(at) if self.minutes[at].0 == minute => self.minutes[at].1 += 1,
+            Some(at) => self.minutes.insert(at, (minute, 1)),
+            None => self.minutes.push_back((minute, 1)),
+        }
+        let newest = self.minutes.back().map_or(minute, |(m, _)| *m);
+        let oldest_kept = newest - HISTORY_MINUTES;
+        let mut expired = false;
+        while self.minutes.front().is_some_and(|(m, _)| *m <= oldest_kept) {
+            self.minutes.pop_front();
+            expired = true;
+        }
+        // a minute rolled off, forget whoever has been quiet since
+        if expired {
+            self.last_seen
+                .retain(|_, at| at.timestamp().div_euclid(60) > oldest_kept);
+        }
+
+        let seen = self
+            .last_seen
+            .entry(msg.login.clone())
+            .or_insert(msg.sent_at);
+        *seen = (*seen).max(msg.sent_at);
+        *self.messages_by.entry(msg.login.clone()).or_default() += 1;
+        trim(&mut self.messages_by);
+
+        let chars: Vec<char> = msg.text.chars().collect();
+        for emote in &msg.emotes {
+            if let Some(name) = chars.get(emote.start..=emote.end) {
+                *self.emotes.entry(name.iter().collect()).or_default() += 1;
+            }
+        }
+        trim(&mut self.emotes);
+    }
+
+    /// Messages in each of the `count` minutes up to and including `now`'s, oldest first.
+    pub fn per_minute(&self, now: DateTime<Utc>, count: usize) -> Vec<u64> {
+        let now = now.timestamp().div_euclid(60);
+        (now - count as i64 + 1..=now)
+            .map(|minute| {
+                self.minutes
+                    .iter()
+                    .find(|(m, _)| *m == minute)
+                    .map_or(0, |(_, messages)| *messages)
+            })
+            .collect()
+    }
+
+    /// Logins that sent something in the last `minutes` minutes.
+    pub fn unique_chatters(&self, now: DateTime<Utc>, minutes: i64) -> usize {
+        let since = now - Duration::minutes(minutes);
+        self.last_seen.values().filter(|at| **at > since).count()
+    }
+
+    /// The `n` logins with the most messages this session, busiest first.
+    pub fn top_chatters(&self, n: usize) -> Vec<(&str, u64)> {
+        top(&self.messages_by, n)
+    }
+
+    /// The `n` most used emotes this session, by name.
+    pub fn top_emotes(&self, n: usize) -> Vec<(&str, u64)> {
+        top(&self.emotes, n)
+    }
+}
+
+// keeps the `MAX_COUNTED` biggest counts once there are twice as many, so a long
+// session doesn't hold on to every one-off chatter; anyone dropped starts over from 1
+fn trim(counts: &mut HashMap<String, u64>) {
+    if counts.len() <= MAX_COUNTED * 2 {
+        return;
+    }
+    let mut sorted: Vec<_> = counts.drain().collect();
+    sorted.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+    sorted.truncate(MAX_COUNTED);
+    counts.extend(sorted);
+}
+
+fn top(counts: &HashMap<String, u64>, n: usize) -> Vec<(&str, u64)> {
+    let mut sorted: Vec<_> = counts
+        .iter()
+        .map(|(name, count)| (name.as_str(), *count))
+        .collect();
+    sorted.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+    sorted.truncate(n);
+    sorted
+}
+
+/// Messages per minute, `data` comes from `Activity::per_minute`.
+pub fn render_sparkline<'a>(data: &'a [u64]) -> Sparkline<'a> {
+    Sparkline::default()
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .style(Style::default().fg(Color::White))
+                .title(format!("Messages/min, last {}m", data.len()))
+                .border_type(BorderType::Plain),
+        )
+        .data(data)
+        .style(Style::default().fg(Color::LightCyan))
+}
+
+pub fn render_stats<'a>(channel: &Channel, now: DateTime<Utc>) -> Paragraph<'a> {
+    let activity = &channel.activity;
+    let label = |text: &str| {
+        Span::styled(
+            text.to_string(),
+            Style::default()
+                .fg(Color::Yellow)
+                .add_modifier(Modifier::BOLD),
+        )
+    };
+    let ranked = |entries: Vec<(&str, u64)>| {
+        if entries.is_empty() {
+            String::from("none yet")
+        } else {
+            entries
+                .iter()
+                .map(|(name, count)| format!("{} ({})", name, count))
+                .collect::<Vec<_>>()
+                .join(", ")
+        }
+    };
+    let this_minute = activity.per_minute(now, 1)[0];
+
+    let lines = vec![
+        Spans::from(vec![
+            label("This minute: "),
+            Span::raw(format!("{} messages", this_minute)),
+        ]),
+        Spans::from(vec![
+            label(&format!("Chatters ({}m): ", WINDOW_MINUTES)),
+            Span::raw(activity.unique_chatters(now, WINDOW_MINUTES).to_string()),
+        ]),
+        Spans::from(vec![
+            label("Top chatters: "),
+            Span::raw(ranked(activity.top_chatters(TOP_LEN))),
+        ]),
+        Spans::from(vec![
+            label("Top emotes: "),
+            Span::raw(ranked(activity.top_emotes(TOP_LEN))),
+        ]),
+        Spans::from(vec![
+            label("Events: "),
+            Span::raw(channel.events.describe()),
+        ]),
+    ];
+
+    Paragraph::new(lines)
+        .style(Style::default().fg(Color::LightCyan))
+        .wrap(Wrap { trim: true })
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .style(Style::default().fg(Color::White))
+                .title(format!("Stats {}", channel.title()))
+                .border_type(BorderType::Plain),
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chat::Emote;
+
+    fn at(minute: i64, second: i64) -> DateTime<Utc> {
+        Utc.timestamp_opt(1_700_000_000 / 60 * 60 + minute * 60 + second, 0)
+            .unwrap()
+    }
+
+    // ann talks every 20 seconds, bob every minute and cy once, over five minutes
+    fn stream() -> Activity {
+        let mut activity = Activity::default();
+        for minute in 0..5 {
+            for second in [0, 20, 40] {
+                activity.record(&ChatMessage::chat("ann", "hi", at(minute, second)));
+            }
+            activity.record(&ChatMessage::chat("bob", "hello", at(minute, 30)));
+        }
+        activity.record(&ChatMessage::chat("cy", "first", at(4, 50)));
+        activity
+    }
+
+    #[test]
+    fn messages_per_minute() {
+        let activity = stream();
+        assert_eq!(activity.per_minute(at(4, 59), 3), [4, 4, 5]);
+        // nothing yet in the minute after
+        assert_eq!(activity.per_minute(at(5, 0), 2), [5, 0]);
+        assert_eq!(activity.per_minute(at(6, 0), 7), [4, 4, 4, 4, 5, 0, 0]);
+    }
+
+    #[test]
+    fn late_messages_go_in_their_own_minute() {
+        let mut activity = stream();
+        activity.record(&ChatMessage::chat("dee", "lagging", at(1, 10)));
+        activity.record(&ChatMessage::chat("dee", "way behind", at(-3, 0)));
+        assert_eq!(activity.per_minute(at(4, 0), 8), [1, 0, 0, 4, 5, 4, 4, 5]);
+    }
+
+    #[test]
+    fn chatters() {
+        let mut activity = stream();
+        assert_eq!(activity.unique_chatters(at(4, 59), WINDOW_MINUTES), 3);
+        // only cy spoke in the last minute
+        assert_eq!(activity.unique_chatters(at(5, 45), 1), 1);
+        assert_eq!(activity.unique_chatters(at(14, 45), WINDOW_MINUTES), 1);
+        assert_eq!(activity.top_chatters(2), [("ann", 15), ("bob", 5)],);
+        // notices aren't anyone chatting
+        let mut notice = ChatMessage::chat("jtv", "raid incoming", at(4, 55));
+        notice.kind = MessageKind::Notice;
+        activity.record(&notice);
+        assert_eq!(activity.top_chatters(5).len(), 3);
+    }
+
+    #[test]
+    fn emotes() {
+        let mut activity = Activity::default();
+        let mut msg = ChatMessage::chat("ann", "Kappa Kappa ✓ PogChamp", at(0, 0));
+        msg.emotes = vec![
+            Emote {
+                id: String::from("25"),
+                start: 0,
+                end: 4,
+            },
+            Emote {
+                id: String::from("25"),
+                start: 6,
+                end: 10,
+            },
+            Emote {
+                id: String::from("88"),
+                start: 14,
+                end: 21,
+            },
+            // past the end of the text, ignored
+            Emote {
+                id: String::from("1"),
+                start: 30,
+                end: 34,
+            },
+        ];
+        activity.record(&msg);
+        assert_eq!(activity.top_emotes(5), [("Kappa", 2), ("PogChamp", 1)]);
+    }
+
+    #[test]
+    fn old_chatters_are_forgotten() {
+        let mut activity = stream();
+        activity.record(&ChatMessage::chat("eve", "back", at(200, 0)));
+        assert_eq!(activity.last_seen.len(), 1);
+        assert_eq!(activity.per_minute(at(200, 0), 1), [1]);
+    }
+
+    #[test]
+    fn counts_are_capped() {
+        let mut activity = Activity::default();
+        for _ in 0..3 {
+            activity.record(&ChatMessage::chat("regular", "hi", at(0, 0)));
+        }
+        for n in 0..MAX_COUNTED * 3 {
+            activity.record(&ChatMessage::chat(
+                &format!("drive-by-{}", n),
+                "hi",
+                at(0, 1),
+            ));
+        }
+        assert!(activity.messages_by.len() <= MAX_COUNTED * 2);
+        assert_eq!(activity.top_chatters(1), [("regular", 3)]);
+    }
+}
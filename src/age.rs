@@ -0,0 +1,161 @@
+use chrono::prelude::*;
+use std::fmt;
+
+use crate::{Error, Pet};
+
+/// How old a pet is on a given day, in whole years and months.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Age {
+    pub years: u32,
+    pub months: u32,
+}
+
+impl Age {
+    /// The age of something born on `birthdate` as of `today`, zero if it's in the future.
+    pub fn between(birthdate: NaiveDate, today: NaiveDate) -> Age {
+        let mut months = (today.year() - birthdate.year()) * 12 + today.month() as i32
+            - birthdate.month() as i32;
+        if today.day() < birthdate.day() {
+            months -= 1;
+        }
+        let months = months.max(0) as u32;
+        Age {
+            years: months / 12,
+            months: months % 12,
+        }
+    }
+}
+
+impl fmt::Display for Age {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.years, self.months) {
+            (0, months) => write!(f, "{}m", months),
+            (years, 0) => write!(f, "{}y", years),
+            (years, months) => write!(f, "{}y {}m", years, months),
+        }
+    }
+}
+
+/// A best guess at the birthdate of a pet that was `years` old on `reference`, if
+/// that's a date chrono can represent.
+pub fn estimated_birthdate(years: u32, reference: NaiveDate) -> Option<NaiveDate> {
+    reference.checked_sub_months(chrono::Months::new(years.checked_mul(12)?))
+}
+
+/// `3`, `1-5`, `2+` or `<2`, in whole years.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct AgeRange {
+    min: Option<u32>,
+    // exclusive
+    max: Option<u32>,
+}
+
+impl AgeRange {
+    pub fn parse(input: &str) -> Result<AgeRange, Error> {
+        let usage =
+            || Error::CommandError(format!("'{}' isn't an age like 3, 1-5, 2+ or <2", input));
+        let years = |value: &str| value.trim().parse::<u32>().map_err(|_| usage());
+        // the exclusive upper end of a range that includes `max`
+        let past = |max: u32| max.checked_add(1).ok_or_else(usage);
+        Ok(if let Some(max) = input.strip_prefix('<') {
+            AgeRange {
+                min: None,
+                max: Some(years(max)?),
+            }
+        } else if let Some(min) = input.strip_suffix('+') {
+            AgeRange {
+                min: Some(years(min)?),
+                max: None,
+            }
+        } else if let Some((min, max)) = input.split_once('-') {
+            AgeRange {
+                min: Some(years(min)?),
+                max: Some(past(years(max)?)?),
+            }
+        } else {
+            let exact = years(input)?;
+            AgeRange {
+                min: Some(exact),
+                max: Some(past(exact)?),
+            }
+        })
+    }
+
+    pub fn contains(&self, age: Age) -> bool {
+        self.min.is_none_or(|min| age.years >= min) && self.max.is_none_or(|max| age.years < max)
+    }
+}
+
+/// The order of the Pets list, cycled with `s`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum PetSort {
+    /// the order they're stored in
+    Added,
+    Youngest,
+    Oldest,
+}
+
+impl PetSort {
+    pub fn next(self) -> PetSort {
+        match self {
+            PetSort::Added => PetSort::Youngest,
+            PetSort::Youngest => PetSort::Oldest,
+            PetSort::Oldest => PetSort::Added,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            PetSort::Added => "as added",
+            PetSort::Youngest => "youngest first",
+            PetSort::Oldest => "oldest first",
+        }
+    }
+
+    pub fn apply(self, pets: &mut [Pet]) {
+        match self {
+            PetSort::Added => {}
+            // a later birthdate is a younger pet
+            PetSort::Youngest => pets.sort_by_key(|pet| std::cmp::Reverse(pet.birthdate)),
+            PetSort::Oldest => pets.sort_by_key(|pet| pet.birthdate),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ranges_at_the_limits() {
+        let max = u32::MAX.to_string();
+        assert!(AgeRange::parse(&max).is_err());
+        assert!(AgeRange::parse(&format!("1-{}", max)).is_err());
+        assert_eq!(
+            AgeRange::parse(&(u32::MAX - 1).to_string()).unwrap(),
+            AgeRange {
+                min: Some(u32::MAX - 1),
+                max: Some(u32::MAX),
+            }
+        );
+        assert!(AgeRange::parse(&format!("<{}", max)).is_ok());
+        assert!(AgeRange::parse(&format!("{}+", max)).is_ok());
+        assert!(AgeRange::parse("4294967296").is_err());
+        assert!(AgeRange::parse("-1").is_err());
+    }
+
+    #[test]
+    fn birthdates_at_the_limits() {
+        let today = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
+        assert_eq!(
+            estimated_birthdate(3, today),
+            NaiveDate::from_ymd_opt(2021, 3, 1)
+        );
+        assert_eq!(estimated_birthdate(0, today), Some(today));
+        // years * 12 overflows
+        assert_eq!(estimated_birthdate(400_000_000, today), None);
+        assert_eq!(estimated_birthdate(u32::MAX, today), None);
+        // fits in months, but not in a date
+        assert_eq!(estimated_birthdate(u32::MAX / 12, today), None);
+    }
+}
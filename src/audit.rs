@@ -0,0 +1,450 @@
+use chrono::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::time::SystemTime;
+use tui::{
+    style::{Color, Modifier, Style},
+    text::{Span, Spans},
+    widgets::{Block, BorderType, Borders, List, ListItem, Paragraph, Wrap},
+};
+
+use crate::categories;
+use crate::schema::Db;
+use crate::{Error, Pet};
+
+pub const AUDIT_PATH: &str = "./data/audit.jsonl";
+
+/// Where a change came from.
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Source {
+    Tui,
+    Cli,
+    Bot,
+    /// the trash being emptied on startup
+    Retention,
+}
+
+impl Source {
+    pub fn label(self) -> &'static str {
+        match self {
+            Source::Tui => "tui",
+            Source::Cli => "cli",
+            Source::Bot => "bot",
+            Source::Retention => "retention",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Action {
+    Add,
+    Update,
+    /// moved to the trash
+    Delete,
+    Restore,
+    /// dropped from the trash for good
+    Purge,
+    /// took on fields of a duplicate, which went to the trash
+    Merge,
+}
+
+impl Action {
+    pub fn label(self) -> &'static str {
+        match self {
+            Action::Add => "added",
+            Action::Update => "updated",
+            Action::Delete => "deleted",
+            Action::Restore => "restored",
+            Action::Purge => "purged",
+            Action::Merge => "merged",
+        }
+    }
+
+    fn color(self) -> Color {
+        match self {
+            Action::Add => Color::LightGreen,
+            Action::Update => Color::LightYellow,
+            Action::Delete => Color::LightRed,
+            Action::Restore => Color::LightCyan,
+            Action::Purge => Color::Red,
+            Action::Merge => Color::LightMagenta,
+        }
+    }
+}
+
+/// One line of `data/audit.jsonl`: what happened to a pet, with the pet as it was before and after.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct AuditEntry {
+    pub at: DateTime<Utc>,
+    pub action: Action,
+    pub source: Source,
+    pub pet_id: usize,
+    pub pet_name: String,
+    /// missing for an add or restore
+    pub before: Option<Pet>,
+    /// missing for a delete
+    pub after: Option<Pet>,
+}
+
+impl AuditEntry {
+    pub fn new(
+        action: Action,
+        source: Source,
+        before: Option<&Pet>,
+        after: Option<&Pet>,
+    ) -> AuditEntry {
+        let pet = after.or(before).expect("a change has a pet on one side");
+        AuditEntry {
+            at: Utc::now(),
+            action,
+            source,
+            pet_id: pet.id,
+            pet_name: pet.name.clone(),
+            before: before.cloned(),
+            after: after.cloned(),
+        }
+    }
+}
+
+/// The entries that take the pets in `before` to the ones in `after`, matched by id.
+pub fn changes(before: &[Pet], after: &[Pet], source: Source) -> Result<Vec<AuditEntry>, Error> {
+    let mut entries = vec![];
+    for old in before {
+        match after.iter().find(|pet| pet.id == old.id) {
+            None => entries.push(AuditEntry::new(Action::Delete, source, Some(old), None)),
+            Some(new) if serde_json::to_value(old)? != serde_json::to_value(new)? => entries.push(
+                AuditEntry::new(Action::Update, source, Some(old), Some(new)),
+            ),
+            Some(_) => {}
+        }
+    }
+    for new in after {
+        if !before.iter().any(|pet| pet.id == new.id) {
+            entries.push(AuditEntry::new(Action::Add, source, None, Some(new)));
+        }
+    }
+    Ok(entries)
+}
+
+/// Appends `entries` to `data/audit.jsonl`; earlier lines are never rewritten.
+pub fn append(entries: &[AuditEntry]) -> Result<(), Error> {
+    if entries.is_empty() {
+        return Ok(());
+    }
+    let mut lines = vec![];
+    for entry in entries {
+        lines.extend(serde_json::to_vec(entry)?);
+        lines.push(b'\n');
+    }
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(AUDIT_PATH)?
+        .write_all(&lines)?;
+    Ok(())
+}
+
+// every entry that can be read, oldest first, and how many lines couldn't be
+fn read_log() -> Result<(Vec<AuditEntry>, usize), Error> {
+    let content = match fs::read_to_string(AUDIT_PATH) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok((vec![], 0)),
+        Err(e) => return Err(e.into()),
+    };
+    Ok(parse_log(&content))
+}
+
+fn parse_log(content: &str) -> (Vec<AuditEntry>, usize) {
+    let mut entries = vec![];
+    let mut skipped = 0;
+    for line in content.lines().filter(|line| !line.trim().is_empty()) {
+        match serde_json::from_str(line) {
+            Ok(entry) => entries.push(entry),
+            Err(_) => skipped += 1,
+        }
+    }
+    (entries, skipped)
+}
+
+// newest first, only those about `pet_id` if given
+fn newest_first(entries: &[AuditEntry], pet_id: Option<usize>) -> Vec<AuditEntry> {
+    entries
+        .iter()
+        .rev()
+        .filter(|entry| pet_id.is_none_or(|id| entry.pet_id == id))
+        .cloned()
+        .collect()
+}
+
+/// Every entry, newest first, only those about `pet_id` if given. Lines that
+/// can't be read are left out.
+pub fn history(pet_id: Option<usize>) -> Result<Vec<AuditEntry>, Error> {
+    let (entries, _) = read_log()?;
+    Ok(newest_first(&entries, pet_id))
+}
+
+/// The log as the History tab lists it, kept between frames. The file is only read
+/// again when its length or modification time changes.
+#[derive(Default)]
+pub struct History {
+    stamp: Option<(SystemTime, u64)>,
+    // the pet `shown` was narrowed to
+    built_for: Option<Option<usize>>,
+    log: Vec<AuditEntry>,
+    /// newest first
+    pub shown: Vec<AuditEntry>,
+    /// lines of the log that couldn't be read
+    pub skipped: usize,
+}
+
+impl History {
+    /// Reads the log again if it changed, then narrows it to `pet_id` if given.
+    pub fn refresh(&mut self, pet_id: Option<usize>) -> Result<(), Error> {
+        let stamp = fs::metadata(AUDIT_PATH)
+            .and_then(|meta| Ok((meta.modified()?, meta.len())))
+            .ok();
+        if stamp != self.stamp {
+            let (log, skipped) = read_log()?;
+            self.log = log;
+            self.skipped = skipped;
+            self.stamp = stamp;
+            self.built_for = None;
+        }
+        if self.built_for != Some(pet_id) {
+            self.shown = newest_first(&self.log, pet_id);
+            self.built_for = Some(pet_id);
+        }
+        Ok(())
+    }
+}
+
+/// Puts a deleted or purged pet back as it was just before `entry` removed it,
+/// taking it out of the trash if it's still there.
+pub fn restore(db: &mut Db, entry: &AuditEntry, source: Source) -> Result<AuditEntry, Error> {
+    let pet = match (entry.action, &entry.before) {
+        (Action::Delete | Action::Purge, Some(pet)) => pet,
+        _ => {
+            return Err(Error::CommandError(String::from(
+                "only a deletion can be restored",
+            )))
+        }
+    };
+    if db.pets.iter().any(|existing| existing.id == pet.id) {
+        return Err(Error::CommandError(format!(
+            "{} is already in the store",
+            pet.name
+        )));
+    }
+    // taking it out of the trash mustn't take a different pet with the same id along
+    if db
+        .trash
+        .iter()
+        .any(|trashed| trashed.id == pet.id && trashed.created_at != pet.created_at)
+    {
+        return Err(Error::CommandError(format!(
+            "another pet in the trash has id {}",
+            pet.id
+        )));
+    }
+    if categories::find(&db.categories, &pet.category).is_none() {
+        return Err(Error::CommandError(format!(
+            "{} was a {}, add that category again first",
+            pet.name, pet.category
+        )));
+    }
+    let mut pet = pet.clone();
+    pet.deleted_at = None;
+    pet.updated_at = Some(Utc::now());
+    db.trash.retain(|trashed| trashed.id != pet.id);
+    db.pets.push(pet.clone());
+    Ok(AuditEntry::new(Action::Restore, source, None, Some(&pet)))
+}
+
+/// The latest entry about the pet with `id`, which has to be its deletion for `restore`.
+pub fn last_entry(id: usize) -> Result<AuditEntry, Error> {
+    history(Some(id))?
+        .into_iter()
+        .next()
+        .ok_or_else(|| Error::CommandError(format!("no history for pet {}", id)))
+}
+
+pub fn render_history<'a>(
+    entries: &[AuditEntry],
+    filter: Option<&str>,
+    skipped: usize,
+) -> List<'a> {
+    let items: Vec<_> = entries
+        .iter()
+        .map(|entry| {
+            ListItem::new(Spans::from(vec![
+                Span::styled(
+                    format!(
+                        "{} ",
+                        entry.at.with_timezone(&Local).format("%Y-%m-%d %H:%M")
+                    ),
+                    Style::default().fg(Color::DarkGray),
+                ),
+                Span::styled(
+                    format!("{:<9}", entry.action.label()),
+                    Style::default().fg(entry.action.color()),
+                ),
+                Span::styled(
+                    entry.pet_name.clone(),
+                    Style::default().add_modifier(Modifier::BOLD),
+                ),
+                Span::styled(
+                    format!("  #{} via {}", entry.pet_id, entry.source.label()),
+                    Style::default().fg(Color::DarkGray),
+                ),
+            ]))
+        })
+        .collect();
+
+    let mut title = match filter {
+        Some(name) => format!("History of {} ({})", name, entries.len()),
+        None => format!("History ({})", entries.len()),
+    };
+    if skipped > 0 {
+        title = format!("{}, {} unreadable lines skipped", title, skipped);
+    }
+    List::new(items)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .style(Style::default().fg(Color::White))
+                .title(title)
+                .border_type(BorderType::Plain),
+        )
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+}
+
+fn show(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        Value::Null => String::from("-"),
+        other => other.to_string(),
+    }
+}
+
+/// The fields an entry changed, or all of them for an add, delete or restore.
+pub fn render_entry<'a>(entry: Option<&AuditEntry>) -> Paragraph<'a> {
+    let fields = |pet: &Option<Pet>| match pet.as_ref().map(serde_json::to_value) {
+        Some(Ok(Value::Object(fields))) => fields,
+        _ => serde_json::Map::new(),
+    };
+    let lines: Vec<Spans> = match entry {
+        None => vec![],
+        Some(entry) => {
+            let (before, after) = (fields(&entry.before), fields(&entry.after));
+            let keys = if after.is_empty() { &before } else { &after };
+            keys.keys()
+                .filter_map(|key| {
+                    let key_span =
+                        Span::styled(format!("{}: ", key), Style::default().fg(Color::Yellow));
+                    match (before.get(key), after.get(key)) {
+                        (Some(old), Some(new)) if old == new => None,
+                        (Some(old), Some(new)) => Some(Spans::from(vec![
+                            key_span,
+                            Span::styled(show(old), Style::default().fg(Color::LightRed)),
+                            Span::raw(" → "),
+                            Span::styled(show(new), Style::default().fg(Color::LightGreen)),
+                        ])),
+                        (Some(value), None) | (None, Some(value)) => {
+                            Some(Spans::from(vec![key_span, Span::raw(show(value))]))
+                        }
+                        (None, None) => None,
+                    }
+                })
+                .collect()
+        }
+    };
+
+    Paragraph::new(lines).wrap(Wrap { trim: false }).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .style(Style::default().fg(Color::White))
+            .title("Changes")
+            .border_type(BorderType::Plain),
+    )
+}
+
+/// The key help, or the result of the last action.
+pub fn render_help<'a>(status: &str) -> Paragraph<'a> {
+    let text = if status.is_empty() {
+        "f only this pet / everything, r restore a deleted pet"
+    } else {
+        status
+    };
+    Paragraph::new(Spans::from(vec![Span::raw(text.to_string())])).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .style(Style::default().fg(Color::White))
+            .border_type(BorderType::Plain),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pet(id: usize, name: &str) -> Pet {
+        Pet::new(
+            id,
+            name,
+            "dogs",
+            NaiveDate::from_ymd_opt(2020, 1, 1).unwrap(),
+            false,
+        )
+    }
+
+    #[test]
+    fn bad_lines_are_skipped_and_counted() {
+        let line = |entry: &AuditEntry| serde_json::to_string(entry).unwrap();
+        let rex = pet(1, "Rex");
+        let bo = pet(2, "Bo");
+        let content = format!(
+            "{}\n{{\"at\": \"not a date\"}}\n\n{}\ntruncated {{\"at",
+            line(&AuditEntry::new(Action::Add, Source::Tui, None, Some(&rex))),
+            line(&AuditEntry::new(
+                Action::Delete,
+                Source::Cli,
+                Some(&bo),
+                None
+            )),
+        );
+        let (entries, skipped) = parse_log(&content);
+        assert_eq!(skipped, 2);
+        let shown = newest_first(&entries, None);
+        let names: Vec<_> = shown.iter().map(|e| e.pet_name.as_str()).collect();
+        assert_eq!(names, ["Bo", "Rex"]);
+        assert_eq!(newest_first(&entries, Some(1))[0].action, Action::Add);
+    }
+
+    #[test]
+    fn restore_refuses_duplicate_ids() {
+        let mut db = Db {
+            categories: vec![categories::Category::new("Dogs", 0)],
+            ..Db::default()
+        };
+        let rex = pet(1, "Rex");
+        let deleted = AuditEntry::new(Action::Delete, Source::Tui, Some(&rex), None);
+
+        // another pet took the id in the meantime
+        let mut other = pet(1, "Bo");
+        other.created_at = rex.created_at + chrono::Duration::days(1);
+        db.pets.push(other.clone());
+        assert!(restore(&mut db, &deleted, Source::Tui).is_err());
+        db.trash.push(db.pets.remove(0));
+        assert!(restore(&mut db, &deleted, Source::Tui).is_err());
+
+        db.trash = vec![rex.clone()];
+        let restored = restore(&mut db, &deleted, Source::Tui).unwrap();
+        assert_eq!(restored.action, Action::Restore);
+        assert_eq!(db.pets[0].name, "Rex");
+        assert!(db.trash.is_empty());
+    }
+}
@@ -0,0 +1,129 @@
+use chrono::prelude::*;
+use std::time::{Duration, Instant};
+use tui::{
+    backend::TestBackend,
+    layout::{Constraint, Direction, Layout},
+    Terminal,
+};
+
+use crate::age::PetSort;
+use crate::categories::Category;
+use crate::cli::BenchOptions;
+use crate::pet_view::PetView;
+use crate::schema::Db;
+use crate::{Error, Pet};
+
+// the size of the terminal frames are drawn into
+const WIDTH: u16 = 120;
+const HEIGHT: u16 = 40;
+
+/// `count` made up pets spread over two categories, born over the last twenty years.
+fn generate(count: usize, today: NaiveDate) -> Db {
+    let categories = vec![Category::new("Dogs", 0), Category::new("Cats", 1)];
+    let pets = (0..count)
+        .map(|i| {
+            let birthdate = today - chrono::Duration::days((i % 7300) as i64);
+            Pet::new(
+                i,
+                &format!("Pet {}", i),
+                &categories[i % categories.len()].key,
+                birthdate,
+                i % 5 == 0,
+            )
+        })
+        .collect();
+    Db {
+        pets,
+        categories,
+        trash: vec![],
+    }
+}
+
+/// What the nth frame does before it's drawn, so the list scrolls every which way.
+fn act(view: &mut PetView, frame: usize, rows: usize) {
+    match frame % 8 {
+        0 | 1 => view.step(true),
+        2 => view.page(rows, true),
+        3 => view.step(false),
+        4 => view.page(rows, false),
+        5 => view.select(view.len().saturating_sub(1)),
+        6 => view.select(0),
+        _ => view.select(frame * 7919 % view.len().max(1)),
+    }
+}
+
+/// Draws the Pets tab over a generated store in memory, timing each frame, and fails
+/// if the slowest one is over the budget.
+pub fn run(options: &BenchOptions) -> Result<(), Error> {
+    let today = Local::now().date_naive();
+    let sort = PetSort::Added;
+
+    let started = Instant::now();
+    let mut view = PetView::new(generate(options.pets, today));
+    view.build(sort, None, today);
+    println!(
+        "{} pets sorted in {:.1} ms",
+        view.len(),
+        started.elapsed().as_secs_f64() * 1000.0
+    );
+
+    let mut terminal = Terminal::new(TestBackend::new(WIDTH, HEIGHT))?;
+    let mut times = Vec::with_capacity(options.frames);
+    for frame in 0..options.frames {
+        let started = Instant::now();
+        terminal.draw(|rect| {
+            let chunks = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([Constraint::Percentage(20), Constraint::Percentage(80)].as_ref())
+                .split(rect.size());
+            let detail_chunks = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Length(4), Constraint::Min(3)].as_ref())
+                .split(chunks[1]);
+            let rows = chunks[0].height.saturating_sub(2) as usize;
+            act(&mut view, frame, rows);
+            let (list, mut state, detail, records) =
+                crate::render_pets(&mut view, rows, sort, false, today);
+            rect.render_stateful_widget(list, chunks[0], &mut state);
+            rect.render_widget(detail, detail_chunks[0]);
+            rect.render_widget(records, detail_chunks[1]);
+        })?;
+        times.push(started.elapsed());
+    }
+
+    let slowest = times.iter().max().copied().unwrap_or_default();
+    let total: Duration = times.iter().sum();
+    let average = total / times.len().max(1) as u32;
+    println!(
+        "{} frames: {:.2} ms average, {:.2} ms slowest (budget {} ms)",
+        times.len(),
+        average.as_secs_f64() * 1000.0,
+        slowest.as_secs_f64() * 1000.0,
+        options.budget_ms
+    );
+    if slowest > Duration::from_millis(options.budget_ms) {
+        return Err(Error::CommandError(format!(
+            "the slowest frame took {:.2} ms, over the {} ms budget",
+            slowest.as_secs_f64() * 1000.0,
+            options.budget_ms
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // slow in a debug build, run with `cargo test --release -- --ignored`
+    #[test]
+    #[ignore]
+    fn hundred_thousand_pets_within_budget() {
+        let options = BenchOptions {
+            pets: 100_000,
+            frames: 200,
+            budget_ms: 16,
+        };
+        assert!(run(&options).is_ok());
+    }
+}
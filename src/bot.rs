@@ -0,0 +1,195 @@
+use chrono::prelude::*;
+use rand::prelude::*;
+use std::collections::{BTreeMap, HashMap};
+use std::time::{Duration, Instant};
+
+use crate::age::{self, AgeRange};
+use crate::audit::Source;
+use crate::categories;
+use crate::chat::{ChatMessage, MessageKind};
+use crate::config::BotConfig;
+use crate::viewers::Role;
+use crate::{add_pet_to_db, read_db, read_store, Error, Pet};
+
+// twitch drops anything longer
+const MAX_REPLY_LEN: usize = 500;
+const MAX_LISTED_PETS: usize = 10;
+
+#[derive(Copy, Clone, PartialEq)]
+enum Permission {
+    Everyone,
+    Moderator,
+}
+
+const COMMANDS: [(&str, Permission); 4] = [
+    ("pet", Permission::Everyone),
+    ("pets", Permission::Everyone),
+    ("randompet", Permission::Everyone),
+    ("adopt", Permission::Moderator),
+];
+
+/// What the bot did with a command: the chat reply, if any, and a line for the bot log.
+pub struct BotAction {
+    pub reply: Option<String>,
+    pub log: String,
+}
+
+/// Answers `!pet`, `!pets`, `!randompet` and `!adopt` from the pet store.
+pub struct Bot {
+    config: BotConfig,
+    // keyed by (channel, command)
+    last_used: HashMap<(String, String), Instant>,
+}
+
+impl Bot {
+    pub fn new(config: &BotConfig) -> Bot {
+        Bot {
+            config: config.clone(),
+            last_used: HashMap::new(),
+        }
+    }
+
+    pub fn handle(&mut self, msg: &ChatMessage) -> Option<BotAction> {
+        if !self.config.enabled || msg.kind != MessageKind::Chat {
+            return None;
+        }
+        let text = msg.text.strip_prefix('!')?;
+        let mut words = text.split_whitespace();
+        let name = words.next()?.to_lowercase();
+        let args: Vec<&str> = words.collect();
+        let (_, permission) = COMMANDS.iter().find(|(command, _)| *command == name)?;
+        let log = |what: String| format!("#{} {}: !{} {}", msg.channel, msg.login, name, what);
+
+        let role = Role::from_badges(&msg.badges);
+        if *permission == Permission::Moderator
+            && !matches!(role, Role::Broadcaster | Role::Moderator)
+        {
+            return Some(BotAction {
+                reply: None,
+                log: log(String::from("denied, moderators only")),
+            });
+        }
+
+        let key = (msg.channel.clone(), name.clone());
+        let cooldown = self
+            .config
+            .cooldowns
+            .get(&name)
+            .copied()
+            .unwrap_or(self.config.cooldown_secs);
+        if let Some(last) = self.last_used.get(&key) {
+            if last.elapsed() < Duration::from_secs(cooldown) {
+                return Some(BotAction {
+                    reply: None,
+                    log: log(String::from("ignored, on cooldown")),
+                });
+            }
+        }
+        self.last_used.insert(key, Instant::now());
+
+        Some(match run_command(&name, &args) {
+            Ok(reply) => {
+                let reply: String = reply.chars().take(MAX_REPLY_LEN).collect();
+                BotAction {
+                    log: log(format!("-> {}", reply)),
+                    reply: Some(reply),
+                }
+            }
+            Err(e) => BotAction {
+                reply: None,
+                log: log(format!("failed: {}", e)),
+            },
+        })
+    }
+}
+
+fn run_command(name: &str, args: &[&str]) -> Result<String, Error> {
+    Ok(match (name, args) {
+        ("pet", []) => String::from("usage: !pet <name>"),
+        ("pet", name) => {
+            let name = name.join(" ");
+            match read_db()?
+                .into_iter()
+                .find(|pet| pet.name.eq_ignore_ascii_case(&name))
+            {
+                Some(pet) => describe(&pet),
+                None => format!("no pet called {}", name),
+            }
+        }
+        ("pets", []) => {
+            let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+            for pet in read_db()? {
+                *counts.entry(pet.category).or_default() += 1;
+            }
+            let counts: Vec<_> = counts
+                .iter()
+                .map(|(category, count)| format!("{} {}", count, category))
+                .collect();
+            format!("we have {}", counts.join(", "))
+        }
+        ("pets", [category, rest @ ..]) => {
+            // a bad range gets the usage hint in chat rather than failing
+            let ages = match rest.first().map(|range| AgeRange::parse(range)) {
+                Some(Err(e)) => return Ok(e.to_string()),
+                Some(Ok(ages)) => Some(ages),
+                None => None,
+            };
+            let today = Local::now().date_naive();
+            let db = read_store()?;
+            let key = match categories::find(&db.categories, category) {
+                Some(found) => found.key.clone(),
+                None => return Ok(format!("no {} here", category)),
+            };
+            let names: Vec<_> = db
+                .pets
+                .into_iter()
+                .filter(|pet| pet.category == key)
+                .filter(|pet| ages.is_none_or(|ages| ages.contains(pet.age(today))))
+                .map(|pet| pet.name)
+                .collect();
+            match names.len() {
+                0 => format!("no {} here", category),
+                n if n > MAX_LISTED_PETS => format!(
+                    "{} {}: {} and {} more",
+                    n,
+                    category,
+                    names[..MAX_LISTED_PETS].join(", "),
+                    n - MAX_LISTED_PETS
+                ),
+                n => format!("{} {}: {}", n, category, names.join(", ")),
+            }
+        }
+        ("randompet", _) => match read_db()?.choose(&mut rand::thread_rng()) {
+            Some(pet) => describe(pet),
+            None => String::from("no pets yet"),
+        },
+        ("adopt", [name, category, age]) => {
+            let today = Local::now().date_naive();
+            let birthdate = match (age.parse(), NaiveDate::parse_from_str(age, "%Y-%m-%d")) {
+                (Ok(years), _) => age::estimated_birthdate(years, today).map(|date| (date, true)),
+                (_, Ok(date)) if date <= today => Some((date, false)),
+                _ => None,
+            };
+            match birthdate {
+                Some((birthdate, estimated)) => {
+                    let pet = add_pet_to_db(name, category, birthdate, estimated, Source::Bot)?;
+                    format!("welcome {}! ({})", pet.name, describe(&pet))
+                }
+                None => format!("'{}' isn't an age or a YYYY-MM-DD birthdate", age),
+            }
+        }
+        ("adopt", _) => String::from("usage: !adopt <name> <category> <age|birthdate>"),
+        _ => unreachable!("only called with names from COMMANDS"),
+    })
+}
+
+fn describe(pet: &Pet) -> String {
+    format!(
+        "{} ({}), {} old, id {}, added {}",
+        pet.name,
+        pet.category,
+        pet.age_label(Local::now().date_naive()),
+        pet.id,
+        pet.created_at.format("%Y-%m-%d")
+    )
+}
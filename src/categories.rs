@@ -0,0 +1,313 @@
+use serde::{Deserialize, Serialize};
+use tui::{
+    style::{Color, Modifier, Style},
+    text::{Span, Spans},
+    widgets::{Block, BorderType, Borders, List, ListItem, Paragraph},
+};
+use tui_input::Input;
+
+use crate::chat;
+use crate::schema::Db;
+use crate::Error;
+
+// new categories take the next of these, `c` and `i` cycle through them
+const COLORS: [&str; 6] = [
+    "#e07a5f", "#3d85c6", "#81b29a", "#f2cc8f", "#b48ead", "#88c0d0",
+];
+const ICONS: [&str; 8] = ["●", "■", "▲", "◆", "★", "♥", "♣", "♠"];
+
+/// A kind of pet. Pets refer to it by `key`, `name` is what's shown.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Category {
+    pub key: String,
+    pub name: String,
+    /// `#RRGGBB`
+    pub color: String,
+    pub icon: String,
+}
+
+impl Category {
+    /// A category called `name`, styled after the `nth` one.
+    pub fn new(name: &str, nth: usize) -> Category {
+        Category {
+            key: key_for(name),
+            name: name.trim().to_string(),
+            color: COLORS[nth % COLORS.len()].to_string(),
+            icon: ICONS[nth % ICONS.len()].to_string(),
+        }
+    }
+
+    pub fn style(&self) -> Style {
+        match chat::parse_color(&self.color) {
+            Some((r, g, b)) => Style::default().fg(Color::Rgb(r, g, b)),
+            None => Style::default(),
+        }
+    }
+
+    pub fn label(&self) -> String {
+        format!("{} {}", self.icon, self.name)
+    }
+}
+
+/// What pets store for a category called `name`: lowercase, dashes for spaces.
+pub fn key_for(name: &str) -> String {
+    name.split_whitespace()
+        .map(|word| {
+            word.chars()
+                .filter(|c| c.is_alphanumeric())
+                .collect::<String>()
+                .to_lowercase()
+        })
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// The category `name` refers to, by key or display name, ignoring case.
+pub fn find<'a>(categories: &'a [Category], name: &str) -> Option<&'a Category> {
+    let key = key_for(name);
+    categories
+        .iter()
+        .find(|category| category.key == key || category.name.eq_ignore_ascii_case(name.trim()))
+}
+
+/// Like `find`, but an unknown name is an error listing the ones there are.
+pub fn lookup<'a>(categories: &'a [Category], name: &str) -> Result<&'a Category, Error> {
+    find(categories, name).ok_or_else(|| {
+        let known: Vec<_> = categories.iter().map(|c| c.key.as_str()).collect();
+        Error::CommandError(format!(
+            "unknown category '{}', use one of: {}",
+            name,
+            known.join(", ")
+        ))
+    })
+}
+
+pub fn pet_count(db: &Db, key: &str) -> usize {
+    db.pets.iter().filter(|pet| pet.category == key).count()
+}
+
+pub fn add(db: &mut Db, name: &str) -> Result<String, Error> {
+    let category = Category::new(name, db.categories.len());
+    if category.key.is_empty() {
+        return Err(Error::CommandError(String::from("a category needs a name")));
+    }
+    if let Some(existing) = find(&db.categories, name) {
+        return Err(Error::CommandError(format!(
+            "there's already {}",
+            existing.name
+        )));
+    }
+    let status = format!("added {}", category.name);
+    db.categories.push(category);
+    Ok(status)
+}
+
+/// Renames the category, and moves its pets over to the new key.
+pub fn rename(db: &mut Db, key: &str, name: &str) -> Result<String, Error> {
+    let new_key = key_for(name);
+    if new_key.is_empty() {
+        return Err(Error::CommandError(String::from("a category needs a name")));
+    }
+    if let Some(other) = db
+        .categories
+        .iter()
+        .find(|c| c.key == new_key && c.key != key)
+    {
+        return Err(Error::CommandError(format!(
+            "{} already exists, merge into it instead",
+            other.name
+        )));
+    }
+    let category = db
+        .categories
+        .iter_mut()
+        .find(|c| c.key == key)
+        .ok_or_else(|| Error::CommandError(format!("no category {}", key)))?;
+    category.key = new_key.clone();
+    category.name = name.trim().to_string();
+    let mut moved = 0;
+    // trashed pets follow along, so they can still be restored
+    for pet in db
+        .pets
+        .iter_mut()
+        .chain(db.trash.iter_mut())
+        .filter(|pet| pet.category == key)
+    {
+        pet.category = new_key.clone();
+        moved += 1;
+    }
+    Ok(format!(
+        "renamed to {}, {} pets updated",
+        name.trim(),
+        moved
+    ))
+}
+
+/// Moves every pet of `from` into `into` and drops `from`.
+pub fn merge(db: &mut Db, from: &str, into: &str) -> Result<String, Error> {
+    if from == into {
+        return Err(Error::CommandError(String::from(
+            "pick another category to merge into",
+        )));
+    }
+    let name = |key: &str| {
+        db.categories
+            .iter()
+            .find(|c| c.key == key)
+            .map(|c| c.name.clone())
+            .ok_or_else(|| Error::CommandError(format!("no category {}", key)))
+    };
+    let (from_name, into_name) = (name(from)?, name(into)?);
+    let mut moved = 0;
+    for pet in db
+        .pets
+        .iter_mut()
+        .chain(db.trash.iter_mut())
+        .filter(|pet| pet.category == from)
+    {
+        pet.category = into.to_string();
+        moved += 1;
+    }
+    db.categories.retain(|c| c.key != from);
+    Ok(format!(
+        "merged {} into {}, {} pets moved",
+        from_name, into_name, moved
+    ))
+}
+
+/// Removes an unused category; one that still has pets, even trashed ones that could
+/// be restored, has to be merged away instead.
+pub fn delete(db: &mut Db, key: &str) -> Result<String, Error> {
+    let trashed = db.trash.iter().filter(|pet| pet.category == key).count();
+    match (pet_count(db, key), trashed) {
+        (0, 0) => {
+            db.categories.retain(|c| c.key != key);
+            Ok(format!("deleted {}", key))
+        }
+        (n, 0) => Err(Error::CommandError(format!(
+            "{} pets are still {}, merge it into another category instead",
+            n, key
+        ))),
+        (n, trashed) => Err(Error::CommandError(format!(
+            "{} pets are still {} ({} in the trash), merge it into another category instead",
+            n + trashed,
+            key,
+            trashed
+        ))),
+    }
+}
+
+/// Moves the category on to the next preset color, or the next icon.
+pub fn cycle_style(db: &mut Db, key: &str, icon: bool) {
+    if let Some(category) = db.categories.iter_mut().find(|c| c.key == key) {
+        if icon {
+            let at = ICONS.iter().position(|i| *i == category.icon);
+            category.icon = ICONS[at.map_or(0, |at| (at + 1) % ICONS.len())].to_string();
+        } else {
+            let at = COLORS.iter().position(|c| *c == category.color);
+            category.color = COLORS[at.map_or(0, |at| (at + 1) % COLORS.len())].to_string();
+        }
+    }
+}
+
+/// The categories with how many pets each has; `merging` is marked while a merge waits for its target.
+pub fn render_categories<'a>(db: &Db, merging: Option<&str>) -> List<'a> {
+    let items: Vec<_> = db
+        .categories
+        .iter()
+        .map(|category| {
+            let mut spans = vec![
+                Span::styled(format!("{} ", category.icon), category.style()),
+                Span::styled(
+                    category.name.clone(),
+                    category.style().add_modifier(Modifier::BOLD),
+                ),
+                Span::styled(
+                    format!("  {} · {} pets", category.key, pet_count(db, &category.key)),
+                    Style::default().fg(Color::DarkGray),
+                ),
+            ];
+            if merging == Some(category.key.as_str()) {
+                spans.push(Span::styled(
+                    "  merging…",
+                    Style::default().fg(Color::Yellow),
+                ));
+            }
+            ListItem::new(Spans::from(spans))
+        })
+        .collect();
+
+    List::new(items)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .style(Style::default().fg(Color::White))
+                .title("Categories")
+                .border_type(BorderType::Plain),
+        )
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+}
+
+/// The key help, or the result of the last action.
+pub fn render_help<'a>(status: &str, merging: bool) -> Paragraph<'a> {
+    let text = if !status.is_empty() {
+        status.to_string()
+    } else if merging {
+        String::from("pick the category to merge into and press m, Esc cancels")
+    } else {
+        String::from("a add, r rename, m merge, d delete, c color, i icon")
+    };
+    Paragraph::new(Spans::from(vec![Span::raw(text)])).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .style(Style::default().fg(Color::White))
+            .border_type(BorderType::Plain),
+    )
+}
+
+/// The name line for adding or renaming a category.
+pub fn render_name_input<'a>(input: &Input, title: &str, width: u16) -> Paragraph<'a> {
+    let scroll = input.visual_scroll(width.saturating_sub(3) as usize);
+    Paragraph::new(Spans::from(vec![
+        Span::styled("> ", Style::default().fg(Color::Yellow)),
+        Span::raw(input.value().chars().skip(scroll).collect::<String>()),
+    ]))
+    .block(
+        Block::default()
+            .borders(Borders::ALL)
+            .style(Style::default().fg(Color::White).bg(Color::Black))
+            .title(format!("{} (Enter saves, Esc cancels)", title))
+            .border_type(BorderType::Rounded),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Pet;
+    use chrono::prelude::*;
+
+    #[test]
+    fn delete_counts_the_trash() {
+        let mut db = Db {
+            categories: vec![Category::new("Dogs", 0), Category::new("Cats", 1)],
+            ..Db::default()
+        };
+        let mut rex = Pet::new(
+            1,
+            "Rex",
+            "dogs",
+            NaiveDate::from_ymd_opt(2020, 1, 1).unwrap(),
+            false,
+        );
+        rex.deleted_at = Some(Utc::now());
+        db.trash.push(rex);
+        let refused = delete(&mut db, "dogs").unwrap_err().to_string();
+        assert!(refused.contains("1 in the trash"), "{}", refused);
+        assert_eq!(db.categories.len(), 2);
+
+        assert_eq!(delete(&mut db, "cats").unwrap(), "deleted cats");
+        assert_eq!(db.categories.len(), 1);
+    }
+}
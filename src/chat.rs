@@ -0,0 +1,1068 @@
+use chrono::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::ops::RangeInclusive;
+use std::sync::mpsc::{Receiver, Sender};
+use std::thread;
+use tui::{
+    style::{Color, Modifier, Style},
+    text::{Span, Spans},
+};
+use twitchchat::{
+    irc::Tags, messages::Commands, messages::Privmsg, messages::Whisper, Encodable, Encoder,
+    FromIrcMessage, IntoOwned,
+};
+
+use crate::activity::Activity;
+use crate::config::ChatConfig;
+use crate::events::{ChatEvent, EventTotals};
+use crate::filter::Filter;
+use crate::highlight::Highlighter;
+use crate::links::Links;
+use crate::poll::Poll;
+use crate::viewers::Viewers;
+use crate::Event;
+
+const TWITCH_IRC_ADDR: &str = "irc.chat.twitch.tv:6667";
+// anonymous, read-only login that twitch accepts without a token
+const ANONYMOUS_NICK: &str = "justinfan12345";
+const SCROLLBACK_LEN: usize = 500;
+
+// the palette twitch falls back to for users who never picked a color
+const DEFAULT_NICK_COLORS: [(u8, u8, u8); 8] = [
+    (0xFF, 0x00, 0x00),
+    (0x00, 0x00, 0xFF),
+    (0x00, 0x80, 0x00),
+    (0xB2, 0x22, 0x22),
+    (0xFF, 0x7F, 0x50),
+    (0x9A, 0xCD, 0x32),
+    (0xFF, 0x45, 0x00),
+    (0x2E, 0x8B, 0x57),
+];
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum Badge {
+    Broadcaster,
+    Moderator,
+    Vip,
+    Subscriber(String),
+    Other(String),
+}
+
+/// An emote occurrence inside a message, as char indices (end inclusive, like twitch sends them).
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Emote {
+    pub id: String,
+    pub start: usize,
+    pub end: usize,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ChatMessage {
+    pub id: Option<String>,
+    pub channel: String,
+    pub login: String,
+    pub display_name: String,
+    pub color: Option<(u8, u8, u8)>,
+    pub badges: Vec<Badge>,
+    pub emotes: Vec<Emote>,
+    pub text: String,
+    pub action: bool,
+    pub sent_at: DateTime<Utc>,
+    pub tags: BTreeMap<String, String>,
+    #[serde(default)]
+    pub highlight: Option<Color>,
+    #[serde(default)]
+    pub kind: MessageKind,
+    /// removed by a moderator, via CLEARCHAT or CLEARMSG
+    #[serde(default)]
+    pub deleted: bool,
+}
+
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq, Default)]
+pub enum MessageKind {
+    #[default]
+    Chat,
+    /// a line from the server rather than a user, e.g. a timeout or a sub announcement
+    Notice,
+    /// a private message, in either direction
+    Whisper,
+}
+
+/// Chat modes from ROOMSTATE, which only sends the tags that changed after the first one.
+#[derive(Clone, Debug, Default)]
+pub struct RoomState {
+    pub emote_only: bool,
+    pub subs_only: bool,
+    pub r9k: bool,
+    /// seconds between messages, 0 when off
+    pub slow: u64,
+    /// minutes a user must have followed for, `None` when off
+    pub followers_only: Option<u64>,
+}
+
+impl RoomState {
+    fn update(&mut self, tags: &BTreeMap<String, String>) {
+        let flag = |key: &str| tags.get(key).map(|value| value == "1");
+        if let Some(on) = flag("emote-only") {
+            self.emote_only = on;
+        }
+        if let Some(on) = flag("subs-only") {
+            self.subs_only = on;
+        }
+        if let Some(on) = flag("r9k") {
+            self.r9k = on;
+        }
+        if let Some(slow) = tags.get("slow") {
+            self.slow = slow.parse().unwrap_or(0);
+        }
+        if let Some(minutes) = tags.get("followers-only") {
+            // -1 means off, 0 means any follower
+            self.followers_only = minutes.parse().ok();
+        }
+    }
+
+    pub fn describe(&self) -> Vec<String> {
+        let mut modes = vec![];
+        if self.slow > 0 {
+            modes.push(format!("slow {}s", self.slow));
+        }
+        if self.subs_only {
+            modes.push(String::from("sub-only"));
+        }
+        if self.emote_only {
+            modes.push(String::from("emote-only"));
+        }
+        if let Some(minutes) = self.followers_only {
+            modes.push(format!("followers {}m", minutes));
+        }
+        if self.r9k {
+            modes.push(String::from("unique"));
+        }
+        modes
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ChannelKind {
+    Joined,
+    /// collects highlighted messages from every joined channel
+    Mentions,
+    /// what the chat bot answered or refused, see `bot.rs`
+    BotLog,
+    /// a private conversation, named after the other user's login
+    Whisper,
+}
+
+pub struct Channel {
+    pub name: String,
+    pub kind: ChannelKind,
+    pub messages: VecDeque<ChatMessage>,
+    /// messages dropped by the ignore and mute rules since startup
+    pub filtered: usize,
+    pub room: RoomState,
+    /// whether USERSTATE says we can moderate here
+    pub is_mod: bool,
+    /// messages that arrived while another channel was shown, only counted for whispers
+    pub unread: usize,
+    /// index into `messages` while the message list has focus
+    pub selected: Option<usize>,
+    /// the other end of a range running to `selected`, set with `m`
+    pub mark: Option<usize>,
+    pub viewers: Viewers,
+    pub poll: Option<Poll>,
+    pub activity: Activity,
+    pub links: Links,
+    pub events: EventTotals,
+    /// only show subs, raids, announcements and cheers, toggled with `E`
+    pub events_only: bool,
+}
+
+impl Channel {
+    pub fn new(name: &str) -> Channel {
+        Channel {
+            name: name.to_string(),
+            kind: ChannelKind::Joined,
+            messages: VecDeque::with_capacity(SCROLLBACK_LEN),
+            filtered: 0,
+            room: RoomState::default(),
+            is_mod: false,
+            unread: 0,
+            selected: None,
+            mark: None,
+            viewers: Viewers::default(),
+            poll: None,
+            activity: Activity::default(),
+            links: Links::default(),
+            events: EventTotals::default(),
+            events_only: false,
+        }
+    }
+
+    pub fn mentions() -> Channel {
+        Channel {
+            kind: ChannelKind::Mentions,
+            ..Channel::new("Mentions")
+        }
+    }
+
+    pub fn bot_log() -> Channel {
+        Channel {
+            kind: ChannelKind::BotLog,
+            ..Channel::new("Bot log")
+        }
+    }
+
+    pub fn whisper(login: &str) -> Channel {
+        Channel {
+            kind: ChannelKind::Whisper,
+            ..Channel::new(&login.to_lowercase())
+        }
+    }
+
+    pub fn title(&self) -> String {
+        match self.kind {
+            ChannelKind::Joined => format!("#{}", self.name),
+            ChannelKind::Whisper => format!("@{}", self.name),
+            ChannelKind::Mentions | ChannelKind::BotLog => self.name.clone(),
+        }
+    }
+
+    pub fn push(&mut self, msg: ChatMessage) {
+        if self.messages.len() >= SCROLLBACK_LEN {
+            self.messages.pop_front();
+            // keep the selection on the same message as the buffer shifts
+            self.selected = self.selected.and_then(|i| i.checked_sub(1));
+            self.mark = self.mark.and_then(|i| i.checked_sub(1));
+        }
+        self.messages.push_back(msg);
+    }
+
+    /// Whether `msg` is listed, given the events-only filter.
+    pub fn shows(&self, msg: &ChatMessage) -> bool {
+        !self.events_only || ChatEvent::from_message(msg).is_some()
+    }
+
+    /// The newest listed message, where the selection starts.
+    pub fn last_shown(&self) -> Option<usize> {
+        self.messages.iter().rposition(|msg| self.shows(msg))
+    }
+
+    /// The oldest listed message, where Home goes.
+    pub fn first_shown(&self) -> Option<usize> {
+        self.messages.iter().position(|msg| self.shows(msg))
+    }
+
+    /// Moves the selection to the next listed message up or down, staying put at either end.
+    pub fn move_selection(&mut self, forward: bool) {
+        let selected = match self.selected {
+            Some(selected) => selected,
+            None => return,
+        };
+        let next = if forward {
+            self.messages
+                .iter()
+                .enumerate()
+                .skip(selected + 1)
+                .find(|(_, msg)| self.shows(msg))
+                .map(|(index, _)| index)
+        } else {
+            self.messages
+                .range(..selected)
+                .rposition(|msg| self.shows(msg))
+        };
+        if next.is_some() {
+            self.selected = next;
+        }
+    }
+
+    /// Moves the selection `count` listed messages up or down, as far as it goes.
+    pub fn page_selection(&mut self, count: usize, forward: bool) {
+        for _ in 0..count {
+            self.move_selection(forward);
+        }
+    }
+
+    pub fn selected_message(&self) -> Option<&ChatMessage> {
+        self.messages.get(self.selected?)
+    }
+
+    /// The messages between the mark and the selection, both included.
+    pub fn marked_range(&self) -> Option<RangeInclusive<usize>> {
+        let (mark, selected) = (self.mark?, self.selected?);
+        Some(mark.min(selected)..=mark.max(selected))
+    }
+
+    fn delete_where(&mut self, pred: impl Fn(&ChatMessage) -> bool) {
+        for msg in self.messages.iter_mut().filter(|msg| pred(msg)) {
+            msg.deleted = true;
+        }
+    }
+}
+
+impl ChatMessage {
+    /// A plain chat line in #petshop, for tests to adjust as they need.
+    #[cfg(test)]
+    pub fn chat(login: &str, text: &str, sent_at: DateTime<Utc>) -> ChatMessage {
+        ChatMessage {
+            id: None,
+            channel: String::from("petshop"),
+            login: login.to_string(),
+            display_name: login.to_string(),
+            color: None,
+            badges: vec![],
+            emotes: vec![],
+            text: text.to_string(),
+            action: false,
+            sent_at,
+            tags: BTreeMap::new(),
+            highlight: None,
+            kind: MessageKind::Chat,
+            deleted: false,
+        }
+    }
+
+    pub fn from_privmsg(msg: &Privmsg<'_>) -> ChatMessage {
+        let tags = tag_map(&msg.tags());
+        let login = msg.name().to_string();
+        let display_name = tags
+            .get("display-name")
+            .filter(|name| !name.is_empty())
+            .cloned()
+            .unwrap_or_else(|| login.clone());
+        // `/me` messages arrive wrapped in `\x01ACTION ...\x01`, the data field keeps the wrapper
+        let text = msg
+            .data()
+            .trim_start_matches("\u{1}ACTION ")
+            .trim_end_matches('\u{1}');
+
+        ChatMessage {
+            id: tags.get("id").cloned(),
+            channel: msg.channel().trim_start_matches('#').to_string(),
+            login,
+            display_name,
+            color: tags.get("color").and_then(|c| parse_color(c)),
+            badges: tags
+                .get("badges")
+                .map(|b| parse_badges(b))
+                .unwrap_or_default(),
+            emotes: tags
+                .get("emotes")
+                .map(|e| parse_emotes(e))
+                .unwrap_or_default(),
+            text: text.to_string(),
+            action: msg.is_action(),
+            sent_at: tags
+                .get("tmi-sent-ts")
+                .and_then(|ts| parse_timestamp(ts))
+                .unwrap_or_else(Utc::now),
+            tags,
+            highlight: None,
+            kind: MessageKind::Chat,
+            deleted: false,
+        }
+    }
+
+    pub fn notice(channel: &str, text: String, tags: BTreeMap<String, String>) -> ChatMessage {
+        ChatMessage {
+            id: tags.get("id").cloned(),
+            channel: channel.trim_start_matches('#').to_string(),
+            // USERNOTICEs say who subbed or raided in their tags
+            login: tags.get("login").cloned().unwrap_or_default(),
+            display_name: tags.get("display-name").cloned().unwrap_or_default(),
+            color: None,
+            badges: vec![],
+            emotes: vec![],
+            text,
+            action: false,
+            sent_at: tags
+                .get("tmi-sent-ts")
+                .and_then(|ts| parse_timestamp(ts))
+                .unwrap_or_else(Utc::now),
+            tags,
+            highlight: None,
+            kind: MessageKind::Notice,
+            deleted: false,
+        }
+    }
+
+    pub fn from_whisper(whisper: &Whisper<'_>) -> ChatMessage {
+        let tags = tag_map(&whisper.tags());
+        let login = whisper.name().to_string();
+        ChatMessage {
+            id: tags.get("message-id").cloned(),
+            // the conversation is named after whoever we're talking to
+            channel: login.clone(),
+            display_name: tags
+                .get("display-name")
+                .filter(|name| !name.is_empty())
+                .cloned()
+                .unwrap_or_else(|| login.clone()),
+            login,
+            color: tags.get("color").and_then(|c| parse_color(c)),
+            badges: tags
+                .get("badges")
+                .map(|b| parse_badges(b))
+                .unwrap_or_default(),
+            emotes: tags
+                .get("emotes")
+                .map(|e| parse_emotes(e))
+                .unwrap_or_default(),
+            text: whisper.data().to_string(),
+            action: false,
+            sent_at: Utc::now(),
+            tags,
+            highlight: None,
+            kind: MessageKind::Whisper,
+            deleted: false,
+        }
+    }
+
+    /// The user's chosen color, or a stable pick from twitch's defaults if they never set one.
+    pub fn nick_color(&self) -> Color {
+        let (r, g, b) = self.color.unwrap_or_else(|| {
+            let sum: usize = self.login.bytes().map(usize::from).sum();
+            DEFAULT_NICK_COLORS[sum % DEFAULT_NICK_COLORS.len()]
+        });
+        Color::Rgb(r, g, b)
+    }
+}
+
+pub fn parse_line(line: &str) -> Option<Commands<'static>> {
+    // the parser only accepts terminated lines, which `BufRead::lines` strips
+    let line = format!("{}\r\n", line.trim_end());
+    let msg = twitchchat::irc::parse(&line).next()?.ok()?;
+    Commands::from_irc(msg).ok().map(IntoOwned::into_owned)
+}
+
+/// Route a raw line from the server to the channel it belongs to.
+///
+/// Returns the chat message, USERNOTICE or whisper it added, if any. Highlighted
+/// messages are also copied into the Mentions channel, and whispers open a
+/// conversation in the sidebar the first time someone sends one.
+pub fn handle_line(
+    channels: &mut Vec<Channel>,
+    highlighter: &Highlighter,
+    filter: &Filter,
+    line: &str,
+) -> Option<ChatMessage> {
+    let mut msg = match parse_line(line)? {
+        Commands::Privmsg(msg) => ChatMessage::from_privmsg(&msg),
+        Commands::Whisper(whisper) => {
+            let msg = ChatMessage::from_whisper(&whisper);
+            let channel = match channels
+                .iter()
+                .position(|c| c.kind == ChannelKind::Whisper && c.name == msg.channel)
+            {
+                Some(index) => &mut channels[index],
+                None => {
+                    channels.push(Channel::whisper(&msg.channel));
+                    channels.last_mut().expect("just pushed")
+                }
+            };
+            channel.unread += 1;
+            channel.push(msg.clone());
+            return Some(msg);
+        }
+        Commands::ClearChat(clear) => {
+            let channel = joined_mut(channels, clear.channel())?;
+            let text = match (clear.name(), clear.ban_duration()) {
+                (Some(user), Some(secs)) => format!("{} was timed out for {}s", user, secs),
+                (Some(user), None) => format!("{} was banned", user),
+                (None, _) => String::from("chat was cleared by a moderator"),
+            };
+            let name = channel.name.clone();
+            match clear.name() {
+                Some(user) => delete_where(channels, &name, |msg| msg.login == user),
+                None => delete_where(channels, &name, |_| true),
+            }
+            let tags = tag_map(&clear.tags());
+            let channel = joined_mut(channels, &name)?;
+            channel.push(ChatMessage::notice(&name, text, tags));
+            return None;
+        }
+        Commands::ClearMsg(clear) => {
+            let name = joined_mut(channels, clear.channel())?.name.clone();
+            let target = clear.target_msg_id()?;
+            delete_where(channels, &name, |msg| msg.id.as_deref() == Some(target));
+            return None;
+        }
+        Commands::UserNotice(notice) => {
+            let tags = tag_map(&notice.tags());
+            let mut text = tags.get("system-msg").cloned().unwrap_or_default();
+            if let Some(message) = notice.message() {
+                text = format!("{} \"{}\"", text, message);
+            }
+            let channel = joined_mut(channels, notice.channel())?;
+            let msg = ChatMessage::notice(&channel.name.clone(), text, tags);
+            channel.events.record(&msg);
+            channel.push(msg.clone());
+            return Some(msg);
+        }
+        Commands::Join(join) => {
+            joined_mut(channels, join.channel())?
+                .viewers
+                .join(join.name());
+            return None;
+        }
+        Commands::Part(part) => {
+            joined_mut(channels, part.channel())?
+                .viewers
+                .part(part.name());
+            return None;
+        }
+        // RPL_NAMREPLY: `:server 353 nick = #channel :login login ...`
+        Commands::Raw(raw) if raw.get_command() == "353" => {
+            let channel = joined_mut(channels, raw.nth_arg(2)?)?;
+            for login in raw.get_data()?.split_whitespace() {
+                channel.viewers.join(login);
+            }
+            return None;
+        }
+        Commands::RoomState(state) => {
+            let tags = tag_map(&state.tags());
+            joined_mut(channels, state.channel())?.room.update(&tags);
+            return None;
+        }
+        Commands::UserState(state) => {
+            let badges = state
+                .tags()
+                .get("badges")
+                .map(parse_badges)
+                .unwrap_or_default();
+            joined_mut(channels, state.channel())?.is_mod = badges
+                .iter()
+                .any(|badge| matches!(badge, Badge::Moderator | Badge::Broadcaster));
+            return None;
+        }
+        _ => return None,
+    };
+    let channel = joined_mut(channels, &msg.channel)?;
+    // ignored users don't count towards the viewers or the stats either
+    if filter.is_filtered(&msg) {
+        channel.filtered += 1;
+        return None;
+    }
+    channel.viewers.seen(&msg);
+    channel.activity.record(&msg);
+    channel.events.record(&msg);
+    channel.links.collect(&msg);
+    msg.highlight = highlighter.check(&msg);
+    channel.push(msg.clone());
+
+    if msg.highlight.is_some() {
+        push_to(channels, ChannelKind::Mentions, msg.clone());
+    }
+    Some(msg)
+}
+
+/// Appends to the first virtual channel of `kind`, if it's in the sidebar.
+pub fn push_to(channels: &mut [Channel], kind: ChannelKind, msg: ChatMessage) {
+    if let Some(channel) = channels.iter_mut().find(|c| c.kind == kind) {
+        channel.push(msg);
+    }
+}
+
+/// Marks the messages of the joined channel `name` that match `pred` deleted, along
+/// with their copies in Mentions.
+fn delete_where(channels: &mut [Channel], name: &str, pred: impl Fn(&ChatMessage) -> bool) {
+    for channel in channels.iter_mut() {
+        match channel.kind {
+            ChannelKind::Joined if channel.name == name => channel.delete_where(&pred),
+            ChannelKind::Mentions => channel.delete_where(|msg| msg.channel == name && pred(msg)),
+            _ => {}
+        }
+    }
+}
+
+pub fn joined_mut<'a>(channels: &'a mut [Channel], name: &str) -> Option<&'a mut Channel> {
+    let name = name.trim_start_matches('#');
+    channels
+        .iter_mut()
+        .find(|c| c.kind == ChannelKind::Joined && c.name == name)
+}
+
+fn tag_map(tags: &Tags<'_>) -> BTreeMap<String, String> {
+    tags.iter()
+        .filter_map(|(key, _)| {
+            tags.get_unescaped(&key)
+                .map(|value| (key.to_string(), value.to_string()))
+        })
+        .collect()
+}
+
+pub fn parse_color(input: &str) -> Option<(u8, u8, u8)> {
+    let hex = input.strip_prefix('#')?;
+    if hex.len() != 6 {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+    Some((channel(0)?, channel(2)?, channel(4)?))
+}
+
+pub fn parse_badges(input: &str) -> Vec<Badge> {
+    input
+        .split(',')
+        .filter_map(|badge| {
+            let mut parts = badge.splitn(2, '/');
+            let name = parts.next().filter(|name| !name.is_empty())?;
+            let version = parts.next().unwrap_or_default();
+            Some(match name {
+                "broadcaster" => Badge::Broadcaster,
+                "moderator" => Badge::Moderator,
+                "vip" => Badge::Vip,
+                "subscriber" | "founder" => Badge::Subscriber(version.to_string()),
+                other => Badge::Other(other.to_string()),
+            })
+        })
+        .collect()
+}
+
+/// Parses the `emotes` tag (`id:0-4,6-10/id2:12-16`) into a list sorted by position.
+pub fn parse_emotes(input: &str) -> Vec<Emote> {
+    let mut emotes: Vec<Emote> = input
+        .split('/')
+        .filter_map(|emote| {
+            let mut parts = emote.splitn(2, ':');
+            let id = parts.next().filter(|id| !id.is_empty())?;
+            let ranges = parts.next()?;
+            Some(ranges.split(',').filter_map(move |range| {
+                let mut bounds = range.splitn(2, '-');
+                let start = bounds.next()?.parse().ok()?;
+                let end = bounds.next()?.parse().ok()?;
+                Some(Emote {
+                    id: id.to_string(),
+                    start,
+                    end,
+                })
+            }))
+        })
+        .flatten()
+        .filter(|emote| emote.start <= emote.end)
+        .collect();
+    emotes.sort_by_key(|emote| emote.start);
+    emotes
+}
+
+pub fn parse_timestamp(input: &str) -> Option<DateTime<Utc>> {
+    let millis: i64 = input.parse().ok()?;
+    Utc.timestamp_millis_opt(millis).single()
+}
+
+/// The glyph and color a badge is drawn with, `None` for the ones we don't show.
+pub fn badge_style(badge: &Badge) -> Option<(&'static str, Color)> {
+    match badge {
+        Badge::Broadcaster => Some(("◉", Color::Red)),
+        Badge::Moderator => Some(("⚔", Color::Green)),
+        Badge::Vip => Some(("◆", Color::Magenta)),
+        Badge::Subscriber(_) => Some(("★", Color::LightMagenta)),
+        Badge::Other(_) => None,
+    }
+}
+
+fn badge_glyph(badge: &Badge) -> Option<Span<'static>> {
+    let (glyph, color) = badge_style(badge)?;
+    Some(Span::styled(glyph, Style::default().fg(color)))
+}
+
+/// Splits the message text on its emote ranges, replacing each emote with a styled placeholder.
+fn render_text(msg: &ChatMessage, style: Style) -> Vec<Span<'static>> {
+    let chars: Vec<char> = msg.text.chars().collect();
+    let emote_style = Style::default()
+        .fg(Color::LightYellow)
+        .add_modifier(Modifier::ITALIC);
+    let mut spans = vec![];
+    let mut pos = 0;
+    for emote in &msg.emotes {
+        if emote.start < pos || emote.end >= chars.len() {
+            continue;
+        }
+        if emote.start > pos {
+            spans.push(Span::styled(
+                chars[pos..emote.start].iter().collect::<String>(),
+                style,
+            ));
+        }
+        let name: String = chars[emote.start..=emote.end].iter().collect();
+        spans.push(Span::styled(format!("[{}]", name), emote_style));
+        pos = emote.end + 1;
+    }
+    if pos < chars.len() {
+        spans.push(Span::styled(chars[pos..].iter().collect::<String>(), style));
+    }
+    spans
+}
+
+/// Renders one scrollback line; deleted messages are struck through, or replaced when `hide_deleted`.
+pub fn render_message(msg: &ChatMessage, hide_deleted: bool) -> Spans<'static> {
+    let mut spans = vec![Span::styled(
+        msg.sent_at
+            .with_timezone(&Local)
+            .format("%H:%M ")
+            .to_string(),
+        Style::default().fg(Color::DarkGray),
+    )];
+    if msg.kind == MessageKind::Notice {
+        spans.push(Span::styled(
+            msg.text.clone(),
+            Style::default()
+                .fg(Color::Gray)
+                .add_modifier(Modifier::ITALIC),
+        ));
+        return Spans::from(spans);
+    }
+
+    let nick_style = Style::default()
+        .fg(msg.nick_color())
+        .add_modifier(Modifier::BOLD);
+    for glyph in msg.badges.iter().filter_map(badge_glyph) {
+        spans.push(glyph);
+    }
+    if !msg.badges.is_empty() {
+        spans.push(Span::raw(" "));
+    }
+    spans.push(Span::styled(msg.display_name.clone(), nick_style));
+    spans.push(Span::raw(if msg.action { " " } else { ": " }));
+    if msg.deleted && hide_deleted {
+        spans.push(Span::styled(
+            "<message deleted>",
+            Style::default().fg(Color::DarkGray),
+        ));
+        return Spans::from(spans);
+    }
+
+    let text_start = spans.len();
+    if msg.action {
+        spans.extend(render_text(
+            msg,
+            Style::default()
+                .fg(msg.nick_color())
+                .add_modifier(Modifier::ITALIC),
+        ));
+    } else {
+        spans.extend(render_text(msg, Style::default()));
+    }
+    if msg.deleted {
+        for span in &mut spans[text_start..] {
+            span.style = span.style.add_modifier(Modifier::CROSSED_OUT);
+        }
+    }
+    Spans::from(spans)
+}
+
+/// Encodes a twitchchat command into the raw line the connection thread writes out.
+pub fn encode<M: Encodable>(cmd: M) -> String {
+    let mut encoder = Encoder::new(Vec::new());
+    encoder.encode(cmd).expect("encoding into a Vec can't fail");
+    String::from_utf8_lossy(&encoder.into_inner()).into_owned()
+}
+
+/// Where raw IRC lines come from: the live twitch connection or a recording.
+pub trait ChatSource: Send {
+    /// Forwards every line to the main loop as `Event::Chat` until the source runs dry.
+    ///
+    /// Lines sent on `outgoing` are chat commands for the server, sources that
+    /// can't deliver them drop the receiver so the sender sees an error.
+    fn run(
+        &self,
+        tx: &Sender<Event<crossterm::event::KeyEvent>>,
+        outgoing: Receiver<String>,
+    ) -> io::Result<()>;
+}
+
+pub struct TwitchIrc {
+    pub config: ChatConfig,
+}
+
+impl ChatSource for TwitchIrc {
+    fn run(
+        &self,
+        tx: &Sender<Event<crossterm::event::KeyEvent>>,
+        outgoing: Receiver<String>,
+    ) -> io::Result<()> {
+        run_connection(&self.config, tx, outgoing)
+    }
+}
+
+/// Reads from twitch until the connection drops, forwarding every line to the main loop.
+///
+/// Lines sent on `outgoing` are written to the server as-is. Logs in anonymously
+/// (read-only) unless both `nick` and `oauth` are configured.
+fn run_connection(
+    config: &ChatConfig,
+    tx: &Sender<Event<crossterm::event::KeyEvent>>,
+    outgoing: Receiver<String>,
+) -> io::Result<()> {
+    let mut stream = TcpStream::connect(TWITCH_IRC_ADDR)?;
+    write!(
+        stream,
+        "CAP REQ :twitch.tv/tags twitch.tv/commands twitch.tv/membership\r\n"
+    )?;
+    match (&config.nick, &config.oauth) {
+        (Some(nick), Some(oauth)) => {
+            write!(stream, "PASS {}\r\n", oauth)?;
+            write!(stream, "NICK {}\r\n", nick.to_lowercase())?;
+        }
+        _ => {
+            write!(stream, "PASS SCHMOOPIIE\r\n")?;
+            write!(stream, "NICK {}\r\n", ANONYMOUS_NICK)?;
+        }
+    }
+    for channel in &config.channels {
+        write!(stream, "JOIN #{}\r\n", channel)?;
+    }
+
+    let mut writer = stream.try_clone()?;
+    thread::spawn(move || {
+        for line in outgoing {
+            if writer.write_all(line.as_bytes()).is_err() {
+                break;
+            }
+        }
+    });
+
+    let reader = BufReader::new(stream.try_clone()?);
+    for line in reader.lines() {
+        let line = line?;
+        if let Some(payload) = line.strip_prefix("PING ") {
+            write!(stream, "PONG {}\r\n", payload)?;
+            continue;
+        }
+        if tx.send(Event::Chat(line)).is_err() {
+            break;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn privmsg(line: &str) -> ChatMessage {
+        match parse_line(line) {
+            Some(Commands::Privmsg(msg)) => ChatMessage::from_privmsg(&msg),
+            _ => panic!("not a PRIVMSG: {}", line),
+        }
+    }
+
+    fn text_of(msg: &ChatMessage) -> String {
+        render_text(msg, Style::default())
+            .iter()
+            .map(|span| span.content.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn full_tags() {
+        let msg = privmsg(
+            "@badge-info=subscriber/14;badges=broadcaster/1,subscriber/12,glhf-pledge/1;color=#1E90FF;\
+             display-name=Pet_Shop;emotes=25:0-4,12-16/1902:6-10;id=abc-123;tmi-sent-ts=1600000000000 \
+             :pet_shop!pet_shop@pet_shop.tmi.twitch.tv PRIVMSG #petshop :Kappa Keepo Kappa",
+        );
+        assert_eq!(msg.channel, "petshop");
+        assert_eq!(msg.login, "pet_shop");
+        assert_eq!(msg.display_name, "Pet_Shop");
+        assert_eq!(msg.id.as_deref(), Some("abc-123"));
+        assert_eq!(msg.color, Some((0x1E, 0x90, 0xFF)));
+        assert_eq!(
+            msg.badges,
+            vec![
+                Badge::Broadcaster,
+                Badge::Subscriber(String::from("12")),
+                Badge::Other(String::from("glhf-pledge")),
+            ]
+        );
+        let ranges: Vec<_> = msg
+            .emotes
+            .iter()
+            .map(|e| (e.id.as_str(), e.start, e.end))
+            .collect();
+        assert_eq!(ranges, [("25", 0, 4), ("1902", 6, 10), ("25", 12, 16)]);
+        assert_eq!(
+            msg.sent_at,
+            Utc.timestamp_millis_opt(1_600_000_000_000).unwrap()
+        );
+        assert_eq!(text_of(&msg), "[Kappa] [Keepo] [Kappa]");
+    }
+
+    #[test]
+    fn missing_tags() {
+        let before = Utc::now();
+        let msg = privmsg(":viewer!viewer@viewer.tmi.twitch.tv PRIVMSG #petshop :hello");
+        assert_eq!(msg.display_name, "viewer");
+        assert_eq!(msg.id, None);
+        assert_eq!(msg.color, None);
+        assert!(msg.badges.is_empty());
+        assert!(msg.emotes.is_empty());
+        // no tmi-sent-ts, so it's stamped on arrival
+        assert!(msg.sent_at >= before);
+        assert!(!msg.action);
+    }
+
+    #[test]
+    fn empty_tags() {
+        let msg = privmsg(
+            "@badges=;color=;display-name=;emotes=;tmi-sent-ts= \
+             :viewer!viewer@viewer.tmi.twitch.tv PRIVMSG #petshop :\u{1}ACTION waves\u{1}",
+        );
+        assert_eq!(msg.display_name, "viewer");
+        assert_eq!(msg.color, None);
+        assert!(msg.badges.is_empty());
+        assert!(msg.emotes.is_empty());
+        assert!(msg.action);
+        assert_eq!(msg.text, "waves");
+    }
+
+    #[test]
+    fn malformed_tags() {
+        let msg = privmsg(
+            "@badges=/1,,moderator/1,vip;color=#GGHHII;emotes=25:abc-def/:0-4/33/44:3-1;tmi-sent-ts=soon \
+             :viewer!viewer@viewer.tmi.twitch.tv PRIVMSG #petshop :hello there",
+        );
+        assert_eq!(msg.color, None);
+        assert_eq!(msg.badges, vec![Badge::Moderator, Badge::Vip]);
+        assert!(msg.emotes.is_empty());
+        assert_eq!(text_of(&msg), "hello there");
+    }
+
+    #[test]
+    fn escaped_tag_values() {
+        let msg = privmsg(
+            "@display-name=Pet\\sShop;id=1 :pet_shop!pet_shop@pet_shop.tmi.twitch.tv PRIVMSG #petshop :hi",
+        );
+        assert_eq!(msg.display_name, "Pet Shop");
+    }
+
+    #[test]
+    fn emotes_count_chars_not_bytes() {
+        // "ünï " is 4 chars but 6 bytes, twitch counts the chars
+        let msg = privmsg(
+            "@emotes=25:4-8 :viewer!viewer@viewer.tmi.twitch.tv PRIVMSG #petshop :ünï Kappa ✓",
+        );
+        assert_eq!(text_of(&msg), "ünï [Kappa] ✓");
+    }
+
+    #[test]
+    fn emotes_out_of_range_are_ignored() {
+        let msg = privmsg(
+            "@emotes=25:0-1,40-44/1902:3-100 :viewer!viewer@viewer.tmi.twitch.tv PRIVMSG #petshop :hé hi",
+        );
+        assert_eq!(msg.emotes.len(), 3);
+        assert_eq!(text_of(&msg), "[hé] hi");
+    }
+
+    #[test]
+    fn color_and_timestamp() {
+        assert_eq!(parse_color("#00ff7F"), Some((0x00, 0xFF, 0x7F)));
+        assert_eq!(parse_color("00FF7F"), None);
+        assert_eq!(parse_color("#00FF7"), None);
+        assert_eq!(parse_color("#ééé"), None);
+        assert_eq!(
+            parse_timestamp("0"),
+            Some(Utc.timestamp_millis_opt(0).unwrap())
+        );
+        assert_eq!(parse_timestamp("-"), None);
+        assert_eq!(parse_timestamp(""), None);
+    }
+
+    fn config() -> ChatConfig {
+        let mut config = ChatConfig {
+            nick: Some(String::from("petshop")),
+            ..ChatConfig::default()
+        };
+        config.filters.global.ignored_users = vec![String::from("spammer")];
+        config
+    }
+
+    fn handle(channels: &mut Vec<Channel>, config: &ChatConfig, line: &str) -> Option<ChatMessage> {
+        let highlighter = Highlighter::new(config).unwrap();
+        let filter = Filter::new(&config.filters).unwrap();
+        handle_line(channels, &highlighter, &filter, line)
+    }
+
+    fn deleted(channel: &Channel) -> Vec<(String, bool)> {
+        channel
+            .messages
+            .iter()
+            .filter(|msg| msg.kind == MessageKind::Chat)
+            .map(|msg| (msg.login.clone(), msg.deleted))
+            .collect()
+    }
+
+    #[test]
+    fn ignored_users_are_not_counted() {
+        let config = config();
+        let mut channels = vec![Channel::new("petshop")];
+        let line = |login: &str| {
+            format!(
+                "@id={0}-1;tmi-sent-ts=1600000000000 :{0}!{0}@{0}.tmi.twitch.tv PRIVMSG #petshop :Kappa",
+                login
+            )
+        };
+        assert!(handle(&mut channels, &config, &line("spammer")).is_none());
+        assert!(handle(&mut channels, &config, &line("viewer")).is_some());
+        let channel = &channels[0];
+        assert_eq!(channel.filtered, 1);
+        assert_eq!(channel.messages.len(), 1);
+        assert_eq!(channel.viewers.count(), 1);
+        assert_eq!(channel.activity.top_chatters(5), [("viewer", 1)]);
+    }
+
+    #[test]
+    fn clears_reach_the_mentions_copies() {
+        let config = config();
+        let mut channels = vec![
+            Channel::mentions(),
+            Channel::new("petshop"),
+            Channel::new("other"),
+        ];
+        let line = |id: &str, login: &str, channel: &str| {
+            format!(
+                "@id={};tmi-sent-ts=1600000000000 :{1}!{1}@{1}.tmi.twitch.tv PRIVMSG #{2} :hi petshop",
+                id, login, channel
+            )
+        };
+        for (id, login, channel) in [
+            ("a", "alice", "petshop"),
+            ("b", "bob", "petshop"),
+            ("c", "bob", "petshop"),
+            ("d", "bob", "other"),
+        ] {
+            handle(&mut channels, &config, &line(id, login, channel));
+        }
+        assert_eq!(channels[0].messages.len(), 4);
+
+        handle(
+            &mut channels,
+            &config,
+            "@target-msg-id=a;tmi-sent-ts=1600000001000 :tmi.twitch.tv CLEARMSG #petshop :hi petshop",
+        );
+        handle(
+            &mut channels,
+            &config,
+            "@ban-duration=60;tmi-sent-ts=1600000002000 :tmi.twitch.tv CLEARCHAT #petshop :bob",
+        );
+        let expected = |login: &str, deleted| (String::from(login), deleted);
+        assert_eq!(
+            deleted(&channels[0]),
+            [
+                expected("alice", true),
+                expected("bob", true),
+                expected("bob", true),
+                expected("bob", false),
+            ]
+        );
+        assert_eq!(
+            deleted(&channels[1]),
+            [
+                expected("alice", true),
+                expected("bob", true),
+                expected("bob", true)
+            ]
+        );
+        assert_eq!(deleted(&channels[2]), [expected("bob", false)]);
+    }
+}
@@ -0,0 +1,201 @@
+use chrono::NaiveDate;
+use std::path::PathBuf;
+
+use crate::export::ExportFormat;
+use crate::Error;
+
+const USAGE: &str = "usage: rust-commandline-example [--replay <file> [--speed <n>]] [--record <file>]
+       rust-commandline-example export <channel> [--format md|html|json] [--since YYYY-MM-DD] [--until YYYY-MM-DD] [--out <file>]
+       rust-commandline-example pets history [<id>]
+       rust-commandline-example pets restore <id>
+       rust-commandline-example pets query <query>
+       rust-commandline-example bench [--pets <n>] [--frames <n>] [--budget-ms <n>]";
+
+/// What to do, picked by the first argument.
+pub enum Command {
+    Tui(Options),
+    Export(ExportOptions),
+    Pets(PetsCommand),
+    Bench(BenchOptions),
+}
+
+/// `pets`: the pet store from the command line.
+pub enum PetsCommand {
+    /// the audit log, oldest first, of one pet if an id is given
+    History(Option<usize>),
+    /// bring back a deleted pet by id
+    Restore(usize),
+    /// the pets matching a query, e.g. `category = cats and age >= 3 order by created_at desc`
+    Query(String),
+}
+
+/// `bench`: how long the Pets tab takes to draw with a big store.
+pub struct BenchOptions {
+    /// how many pets to make up
+    pub pets: usize,
+    pub frames: usize,
+    /// the slowest frame allowed, in milliseconds
+    pub budget_ms: u64,
+}
+
+/// Flags the TUI was started with.
+pub struct Options {
+    /// run the Channels tab from a recording instead of twitch
+    pub replay: Option<PathBuf>,
+    /// playback speed for `replay`, 0 for as fast as possible
+    pub speed: f64,
+    /// save every received IRC line here
+    pub record: Option<PathBuf>,
+}
+
+/// `export`: a transcript of a channel's persisted logs.
+pub struct ExportOptions {
+    pub channel: String,
+    pub format: ExportFormat,
+    pub since: Option<NaiveDate>,
+    pub until: Option<NaiveDate>,
+    /// stdout when not given
+    pub out: Option<PathBuf>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            replay: None,
+            speed: 1.0,
+            record: None,
+        }
+    }
+}
+
+fn usage_error(problem: String) -> Error {
+    Error::CommandError(format!("{}\n{}", problem, USAGE))
+}
+
+/// Parses the arguments after the program name.
+pub fn parse_args(args: impl IntoIterator<Item = String>) -> Result<Command, Error> {
+    let mut args = args.into_iter().peekable();
+    if args.peek().map(String::as_str) == Some("export") {
+        args.next();
+        return parse_export(args).map(Command::Export);
+    }
+    if args.peek().map(String::as_str) == Some("pets") {
+        args.next();
+        return parse_pets(args).map(Command::Pets);
+    }
+    if args.peek().map(String::as_str) == Some("bench") {
+        args.next();
+        return parse_bench(args).map(Command::Bench);
+    }
+
+    let mut options = Options::default();
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| usage_error(format!("{} needs a value", arg)))
+        };
+        match arg.as_str() {
+            "--replay" => options.replay = Some(PathBuf::from(value()?)),
+            "--record" => options.record = Some(PathBuf::from(value()?)),
+            "--speed" => {
+                let speed = value()?;
+                options.speed = speed
+                    .parse()
+                    .ok()
+                    .filter(|speed: &f64| *speed >= 0.0)
+                    .ok_or_else(|| usage_error(format!("'{}' isn't a speed", speed)))?;
+            }
+            "-h" | "--help" => return Err(Error::CommandError(USAGE.to_string())),
+            other => return Err(usage_error(format!("unknown argument {}", other))),
+        }
+    }
+    Ok(Command::Tui(options))
+}
+
+fn parse_pets(args: impl Iterator<Item = String>) -> Result<PetsCommand, Error> {
+    let args: Vec<String> = args.collect();
+    let id = |value: &str| {
+        value
+            .trim_start_matches('#')
+            .parse::<usize>()
+            .map_err(|_| usage_error(format!("'{}' isn't a pet id", value)))
+    };
+    match args
+        .iter()
+        .map(String::as_str)
+        .collect::<Vec<_>>()
+        .as_slice()
+    {
+        ["history"] => Ok(PetsCommand::History(None)),
+        ["history", pet] => Ok(PetsCommand::History(Some(id(pet)?))),
+        ["restore", pet] => Ok(PetsCommand::Restore(id(pet)?)),
+        // the shell splits an unquoted query into words, put it back together
+        ["query", query @ ..] if !query.is_empty() => Ok(PetsCommand::Query(query.join(" "))),
+        _ => Err(usage_error(String::from(
+            "pets needs history [<id>], restore <id> or query <query>",
+        ))),
+    }
+}
+
+fn parse_bench(mut args: impl Iterator<Item = String>) -> Result<BenchOptions, Error> {
+    let mut options = BenchOptions {
+        pets: 100_000,
+        frames: 200,
+        budget_ms: 16,
+    };
+    let number = |value: String| {
+        value
+            .parse::<usize>()
+            .map_err(|_| usage_error(format!("'{}' isn't a number", value)))
+    };
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| usage_error(format!("{} needs a value", arg)))
+        };
+        match arg.as_str() {
+            "--pets" => options.pets = number(value()?)?,
+            "--frames" => options.frames = number(value()?)?.max(1),
+            "--budget-ms" => options.budget_ms = number(value()?)? as u64,
+            other => return Err(usage_error(format!("unknown argument {}", other))),
+        }
+    }
+    Ok(options)
+}
+
+fn parse_export(mut args: impl Iterator<Item = String>) -> Result<ExportOptions, Error> {
+    let mut options = ExportOptions {
+        channel: String::new(),
+        format: ExportFormat::Markdown,
+        since: None,
+        until: None,
+        out: None,
+    };
+    let date = |value: String| {
+        NaiveDate::parse_from_str(&value, "%Y-%m-%d")
+            .map_err(|_| usage_error(format!("'{}' isn't a YYYY-MM-DD date", value)))
+    };
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| usage_error(format!("{} needs a value", arg)))
+        };
+        match arg.as_str() {
+            "--format" => options.format = ExportFormat::from_name(&value()?)?,
+            "--since" => options.since = Some(date(value()?)?),
+            "--until" => options.until = Some(date(value()?)?),
+            "--out" => options.out = Some(PathBuf::from(value()?)),
+            other if other.starts_with('-') => {
+                return Err(usage_error(format!("unknown argument {}", other)))
+            }
+            channel if options.channel.is_empty() => {
+                options.channel = channel.trim_start_matches('#').to_lowercase()
+            }
+            other => return Err(usage_error(format!("unexpected argument {}", other))),
+        }
+    }
+    if options.channel.is_empty() {
+        return Err(usage_error(String::from("export needs a channel")));
+    }
+    Ok(options)
+}
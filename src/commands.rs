@@ -0,0 +1,136 @@
+use crate::Error;
+
+/// the longest a poll can run, a day
+const MAX_POLL_SECS: u64 = 24 * 60 * 60;
+
+/// Slash commands typed into the Channels prompt.
+#[derive(Debug, PartialEq)]
+pub enum ChatCommand {
+    Ignore {
+        user: String,
+        global: bool,
+    },
+    Unignore {
+        user: String,
+        global: bool,
+    },
+    Mute {
+        pattern: String,
+        global: bool,
+    },
+    Unmute {
+        pattern: String,
+        global: bool,
+    },
+    ToggleBots,
+    /// flip the triggers between sending and only logging
+    DryRun,
+    Poll {
+        secs: u64,
+        question: String,
+        options: Vec<String>,
+    },
+    /// close the running poll early, or dismiss the results of a closed one
+    EndPoll,
+}
+
+impl ChatCommand {
+    pub fn parse(line: &str) -> Result<ChatCommand, Error> {
+        let line = line.trim();
+        let rest = line
+            .strip_prefix('/')
+            .ok_or_else(|| Error::CommandError(String::from("commands start with '/'")))?;
+        let (name, args) = split_word(rest);
+        let (global, args) = match args.strip_prefix("-g") {
+            Some(args) if args.is_empty() || args.starts_with(' ') => (true, args.trim()),
+            _ => (false, args),
+        };
+        let arg = |usage: &str| {
+            if args.is_empty() {
+                Err(Error::CommandError(format!(
+                    "usage: /{} [-g] {}",
+                    name, usage
+                )))
+            } else {
+                Ok(args.to_string())
+            }
+        };
+
+        Ok(match name {
+            "ignore" => ChatCommand::Ignore {
+                user: arg("<user>")?.to_lowercase(),
+                global,
+            },
+            "unignore" => ChatCommand::Unignore {
+                user: arg("<user>")?.to_lowercase(),
+                global,
+            },
+            "mute" => ChatCommand::Mute {
+                pattern: arg("<regex>")?,
+                global,
+            },
+            "unmute" => ChatCommand::Unmute {
+                pattern: arg("<regex>")?,
+                global,
+            },
+            "bots" => ChatCommand::ToggleBots,
+            "dryrun" => ChatCommand::DryRun,
+            "poll" => {
+                let usage = || {
+                    Error::CommandError(String::from(
+                        "usage: /poll <seconds, at most 86400> <question> | <option> | <option>...",
+                    ))
+                };
+                let (secs, rest) = split_word(args);
+                let secs = secs
+                    .parse()
+                    .ok()
+                    .filter(|secs| *secs <= MAX_POLL_SECS)
+                    .ok_or_else(usage)?;
+                let mut parts = rest.split('|').map(str::trim);
+                let question = parts.next().filter(|q| !q.is_empty()).ok_or_else(usage)?;
+                let options: Vec<String> = parts
+                    .filter(|option| !option.is_empty())
+                    .map(String::from)
+                    .collect();
+                if options.len() < 2 {
+                    return Err(usage());
+                }
+                ChatCommand::Poll {
+                    secs,
+                    question: question.to_string(),
+                    options,
+                }
+            }
+            "endpoll" => ChatCommand::EndPoll,
+            other => return Err(Error::CommandError(format!("unknown command /{}", other))),
+        })
+    }
+}
+
+fn split_word(input: &str) -> (&str, &str) {
+    match input.find(' ') {
+        Some(pos) => (&input[..pos], input[pos..].trim()),
+        None => (input, ""),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn polls_last_at_most_a_day() {
+        assert_eq!(
+            ChatCommand::parse("/poll 86400 Best pet? | cats | dogs").unwrap(),
+            ChatCommand::Poll {
+                secs: MAX_POLL_SECS,
+                question: String::from("Best pet?"),
+                options: vec![String::from("cats"), String::from("dogs")],
+            }
+        );
+        assert!(ChatCommand::parse("/poll 86401 Best pet? | cats | dogs").is_err());
+        assert!(ChatCommand::parse("/poll 18446744073709551615 Best pet? | cats | dogs").is_err());
+        assert!(ChatCommand::parse("/poll 18446744073709551616 Best pet? | cats | dogs").is_err());
+    }
+}
@@ -0,0 +1,233 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+
+use crate::Error;
+
+const CONFIG_PATH: &str = "./data/chat.json";
+
+/// Everything the Channels tab persists between runs.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ChatConfig {
+    #[serde(default)]
+    pub nick: Option<String>,
+    /// `oauth:...` token for `nick`, without one we connect read-only
+    #[serde(default)]
+    pub oauth: Option<String>,
+    #[serde(default = "default_channels")]
+    pub channels: Vec<String>,
+    #[serde(default)]
+    pub highlights: Vec<HighlightRule>,
+    #[serde(default)]
+    pub notify: NotifyConfig,
+    #[serde(default)]
+    pub filters: FilterConfig,
+    /// replace deleted messages with a placeholder instead of striking them through
+    #[serde(default)]
+    pub hide_deleted: bool,
+    #[serde(default)]
+    pub bot: BotConfig,
+    #[serde(default)]
+    pub polls: PollConfig,
+    /// keep every message in `data/logs` so it can be searched later
+    #[serde(default = "default_true")]
+    pub log_messages: bool,
+    /// program the Links list opens URLs with, e.g. `xdg-open`; the URL is appended
+    #[serde(default)]
+    pub open_command: Option<String>,
+    #[serde(default)]
+    pub trash: TrashConfig,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct TrashConfig {
+    /// deleted pets are purged on startup once they're this old, 0 keeps them
+    #[serde(default = "default_retention_days")]
+    pub retention_days: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PollConfig {
+    /// send the result line to the channel when a poll ends; needs `nick` and `oauth`
+    #[serde(default)]
+    pub post_results: bool,
+    /// write each finished poll to `data/polls`
+    #[serde(default = "default_true")]
+    pub export: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct BotConfig {
+    /// answer `!pet`-style commands; replies need `nick` and `oauth`
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_cooldown_secs")]
+    pub cooldown_secs: u64,
+    /// per-command overrides of `cooldown_secs`, keyed by name without the `!`
+    #[serde(default)]
+    pub cooldowns: BTreeMap<String, u64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(tag = "match", content = "value", rename_all = "snake_case")]
+pub enum HighlightPattern {
+    Keyword(String),
+    Regex(String),
+    User(String),
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct HighlightRule {
+    #[serde(flatten)]
+    pub pattern: HighlightPattern,
+    /// `#RRGGBB`, falls back to the default highlight color
+    #[serde(default)]
+    pub color: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct NotifyConfig {
+    #[serde(default)]
+    pub bell: bool,
+    #[serde(default)]
+    pub flash_title: bool,
+    /// seconds without a key press before the window counts as unfocused
+    #[serde(default = "default_idle_secs")]
+    pub idle_secs: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct FilterConfig {
+    #[serde(default)]
+    pub global: FilterRules,
+    /// keyed by channel name, without the `#`
+    #[serde(default)]
+    pub channels: BTreeMap<String, FilterRules>,
+    #[serde(default)]
+    pub hide_bots: bool,
+    #[serde(default = "default_bots")]
+    pub bots: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct FilterRules {
+    #[serde(default)]
+    pub ignored_users: Vec<String>,
+    #[serde(default)]
+    pub muted_patterns: Vec<String>,
+}
+
+impl Default for ChatConfig {
+    fn default() -> Self {
+        ChatConfig {
+            nick: None,
+            oauth: None,
+            channels: default_channels(),
+            highlights: vec![],
+            notify: NotifyConfig::default(),
+            filters: FilterConfig::default(),
+            hide_deleted: false,
+            bot: BotConfig::default(),
+            polls: PollConfig::default(),
+            log_messages: default_true(),
+            open_command: None,
+            trash: TrashConfig::default(),
+        }
+    }
+}
+
+impl Default for BotConfig {
+    fn default() -> Self {
+        BotConfig {
+            enabled: false,
+            cooldown_secs: default_cooldown_secs(),
+            cooldowns: BTreeMap::new(),
+        }
+    }
+}
+
+impl Default for FilterConfig {
+    fn default() -> Self {
+        FilterConfig {
+            global: FilterRules::default(),
+            channels: BTreeMap::new(),
+            hide_bots: false,
+            bots: default_bots(),
+        }
+    }
+}
+
+impl Default for TrashConfig {
+    fn default() -> Self {
+        TrashConfig {
+            retention_days: default_retention_days(),
+        }
+    }
+}
+
+impl Default for PollConfig {
+    fn default() -> Self {
+        PollConfig {
+            post_results: false,
+            export: default_true(),
+        }
+    }
+}
+
+impl Default for NotifyConfig {
+    fn default() -> Self {
+        NotifyConfig {
+            bell: false,
+            flash_title: false,
+            idle_secs: default_idle_secs(),
+        }
+    }
+}
+
+fn default_channels() -> Vec<String> {
+    vec![String::from("strager"), String::from("het_tanis")]
+}
+
+fn default_retention_days() -> u64 {
+    30
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_idle_secs() -> u64 {
+    30
+}
+
+fn default_cooldown_secs() -> u64 {
+    10
+}
+
+fn default_bots() -> Vec<String> {
+    [
+        "nightbot",
+        "streamelements",
+        "streamlabs",
+        "moobot",
+        "fossabot",
+        "wizebot",
+    ]
+    .iter()
+    .map(|bot| bot.to_string())
+    .collect()
+}
+
+pub fn read_config() -> Result<ChatConfig, Error> {
+    match fs::read_to_string(CONFIG_PATH) {
+        Ok(content) => Ok(serde_json::from_str(&content)?),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(ChatConfig::default()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+pub fn write_config(config: &ChatConfig) -> Result<(), Error> {
+    fs::write(CONFIG_PATH, &serde_json::to_vec_pretty(config)?)?;
+    Ok(())
+}
@@ -0,0 +1,465 @@
+use chrono::prelude::*;
+use std::fs;
+use std::time::SystemTime;
+use tui::{
+    backend::Backend,
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Color, Modifier, Style},
+    symbols,
+    text::{Span, Spans},
+    widgets::{
+        Axis, BarChart, Block, BorderType, Borders, Chart, Dataset, GraphType, Paragraph, Wrap,
+    },
+    Frame,
+};
+
+use crate::age::Age;
+use crate::schema::Db;
+use crate::{Error, DB_PATH};
+
+/// (label, lowest age in years) of each histogram bucket
+const AGE_BUCKETS: [(&str, u32); 5] = [("<1", 0), ("1-2", 1), ("3-5", 3), ("6-9", 6), ("10+", 10)];
+
+/// Figures about the pet store, worked out once per change to `db.json` (or per day,
+/// since ages move on) rather than on every frame.
+#[derive(Default)]
+pub struct Dashboard {
+    stamp: Option<(SystemTime, u64)>,
+    computed_for: Option<NaiveDate>,
+    /// display name and pet count of every category, in registry order
+    pub per_category: Vec<(String, u64)>,
+    /// pets in each of `AGE_BUCKETS`
+    pub per_age: Vec<(&'static str, u64)>,
+    /// (days since the first pet was added, pets by then), one point per pet
+    pub added: Vec<(f64, f64)>,
+    pub first_added: Option<NaiveDate>,
+    pub total: usize,
+    pub average_age: Option<Age>,
+    /// the last pet added, and when
+    pub newest: Option<(String, NaiveDate)>,
+    /// the oldest pet, and its age
+    pub oldest: Option<(String, Age)>,
+}
+
+impl Dashboard {
+    /// Recomputes everything if the store or the day changed since last time.
+    pub fn refresh(&mut self, today: NaiveDate) -> Result<(), Error> {
+        let stamp = fs::metadata(DB_PATH)
+            .and_then(|meta| Ok((meta.modified()?, meta.len())))
+            .ok();
+        if stamp.is_some() && stamp == self.stamp && self.computed_for == Some(today) {
+            return Ok(());
+        }
+        let db = crate::read_store()?;
+        *self = Dashboard::compute(&db, today);
+        self.stamp = stamp;
+        self.computed_for = Some(today);
+        Ok(())
+    }
+
+    fn compute(db: &Db, today: NaiveDate) -> Dashboard {
+        let per_category = db
+            .categories
+            .iter()
+            .map(|category| {
+                let count = db
+                    .pets
+                    .iter()
+                    .filter(|pet| pet.category == category.key)
+                    .count();
+                (category.name.clone(), count as u64)
+            })
+            .collect();
+
+        let ages: Vec<Age> = db.pets.iter().map(|pet| pet.age(today)).collect();
+        let per_age = AGE_BUCKETS
+            .iter()
+            .enumerate()
+            .map(|(index, (label, from))| {
+                let until = AGE_BUCKETS.get(index + 1).map(|(_, until)| *until);
+                let count = ages
+                    .iter()
+                    .filter(|age| age.years >= *from && until.is_none_or(|until| age.years < until))
+                    .count();
+                (*label, count as u64)
+            })
+            .collect();
+
+        let mut added: Vec<NaiveDate> = db
+            .pets
+            .iter()
+            .map(|pet| pet.created_at.with_timezone(&Local).date_naive())
+            .collect();
+        added.sort_unstable();
+        let first_added = added.first().copied();
+        let points = added
+            .iter()
+            .enumerate()
+            .map(|(index, date)| {
+                let days = first_added.map_or(0, |first| (*date - first).num_days());
+                (days as f64, (index + 1) as f64)
+            })
+            .collect();
+
+        let average_age = match ages.len() {
+            0 => None,
+            n => {
+                let months = ages
+                    .iter()
+                    .map(|age| age.years * 12 + age.months)
+                    .sum::<u32>()
+                    / n as u32;
+                Some(Age {
+                    years: months / 12,
+                    months: months % 12,
+                })
+            }
+        };
+
+        Dashboard {
+            stamp: None,
+            computed_for: None,
+            per_category,
+            per_age,
+            added: points,
+            first_added,
+            total: db.pets.len(),
+            average_age,
+            newest: db.pets.iter().max_by_key(|pet| pet.created_at).map(|pet| {
+                (
+                    pet.name.clone(),
+                    pet.created_at.with_timezone(&Local).date_naive(),
+                )
+            }),
+            oldest: db
+                .pets
+                .iter()
+                .min_by_key(|pet| pet.birthdate)
+                .map(|pet| (pet.name.clone(), pet.age(today))),
+        }
+    }
+}
+
+/// The whole tab in `area`.
+pub fn render<B: Backend>(rect: &mut Frame<B>, area: Rect, dashboard: &Dashboard) {
+    let summary = summary_lines(dashboard, area.width);
+    let summary_height = summary.len() as u16 + 2;
+    // the growth chart only gets room when there's enough height for it
+    let constraints = if area.height >= 18 {
+        [
+            Constraint::Length(summary_height),
+            Constraint::Percentage(45),
+            Constraint::Min(6),
+        ]
+    } else {
+        [
+            Constraint::Length(summary_height),
+            Constraint::Min(4),
+            Constraint::Length(0),
+        ]
+    };
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(constraints.as_ref())
+        .split(area);
+    let bar_chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
+        .split(chunks[1]);
+    let per_category: Vec<(&str, u64)> = dashboard
+        .per_category
+        .iter()
+        .map(|(name, count)| (name.as_str(), *count))
+        .collect();
+    rect.render_widget(render_summary(summary), chunks[0]);
+    rect.render_widget(
+        render_categories(&per_category, bar_chunks[0].width),
+        bar_chunks[0],
+    );
+    rect.render_widget(
+        render_ages(&dashboard.per_age, bar_chunks[1].width),
+        bar_chunks[1],
+    );
+    if chunks[2].height > 0 {
+        rect.render_widget(render_added(dashboard), chunks[2]);
+    }
+}
+
+/// The summary figures, two to a line where `width` has room for them, one otherwise.
+fn summary_lines<'a>(dashboard: &Dashboard, width: u16) -> Vec<Spans<'a>> {
+    let or_none = |value: Option<String>| value.unwrap_or_else(|| String::from("-"));
+    let figures = [
+        ("Pets: ", dashboard.total.to_string()),
+        (
+            "Average age: ",
+            or_none(dashboard.average_age.map(|age| age.to_string())),
+        ),
+        (
+            "Newest: ",
+            or_none(
+                dashboard
+                    .newest
+                    .as_ref()
+                    .map(|(name, added)| format!("{} (added {})", name, added)),
+            ),
+        ),
+        (
+            "Oldest: ",
+            or_none(
+                dashboard
+                    .oldest
+                    .as_ref()
+                    .map(|(name, age)| format!("{} ({})", name, age)),
+            ),
+        ),
+    ];
+    let room = width.saturating_sub(2) as usize;
+    let length = |(label, value): &(&str, String)| label.len() + value.chars().count();
+
+    let mut lines = vec![];
+    for pair in figures.chunks(2) {
+        let together = pair.iter().map(length).sum::<usize>() + 3 <= room;
+        let split: Vec<&[(&str, String)]> = match together {
+            true => vec![pair],
+            false => pair.chunks(1).collect(),
+        };
+        for line in split {
+            let mut spans = vec![];
+            for (at, (label, value)) in line.iter().enumerate() {
+                if at > 0 {
+                    spans.push(Span::raw("   "));
+                }
+                spans.push(Span::styled(
+                    label.to_string(),
+                    Style::default()
+                        .fg(Color::Yellow)
+                        .add_modifier(Modifier::BOLD),
+                ));
+                spans.push(Span::raw(value.clone()));
+            }
+            lines.push(Spans::from(spans));
+        }
+    }
+    lines
+}
+
+/// `lines` comes from `summary_lines`.
+pub fn render_summary<'a>(lines: Vec<Spans<'a>>) -> Paragraph<'a> {
+    Paragraph::new(lines).wrap(Wrap { trim: true }).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .style(Style::default().fg(Color::White))
+            .title("Summary")
+            .border_type(BorderType::Plain),
+    )
+}
+
+/// Bars as wide as fit `width`, so a narrow terminal still shows every one.
+fn bar_chart<'a>(
+    title: &'a str,
+    data: &'a [(&'a str, u64)],
+    width: u16,
+    color: Color,
+) -> BarChart<'a> {
+    let bars = data.len().max(1) as u16;
+    let bar_width = (width.saturating_sub(2) / bars)
+        .saturating_sub(1)
+        .clamp(1, 12);
+
+    BarChart::default()
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .style(Style::default().fg(Color::White))
+                .title(title)
+                .border_type(BorderType::Plain),
+        )
+        .data(data)
+        .bar_width(bar_width)
+        .bar_gap(1)
+        .bar_style(Style::default().fg(color))
+        .value_style(
+            Style::default()
+                .fg(Color::Black)
+                .bg(color)
+                .add_modifier(Modifier::BOLD),
+        )
+}
+
+/// Pets per category; `data` comes from `Dashboard::per_category`.
+pub fn render_categories<'a>(data: &'a [(&'a str, u64)], width: u16) -> BarChart<'a> {
+    bar_chart("Pets per category", data, width, Color::LightCyan)
+}
+
+/// Pets per age bracket; `data` comes from `Dashboard::per_age`.
+pub fn render_ages<'a>(data: &'a [(&'a str, u64)], width: u16) -> BarChart<'a> {
+    bar_chart("Ages (years)", data, width, Color::LightGreen)
+}
+
+/// How the number of pets grew, from `Dashboard::added`.
+pub fn render_added<'a>(dashboard: &'a Dashboard) -> Chart<'a> {
+    let last_day = dashboard.added.last().map_or(1.0, |(day, _)| day.max(1.0));
+    let total = (dashboard.total as f64).max(1.0);
+    let date_label = |days: f64| match dashboard.first_added {
+        Some(first) => (first + chrono::Duration::days(days as i64))
+            .format("%Y-%m-%d")
+            .to_string(),
+        None => String::from("-"),
+    };
+
+    Chart::new(vec![Dataset::default()
+        .name("pets")
+        .marker(symbols::Marker::Braille)
+        .graph_type(GraphType::Line)
+        .style(Style::default().fg(Color::LightMagenta))
+        .data(&dashboard.added)])
+    .block(
+        Block::default()
+            .borders(Borders::ALL)
+            .style(Style::default().fg(Color::White))
+            .title("Pets added over time")
+            .border_type(BorderType::Plain),
+    )
+    .x_axis(
+        Axis::default()
+            .style(Style::default().fg(Color::Gray))
+            .bounds([0.0, last_day])
+            .labels(vec![
+                Span::raw(date_label(0.0)),
+                Span::raw(date_label(last_day)),
+            ]),
+    )
+    .y_axis(
+        Axis::default()
+            .style(Style::default().fg(Color::Gray))
+            .bounds([0.0, total])
+            .labels(vec![Span::raw("0"), Span::raw(dashboard.total.to_string())]),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::categories::Category;
+    use crate::Pet;
+    use tui::{backend::TestBackend, Terminal};
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    fn today() -> NaiveDate {
+        date(2024, 6, 15)
+    }
+
+    fn db() -> Db {
+        let pets = [
+            ("Pip", "rabbits", date(2024, 1, 1), date(2024, 2, 1)),
+            ("Rex", "dogs", date(2022, 3, 1), date(2024, 5, 1)),
+            ("Tom", "cats", date(2020, 6, 15), date(2023, 1, 1)),
+            ("Old Bess", "dogs", date(2012, 1, 1), date(2023, 7, 1)),
+        ];
+        Db {
+            pets: pets
+                .iter()
+                .enumerate()
+                .map(|(id, (name, category, birthdate, added))| {
+                    let mut pet = Pet::new(id, name, category, *birthdate, false);
+                    pet.created_at = Utc.from_utc_datetime(&added.and_hms_opt(12, 0, 0).unwrap());
+                    pet
+                })
+                .collect(),
+            categories: ["Dogs", "Cats", "Rabbits"]
+                .iter()
+                .enumerate()
+                .map(|(nth, name)| Category::new(name, nth))
+                .collect(),
+            ..Db::default()
+        }
+    }
+
+    #[test]
+    fn figures() {
+        let dashboard = Dashboard::compute(&db(), today());
+        assert_eq!(dashboard.total, 4);
+        assert_eq!(
+            dashboard.per_category,
+            [
+                (String::from("Dogs"), 2),
+                (String::from("Cats"), 1),
+                (String::from("Rabbits"), 1)
+            ]
+        );
+        assert_eq!(
+            dashboard.per_age,
+            [("<1", 1), ("1-2", 1), ("3-5", 1), ("6-9", 0), ("10+", 1)]
+        );
+        // 5, 27, 48 and 149 months
+        assert_eq!(
+            dashboard.average_age,
+            Some(Age {
+                years: 4,
+                months: 9
+            })
+        );
+        assert_eq!(
+            dashboard.newest,
+            Some((String::from("Rex"), date(2024, 5, 1)))
+        );
+        assert_eq!(
+            dashboard.oldest,
+            Some((
+                String::from("Old Bess"),
+                Age {
+                    years: 12,
+                    months: 5
+                }
+            ))
+        );
+        assert_eq!(dashboard.first_added, Some(date(2023, 1, 1)));
+        assert_eq!(dashboard.added.last(), Some(&(486.0, 4.0)));
+    }
+
+    #[test]
+    fn no_pets() {
+        let dashboard = Dashboard::compute(&Db::default(), today());
+        assert_eq!(dashboard.total, 0);
+        assert_eq!(dashboard.average_age, None);
+        assert_eq!(dashboard.newest, None);
+        assert_eq!(dashboard.oldest, None);
+        assert!(dashboard.per_age.iter().all(|(_, count)| *count == 0));
+    }
+
+    #[test]
+    fn fits_a_small_terminal() {
+        let dashboard = Dashboard::compute(&db(), today());
+        let mut terminal = Terminal::new(TestBackend::new(40, 12)).unwrap();
+        terminal
+            .draw(|rect| render(rect, rect.size(), &dashboard))
+            .unwrap();
+        let buffer = terminal.backend().buffer();
+        let rows: Vec<String> = (0..buffer.area.height)
+            .map(|y| {
+                (0..buffer.area.width)
+                    .map(|x| buffer.get(x, y).symbol.as_str())
+                    .collect()
+            })
+            .collect();
+        let row = |text: &str| format!("│{:<38}│", text);
+        let added = dashboard.newest.as_ref().unwrap().1;
+        assert_eq!(
+            rows[..5],
+            [
+                format!("┌Summary{}┐", "─".repeat(31)),
+                row("Pets: 4   Average age: 4y 9m"),
+                row(&format!("Newest: Rex (added {})", added)),
+                row("Oldest: Old Bess (12y 5m)"),
+                format!("└{}┘", "─".repeat(38)),
+            ]
+        );
+        // every bar keeps its value and the start of its label
+        assert_eq!(rows[9], "│██2██ ██1██ ██1██ ││1█ 1█ 1█    1█    │");
+        assert_eq!(rows[10], "│Dogs  Cats  Rabbi ││<1 1- 3- 6- 10    │");
+        assert_eq!(rows[11], format!("└{0}┘└{0}┘", "─".repeat(18)));
+    }
+}
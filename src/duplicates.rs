@@ -0,0 +1,560 @@
+use chrono::prelude::*;
+use serde_json::{Map, Value};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashSet;
+use std::hash::{Hash, Hasher};
+use tui::{
+    layout::Constraint,
+    style::{Color, Modifier, Style},
+    text::{Span, Spans},
+    widgets::{Block, BorderType, Borders, Cell, List, ListItem, Paragraph, Row, Table},
+};
+
+use crate::schema::Db;
+use crate::trash;
+use crate::{Error, Pet};
+
+/// The fields the compare view lets you pick between, in the order it lists them.
+/// `birthdate_estimated` goes along with `birthdate`.
+pub const FIELDS: [&str; 11] = [
+    "name",
+    "category",
+    "birthdate",
+    "breed",
+    "sex",
+    "weight",
+    "color",
+    "owner",
+    "notes",
+    "tags",
+    "records",
+];
+
+// lists where keeping both sides makes sense
+const LIST_FIELDS: [&str; 2] = ["tags", "records"];
+
+/// Why two pets look like the same one.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Reason {
+    SameName,
+    /// names within a couple of typos of each other
+    SimilarName,
+    SameBirthdate,
+}
+
+impl Reason {
+    pub fn label(self) -> &'static str {
+        match self {
+            Reason::SameName => "same name",
+            Reason::SimilarName => "similar name",
+            Reason::SameBirthdate => "same birthdate",
+        }
+    }
+}
+
+/// Two pets of the same category that are probably one.
+#[derive(Clone)]
+pub struct Candidate {
+    pub left: Pet,
+    pub right: Pet,
+    pub reasons: Vec<Reason>,
+}
+
+/// Lowercase letters and digits only, so "Mr. Whiskers" and "mr whiskers" match.
+pub fn normalize(name: &str) -> String {
+    name.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// How many single-character edits turn `a` into `b`.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+// short names get less slack, "Tom" and "Tim" are different pets
+fn max_distance(name: &str) -> usize {
+    match name.chars().count() {
+        0..=3 => 0,
+        4..=6 => 1,
+        _ => 2,
+    }
+}
+
+// a hash of every string left after deleting up to `k` chars of `name`, along with
+// the category; two pets that share one may be within `k` edits of each other
+fn deletions(category: &str, name: &[char], k: usize, out: &mut Vec<u64>) {
+    let mut hasher = DefaultHasher::new();
+    (category, name).hash(&mut hasher);
+    out.push(hasher.finish());
+    if k == 0 {
+        return;
+    }
+    let mut shorter = Vec::with_capacity(name.len());
+    for at in 0..name.len() {
+        // deleting either of two equal neighbours leaves the same string
+        if at > 0 && name[at] == name[at - 1] {
+            continue;
+        }
+        shorter.clear();
+        shorter.extend_from_slice(&name[..at]);
+        shorter.extend_from_slice(&name[at + 1..]);
+        deletions(category, &shorter, k - 1, out);
+    }
+}
+
+/// Every pair of pets in the same category whose names match or nearly match, or
+/// that were born the same day, the ones with the most reasons first. Pairs with
+/// only a birthdate in common come last, littermates have one too.
+///
+/// Names within `k` edits of each other have a deletion of at most `k` chars in
+/// common, so pets are only compared with the ones they share one with rather
+/// than with every other pet; the birthdate goes into the index as one more key.
+pub fn scan(pets: &[Pet]) -> Vec<Candidate> {
+    let names: Vec<String> = pets.iter().map(|pet| normalize(&pet.name)).collect();
+    // (variant, pet) for every variant of every name, sorted so pets sharing one are together
+    let mut index = vec![];
+    let mut variants = vec![];
+    for (i, (pet, name)) in pets.iter().zip(&names).enumerate() {
+        let chars: Vec<char> = name.chars().collect();
+        variants.clear();
+        deletions(&pet.category, &chars, max_distance(name), &mut variants);
+        let mut hasher = DefaultHasher::new();
+        (&pet.category, pet.birthdate).hash(&mut hasher);
+        variants.push(hasher.finish());
+        variants.sort_unstable();
+        variants.dedup();
+        index.extend(variants.iter().map(|variant| (*variant, i)));
+    }
+    index.sort_unstable();
+    let mut pairs = HashSet::new();
+    for sharing in index.chunk_by(|a, b| a.0 == b.0) {
+        for (at, (_, i)) in sharing.iter().enumerate() {
+            pairs.extend(sharing[at + 1..].iter().map(|(_, j)| (*i, *j)));
+        }
+    }
+    // go by the order of the pets rather than the hashes
+    let mut pairs: Vec<_> = pairs.into_iter().collect();
+    pairs.sort_unstable();
+
+    let mut candidates = vec![];
+    for (i, j) in pairs {
+        let (left, right) = (&pets[i], &pets[j]);
+        // a hash collision can pair up pets of different categories
+        if left.category != right.category {
+            continue;
+        }
+        let (a, b) = (&names[i], &names[j]);
+        let mut reasons = vec![];
+        if a == b {
+            reasons.push(Reason::SameName);
+        } else if edit_distance(a, b) <= max_distance(a).min(max_distance(b)) {
+            reasons.push(Reason::SimilarName);
+        }
+        if left.birthdate == right.birthdate {
+            reasons.push(Reason::SameBirthdate);
+        }
+        if reasons.is_empty() {
+            continue;
+        }
+        candidates.push(Candidate {
+            left: left.clone(),
+            right: right.clone(),
+            reasons,
+        });
+    }
+    candidates.sort_by_key(|candidate| {
+        (
+            std::cmp::Reverse(candidate.reasons.len()),
+            candidate.reasons[0] != Reason::SameName,
+            candidate.reasons[0] == Reason::SameBirthdate,
+        )
+    });
+    candidates
+}
+
+/// Which side a merged field comes from.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Pick {
+    Left,
+    Right,
+    /// both lists put together, only for `LIST_FIELDS`
+    Both,
+}
+
+/// A candidate being merged, with a pick for each of `FIELDS`.
+pub struct Compare {
+    pub candidate: Candidate,
+    pub picks: Vec<Pick>,
+    /// the field the arrow keys change
+    pub row: usize,
+    left: Map<String, Value>,
+    right: Map<String, Value>,
+}
+
+fn fields_of(pet: &Pet) -> Result<Map<String, Value>, Error> {
+    match serde_json::to_value(pet)? {
+        Value::Object(fields) => Ok(fields),
+        _ => Ok(Map::new()),
+    }
+}
+
+impl Compare {
+    /// Starts out with the left pet's values, and both sides of lists.
+    pub fn new(candidate: Candidate) -> Result<Compare, Error> {
+        let picks = FIELDS
+            .iter()
+            .map(|field| match LIST_FIELDS.contains(field) {
+                true => Pick::Both,
+                false => Pick::Left,
+            })
+            .collect();
+        Ok(Compare {
+            left: fields_of(&candidate.left)?,
+            right: fields_of(&candidate.right)?,
+            candidate,
+            picks,
+            row: 0,
+        })
+    }
+
+    pub fn move_row(&mut self, forward: bool) {
+        self.row = match forward {
+            true => (self.row + 1) % FIELDS.len(),
+            false => (self.row + FIELDS.len() - 1) % FIELDS.len(),
+        };
+    }
+
+    /// Moves the current field's pick towards the left or right column; lists have
+    /// "both" in the middle.
+    pub fn pick(&mut self, right: bool) {
+        let list = LIST_FIELDS.contains(&FIELDS[self.row]);
+        let pick = &mut self.picks[self.row];
+        *pick = match (*pick, right, list) {
+            (Pick::Left, true, true) => Pick::Both,
+            (Pick::Left, true, false) | (Pick::Both, true, _) => Pick::Right,
+            (Pick::Right, false, true) => Pick::Both,
+            (Pick::Right, false, false) | (Pick::Both, false, _) => Pick::Left,
+            (pick, _, _) => pick,
+        };
+    }
+
+    /// The left pet with the picked values; it keeps its id and the earlier `created_at`.
+    pub fn merged(&self, now: DateTime<Utc>) -> Result<Pet, Error> {
+        let mut merged = self.left.clone();
+        for (field, pick) in FIELDS.iter().zip(&self.picks) {
+            let right = self.right.get(*field).cloned().unwrap_or(Value::Null);
+            match pick {
+                Pick::Left => {}
+                Pick::Right => {
+                    merged.insert(field.to_string(), right);
+                    if *field == "birthdate" {
+                        let estimated = self.right.get("birthdate_estimated").cloned();
+                        merged.insert(
+                            String::from("birthdate_estimated"),
+                            estimated.unwrap_or(Value::Bool(false)),
+                        );
+                    }
+                }
+                Pick::Both => {
+                    let mut items = match merged.get(*field) {
+                        Some(Value::Array(items)) => items.clone(),
+                        _ => vec![],
+                    };
+                    if let Value::Array(more) = right {
+                        for item in more {
+                            if !items.contains(&item) {
+                                items.push(item);
+                            }
+                        }
+                    }
+                    merged.insert(field.to_string(), Value::Array(items));
+                }
+            }
+        }
+        let mut pet: Pet = serde_json::from_value(Value::Object(merged))?;
+        pet.created_at = pet.created_at.min(self.candidate.right.created_at);
+        pet.updated_at = Some(now);
+        Ok(pet)
+    }
+}
+
+/// Replaces the left pet with the merged one and moves the right one to the trash,
+/// returning the left pet as it was, the merged pet and the trashed one. Refuses if
+/// either pet was changed since the scan, the merge would undo the change.
+pub fn merge(db: &mut Db, compare: &Compare, now: DateTime<Utc>) -> Result<(Pet, Pet, Pet), Error> {
+    for scanned in [&compare.candidate.left, &compare.candidate.right] {
+        let stored = db
+            .pets
+            .iter()
+            .find(|pet| pet.id == scanned.id)
+            .ok_or_else(|| Error::CommandError(format!("{} is gone", scanned.name)))?;
+        if fields_of(stored)? != fields_of(scanned)? {
+            return Err(Error::CommandError(format!(
+                "{} changed since the scan, check the pair again",
+                scanned.name
+            )));
+        }
+    }
+    let merged = compare.merged(now)?;
+    let at = db
+        .pets
+        .iter()
+        .position(|pet| pet.id == merged.id)
+        .ok_or_else(|| Error::CommandError(format!("{} is gone", compare.candidate.left.name)))?;
+    let before = std::mem::replace(&mut db.pets[at], merged.clone());
+    let absorbed = trash::trash(db, compare.candidate.right.id, now)?;
+    Ok((before, merged, absorbed))
+}
+
+fn show(value: Option<&Value>) -> String {
+    match value {
+        None | Some(Value::Null) => String::from("-"),
+        Some(Value::String(text)) => text.clone(),
+        Some(Value::Array(items)) if items.is_empty() => String::from("-"),
+        Some(Value::Array(items)) if items.iter().all(Value::is_string) => items
+            .iter()
+            .filter_map(Value::as_str)
+            .collect::<Vec<_>>()
+            .join(", "),
+        Some(Value::Array(items)) => format!("{} entries", items.len()),
+        Some(other) => other.to_string(),
+    }
+}
+
+pub fn render_candidates<'a>(candidates: &[Candidate]) -> List<'a> {
+    let items: Vec<_> = candidates
+        .iter()
+        .map(|candidate| {
+            let reasons: Vec<_> = candidate.reasons.iter().map(|r| r.label()).collect();
+            ListItem::new(Spans::from(vec![
+                Span::styled(
+                    candidate.left.name.clone(),
+                    Style::default().add_modifier(Modifier::BOLD),
+                ),
+                Span::raw(" ↔ "),
+                Span::styled(
+                    candidate.right.name.clone(),
+                    Style::default().add_modifier(Modifier::BOLD),
+                ),
+                Span::styled(
+                    format!("  {} · {}", candidate.left.category, reasons.join(", ")),
+                    Style::default().fg(Color::DarkGray),
+                ),
+            ]))
+        })
+        .collect();
+
+    List::new(items)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .style(Style::default().fg(Color::White))
+                .title(format!("Possible duplicates ({})", candidates.len()))
+                .border_type(BorderType::Plain),
+        )
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+}
+
+/// Both pets side by side, the picked value of each field in green.
+pub fn render_compare<'a>(compare: &Compare) -> Table<'a> {
+    let picked = Style::default()
+        .fg(Color::LightGreen)
+        .add_modifier(Modifier::BOLD);
+    let unpicked = Style::default().fg(Color::DarkGray);
+    let rows: Vec<_> = FIELDS
+        .iter()
+        .zip(&compare.picks)
+        .enumerate()
+        .map(|(row, (field, pick))| {
+            let (left_style, right_style) = match pick {
+                Pick::Left => (picked, unpicked),
+                Pick::Right => (unpicked, picked),
+                Pick::Both => (picked, picked),
+            };
+            let style = match row == compare.row {
+                true => Style::default().add_modifier(Modifier::REVERSED),
+                false => Style::default(),
+            };
+            Row::new(vec![
+                Cell::from(Span::styled(
+                    field.to_string(),
+                    Style::default().fg(Color::Yellow),
+                )),
+                Cell::from(Span::styled(show(compare.left.get(*field)), left_style)),
+                Cell::from(Span::styled(show(compare.right.get(*field)), right_style)),
+            ])
+            .style(style)
+        })
+        .collect();
+
+    let bold = |title: String| {
+        Cell::from(Span::styled(
+            title,
+            Style::default().add_modifier(Modifier::BOLD),
+        ))
+    };
+    Table::new(rows)
+        .header(Row::new(vec![
+            bold(String::from("Field")),
+            bold(format!("#{}", compare.candidate.left.id)),
+            bold(format!("#{}", compare.candidate.right.id)),
+        ]))
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .style(Style::default().fg(Color::White))
+                .title("Merge")
+                .border_type(BorderType::Plain),
+        )
+        .widths(&[
+            Constraint::Length(10),
+            Constraint::Percentage(45),
+            Constraint::Percentage(45),
+        ])
+}
+
+/// The key help, or the result of the last action.
+pub fn render_help<'a>(status: &str, comparing: bool) -> Paragraph<'a> {
+    let text = if !status.is_empty() {
+        status
+    } else if comparing {
+        "←/→ pick a side, ↑/↓ field, Enter merge into the left one, Esc back"
+    } else {
+        "Enter compare and merge, s scan again"
+    };
+    Paragraph::new(Spans::from(vec![Span::raw(text.to_string())])).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .style(Style::default().fg(Color::White))
+            .border_type(BorderType::Plain),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pet(id: usize, name: &str, category: &str, day: u32) -> Pet {
+        Pet::new(
+            id,
+            name,
+            category,
+            NaiveDate::from_ymd_opt(2020, 1, day).unwrap(),
+            false,
+        )
+    }
+
+    fn pairs(candidates: &[Candidate]) -> Vec<(usize, usize, Vec<Reason>)> {
+        candidates
+            .iter()
+            .map(|c| (c.left.id, c.right.id, c.reasons.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn names_that_match_or_nearly_match() {
+        let pets = vec![
+            pet(1, "Mr. Whiskers", "cats", 1),
+            pet(2, "Tom", "cats", 4),
+            pet(3, "mr whiskers", "cats", 2),
+            pet(4, "Tim", "cats", 5),
+            pet(5, "Mr Wiskers", "cats", 1),
+            pet(6, "Mr. Whiskers", "dogs", 1),
+            pet(7, "Bella", "dogs", 3),
+            pet(8, "Bela", "dogs", 3),
+            pet(9, "Biscuit", "dogs", 6),
+        ];
+        assert_eq!(
+            pairs(&scan(&pets)),
+            [
+                (1, 5, vec![Reason::SimilarName, Reason::SameBirthdate]),
+                (7, 8, vec![Reason::SimilarName, Reason::SameBirthdate]),
+                (1, 3, vec![Reason::SameName]),
+                (3, 5, vec![Reason::SimilarName]),
+            ]
+        );
+    }
+
+    #[test]
+    fn same_as_comparing_every_pair() {
+        let names = [
+            "Max", "Maxx", "Mac", "Luna", "Lunar", "Lune", "Charlie", "Charley", "Charles",
+            "Bailey", "Baily", "Daisy", "Daisey", "Lucy", "Lucky", "Cooper", "Copper", "Ada",
+        ];
+        let pets: Vec<_> = (0..200)
+            .map(|i| {
+                let category = ["cats", "dogs"][i % 2];
+                pet(i, names[i * 7 % names.len()], category, 1 + (i % 28) as u32)
+            })
+            .collect();
+        let mut expected = vec![];
+        for (i, left) in pets.iter().enumerate() {
+            for right in &pets[i + 1..] {
+                let (a, b) = (normalize(&left.name), normalize(&right.name));
+                if left.category == right.category
+                    && (edit_distance(&a, &b) <= max_distance(&a).min(max_distance(&b))
+                        || left.birthdate == right.birthdate)
+                {
+                    expected.push((left.id, right.id));
+                }
+            }
+        }
+        let mut found: Vec<_> = scan(&pets)
+            .iter()
+            .map(|c| (c.left.id, c.right.id))
+            .collect();
+        found.sort_unstable();
+        assert_eq!(found, expected);
+    }
+
+    #[test]
+    fn birthdates_alone_come_last() {
+        let pets = vec![
+            pet(1, "Pip", "rabbits", 7),
+            pet(2, "Pop", "rabbits", 7),
+            pet(3, "Snowball", "rabbits", 8),
+            pet(4, "Snowbal", "rabbits", 9),
+            pet(5, "Pip", "hamsters", 7),
+        ];
+        assert_eq!(
+            pairs(&scan(&pets)),
+            [
+                (3, 4, vec![Reason::SimilarName]),
+                (1, 2, vec![Reason::SameBirthdate]),
+            ]
+        );
+    }
+
+    #[test]
+    fn merging_a_changed_pet_is_refused() {
+        let mut db = Db {
+            pets: vec![pet(1, "Bella", "dogs", 3), pet(2, "Bela", "dogs", 3)],
+            ..Db::default()
+        };
+        let candidate = scan(&db.pets).remove(0);
+        let compare = Compare::new(candidate).unwrap();
+        db.pets[0].notes = Some(String::from("shy with cats"));
+        assert!(merge(&mut db, &compare, Utc::now()).is_err());
+        assert_eq!(db.pets.len(), 2);
+        assert_eq!(db.pets[0].notes.as_deref(), Some("shy with cats"));
+
+        let candidate = scan(&db.pets).remove(0);
+        let compare = Compare::new(candidate).unwrap();
+        let (_, merged, absorbed) = merge(&mut db, &compare, Utc::now()).unwrap();
+        assert_eq!(merged.notes.as_deref(), Some("shy with cats"));
+        assert_eq!(absorbed.id, 2);
+        assert_eq!(db.pets.len(), 1);
+    }
+}
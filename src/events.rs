@@ -0,0 +1,249 @@
+use chrono::prelude::*;
+use tui::{
+    style::{Color, Modifier, Style},
+    text::{Span, Spans, Text},
+};
+
+use crate::chat::{ChatMessage, MessageKind};
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum EventKind {
+    Sub,
+    Resub,
+    SubGift,
+    Raid,
+    Announcement,
+    /// a chat message with bits attached
+    Cheer,
+}
+
+impl EventKind {
+    fn label(self) -> &'static str {
+        match self {
+            EventKind::Sub => "★ SUB",
+            EventKind::Resub => "★ RESUB",
+            EventKind::SubGift => "♥ GIFT",
+            EventKind::Raid => "» RAID",
+            EventKind::Announcement => "! ANNOUNCEMENT",
+            EventKind::Cheer => "◆ BITS",
+        }
+    }
+
+    fn color(self) -> Color {
+        match self {
+            EventKind::Sub | EventKind::Resub => Color::LightMagenta,
+            EventKind::SubGift => Color::LightRed,
+            EventKind::Raid => Color::LightCyan,
+            EventKind::Announcement => Color::LightYellow,
+            EventKind::Cheer => Color::LightBlue,
+        }
+    }
+}
+
+/// A sub, raid, announcement or cheer, read from the tags of the message that carried it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChatEvent {
+    pub kind: EventKind,
+    /// who subbed, gifted, raided, announced or cheered
+    pub user: String,
+    /// months, gifts, raiders or bits, depending on `kind`
+    pub count: u64,
+    /// the gift's recipient, if it had one
+    pub recipient: Option<String>,
+    /// what the user wrote along with it
+    pub message: Option<String>,
+}
+
+impl ChatEvent {
+    /// The event `msg` announces, `None` for plain chat and other notices.
+    pub fn from_message(msg: &ChatMessage) -> Option<ChatEvent> {
+        let tag = |key: &str| msg.tags.get(key).map(String::as_str);
+        let number = |key: &str| tag(key).and_then(|value| value.parse().ok());
+        let (kind, count, user) = match msg.kind {
+            MessageKind::Chat => (EventKind::Cheer, number("bits")?, None),
+            MessageKind::Notice => match tag("msg-id")? {
+                "sub" => (
+                    EventKind::Sub,
+                    number("msg-param-cumulative-months").unwrap_or(1),
+                    None,
+                ),
+                "resub" => (
+                    EventKind::Resub,
+                    number("msg-param-cumulative-months").unwrap_or(1),
+                    None,
+                ),
+                "subgift" | "anonsubgift" => (EventKind::SubGift, 1, None),
+                "submysterygift" | "anonsubmysterygift" => (
+                    EventKind::SubGift,
+                    number("msg-param-mass-gift-count").unwrap_or(1),
+                    None,
+                ),
+                "raid" => (
+                    EventKind::Raid,
+                    number("msg-param-viewerCount").unwrap_or(0),
+                    tag("msg-param-displayName"),
+                ),
+                "announcement" => (EventKind::Announcement, 0, None),
+                _ => return None,
+            },
+            MessageKind::Whisper => return None,
+        };
+
+        let message = match msg.kind {
+            // notices keep the user's message quoted after the system message
+            MessageKind::Notice => msg
+                .text
+                .strip_prefix(tag("system-msg").unwrap_or_default())
+                .map(|rest| rest.trim().trim_matches('"').to_string()),
+            _ => Some(msg.text.clone()),
+        };
+        Some(ChatEvent {
+            kind,
+            user: user
+                .map(str::to_string)
+                .filter(|user| !user.is_empty())
+                .unwrap_or_else(|| match msg.display_name.as_str() {
+                    "" => String::from("anonymous"),
+                    name => name.to_string(),
+                }),
+            count,
+            recipient: tag("msg-param-recipient-display-name").map(str::to_string),
+            message: message.filter(|message| !message.is_empty()),
+        })
+    }
+
+    // gifts from a mystery gift arrive one by one after it, already counted by it
+    fn from_mystery_gift(msg: &ChatMessage) -> bool {
+        msg.tags.contains_key("msg-param-community-gift-id")
+    }
+
+    fn describe_count(&self) -> Option<String> {
+        match self.kind {
+            EventKind::Sub | EventKind::Resub => Some(plural(self.count, "month")),
+            EventKind::SubGift => Some(match &self.recipient {
+                Some(recipient) => format!("to {}", recipient),
+                None => plural(self.count, "sub"),
+            }),
+            EventKind::Raid => Some(plural(self.count, "viewer")),
+            EventKind::Cheer => Some(plural(self.count, "bit")),
+            EventKind::Announcement => None,
+        }
+    }
+}
+
+/// Events seen in a channel since startup.
+#[derive(Default)]
+pub struct EventTotals {
+    pub subs: u64,
+    pub resubs: u64,
+    pub gifted: u64,
+    pub raids: u64,
+    pub raiders: u64,
+    pub bits: u64,
+    pub announcements: u64,
+}
+
+impl EventTotals {
+    pub fn record(&mut self, msg: &ChatMessage) {
+        let event = match ChatEvent::from_message(msg) {
+            Some(event) => event,
+            None => return,
+        };
+        match event.kind {
+            EventKind::Sub => self.subs += 1,
+            EventKind::Resub => self.resubs += 1,
+            EventKind::SubGift if !ChatEvent::from_mystery_gift(msg) => self.gifted += event.count,
+            EventKind::SubGift => {}
+            EventKind::Raid => {
+                self.raids += 1;
+                self.raiders += event.count;
+            }
+            EventKind::Announcement => self.announcements += 1,
+            EventKind::Cheer => self.bits += event.count,
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        let mut parts = vec![];
+        let mut add = |count: u64, text: String| {
+            if count > 0 {
+                parts.push(text);
+            }
+        };
+        add(self.subs, plural(self.subs, "sub"));
+        add(self.resubs, plural(self.resubs, "resub"));
+        add(self.gifted, format!("{} gifted", self.gifted));
+        add(
+            self.raids,
+            format!(
+                "{} ({})",
+                plural(self.raids, "raid"),
+                plural(self.raiders, "viewer")
+            ),
+        );
+        add(self.bits, plural(self.bits, "bit"));
+        add(
+            self.announcements,
+            plural(self.announcements, "announcement"),
+        );
+        if parts.is_empty() {
+            String::from("none yet")
+        } else {
+            parts.join(", ")
+        }
+    }
+}
+
+fn plural(n: u64, word: &str) -> String {
+    match n {
+        1 => format!("1 {}", word),
+        n => format!("{} {}s", n, word),
+    }
+}
+
+/// Draws an event as a card: a colored bar with the kind, user and count, and
+/// the user's message underneath when there is one.
+pub fn render_card(msg: &ChatMessage, event: &ChatEvent) -> Text<'static> {
+    let color = event.kind.color();
+    let bar = || Span::styled("▌ ", Style::default().fg(color));
+    let mut header = vec![
+        bar(),
+        Span::styled(
+            msg.sent_at
+                .with_timezone(&Local)
+                .format("%H:%M ")
+                .to_string(),
+            Style::default().fg(Color::DarkGray),
+        ),
+        Span::styled(
+            format!(" {} ", event.kind.label()),
+            Style::default()
+                .fg(Color::Black)
+                .bg(color)
+                .add_modifier(Modifier::BOLD),
+        ),
+        Span::styled(
+            format!(" {}", event.user),
+            Style::default().fg(color).add_modifier(Modifier::BOLD),
+        ),
+    ];
+    if let Some(count) = event.describe_count() {
+        header.push(Span::styled(
+            format!(" · {}", count),
+            Style::default().add_modifier(Modifier::BOLD),
+        ));
+    }
+
+    let mut lines = vec![Spans::from(header)];
+    if let Some(message) = &event.message {
+        let mut style = Style::default().add_modifier(Modifier::ITALIC);
+        if msg.deleted {
+            style = style.add_modifier(Modifier::CROSSED_OUT);
+        }
+        lines.push(Spans::from(vec![
+            bar(),
+            Span::styled(message.clone(), style),
+        ]));
+    }
+    Text::from(lines)
+}
@@ -0,0 +1,286 @@
+use chrono::prelude::*;
+use std::fs;
+use std::path::PathBuf;
+use tui::{
+    style::{Color, Modifier, Style},
+    text::{Span, Spans},
+    widgets::{Block, BorderType, Borders, List, ListItem},
+};
+
+use crate::chat::{self, Badge, ChatMessage, MessageKind};
+use crate::Error;
+
+const EXPORTS_DIR: &str = "./data/exports";
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ExportFormat {
+    Markdown,
+    Html,
+    Json,
+}
+
+pub const EXPORT_FORMATS: [ExportFormat; 3] = [
+    ExportFormat::Markdown,
+    ExportFormat::Html,
+    ExportFormat::Json,
+];
+
+impl ExportFormat {
+    pub fn from_name(name: &str) -> Result<ExportFormat, Error> {
+        match name.to_lowercase().as_str() {
+            "md" | "markdown" => Ok(ExportFormat::Markdown),
+            "html" => Ok(ExportFormat::Html),
+            "json" => Ok(ExportFormat::Json),
+            other => Err(Error::CommandError(format!(
+                "unknown format '{}', use md, html or json",
+                other
+            ))),
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ExportFormat::Markdown => "Markdown",
+            ExportFormat::Html => "HTML",
+            ExportFormat::Json => "JSON (all tags)",
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Markdown => "md",
+            ExportFormat::Html => "html",
+            ExportFormat::Json => "json",
+        }
+    }
+}
+
+/// `messages` as a transcript called `title`.
+pub fn render_transcript(
+    messages: &[ChatMessage],
+    title: &str,
+    format: ExportFormat,
+) -> Result<String, Error> {
+    Ok(match format {
+        ExportFormat::Markdown => to_markdown(messages, title),
+        ExportFormat::Html => to_html(messages, title),
+        ExportFormat::Json => serde_json::to_string_pretty(messages)? + "\n",
+    })
+}
+
+/// Writes a transcript to `data/exports`, returning where it went.
+pub fn write_transcript(
+    messages: &[ChatMessage],
+    title: &str,
+    format: ExportFormat,
+) -> Result<PathBuf, Error> {
+    let content = render_transcript(messages, title, format)?;
+    fs::create_dir_all(EXPORTS_DIR)?;
+    let name: String = title
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '-' })
+        .collect();
+    let path = PathBuf::from(EXPORTS_DIR).join(format!(
+        "{}-{}.{}",
+        name.trim_matches('-'),
+        Local::now().format("%Y%m%d-%H%M%S"),
+        format.extension()
+    ));
+    fs::write(&path, content)?;
+    Ok(path)
+}
+
+fn badge_name(badge: &Badge) -> String {
+    match badge {
+        Badge::Broadcaster => String::from("broadcaster"),
+        Badge::Moderator => String::from("moderator"),
+        Badge::Vip => String::from("vip"),
+        Badge::Subscriber(months) => format!("subscriber/{}", months),
+        Badge::Other(name) => name.clone(),
+    }
+}
+
+fn to_markdown(messages: &[ChatMessage], title: &str) -> String {
+    let escape = |text: &str| {
+        text.chars().fold(String::new(), |mut out, c| {
+            if "\\`*_[]<>#|".contains(c) {
+                out.push('\\');
+            }
+            out.push(c);
+            out
+        })
+    };
+    let mut out = format!("# {}\n\n", escape(title));
+    for msg in messages {
+        let time = msg
+            .sent_at
+            .with_timezone(&Local)
+            .format("%Y-%m-%d %H:%M:%S");
+        let line = match msg.kind {
+            MessageKind::Notice => format!("- `{}` _{}_", time, escape(&msg.text)),
+            MessageKind::Chat | MessageKind::Whisper => {
+                let badges: Vec<_> = msg
+                    .badges
+                    .iter()
+                    .filter_map(chat::badge_style)
+                    .map(|(glyph, _)| glyph)
+                    .collect();
+                let text = match (msg.action, msg.deleted) {
+                    (_, true) => format!("~~{}~~", escape(&msg.text)),
+                    (true, false) => format!("_{}_", escape(&msg.text)),
+                    (false, false) => escape(&msg.text),
+                };
+                format!(
+                    "- `{}` {}**{}**: {}",
+                    time,
+                    badges
+                        .iter()
+                        .map(|glyph| format!("{} ", glyph))
+                        .collect::<String>(),
+                    escape(&msg.display_name),
+                    text
+                )
+            }
+        };
+        out.push_str(&line);
+        out.push('\n');
+    }
+    out
+}
+
+fn escape_html(text: &str) -> String {
+    text.chars().fold(String::new(), |mut out, c| {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+        out
+    })
+}
+
+fn css_color(color: Color) -> String {
+    match color {
+        Color::Rgb(r, g, b) => format!("#{:02x}{:02x}{:02x}", r, g, b),
+        Color::Red => String::from("#d03030"),
+        Color::Green => String::from("#30a030"),
+        Color::Magenta => String::from("#b030b0"),
+        Color::LightMagenta => String::from("#e070e0"),
+        _ => String::from("inherit"),
+    }
+}
+
+// the message text with emotes wrapped in spans
+fn html_text(msg: &ChatMessage) -> String {
+    let chars: Vec<char> = msg.text.chars().collect();
+    let mut out = String::new();
+    let mut pos = 0;
+    for emote in &msg.emotes {
+        if emote.start < pos || emote.end >= chars.len() {
+            continue;
+        }
+        out.push_str(&escape_html(
+            &chars[pos..emote.start].iter().collect::<String>(),
+        ));
+        let name: String = chars[emote.start..=emote.end].iter().collect();
+        out.push_str(&format!(
+            "<span class=\"emote\" title=\"emote {}\">{}</span>",
+            escape_html(&emote.id),
+            escape_html(&name)
+        ));
+        pos = emote.end + 1;
+    }
+    out.push_str(&escape_html(
+        &chars[pos.min(chars.len())..].iter().collect::<String>(),
+    ));
+    out
+}
+
+fn to_html(messages: &[ChatMessage], title: &str) -> String {
+    let mut out = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>\n{}</style>\n</head>\n<body>\n<h1>{}</h1>\n<ul>\n",
+        escape_html(title),
+        "body { background: #18181b; color: #efeff1; font-family: sans-serif; }\n\
+         ul { list-style: none; padding: 0; }\n\
+         li { padding: 2px 0; }\n\
+         .time { color: #777; font-family: monospace; margin-right: 6px; }\n\
+         .nick { font-weight: bold; }\n\
+         .notice { color: #aaa; font-style: italic; }\n\
+         .action { font-style: italic; }\n\
+         .deleted { text-decoration: line-through; opacity: 0.6; }\n\
+         .emote { color: #fff59d; font-style: italic; }\n\
+         .badge { margin-right: 3px; }\n",
+        escape_html(title)
+    );
+    for msg in messages {
+        let time = msg
+            .sent_at
+            .with_timezone(&Local)
+            .format("%Y-%m-%d %H:%M:%S");
+        out.push_str(&format!(
+            "<li><span class=\"time\" title=\"{}\">{}</span>",
+            msg.sent_at.to_rfc3339(),
+            time
+        ));
+        match msg.kind {
+            MessageKind::Notice => out.push_str(&format!(
+                "<span class=\"notice\">{}</span>",
+                escape_html(&msg.text)
+            )),
+            MessageKind::Chat | MessageKind::Whisper => {
+                for badge in &msg.badges {
+                    if let Some((glyph, color)) = chat::badge_style(badge) {
+                        out.push_str(&format!(
+                            "<span class=\"badge\" title=\"{}\" style=\"color: {}\">{}</span>",
+                            escape_html(&badge_name(badge)),
+                            css_color(color),
+                            glyph
+                        ));
+                    }
+                }
+                let mut classes = vec!["text"];
+                if msg.action {
+                    classes.push("action");
+                }
+                if msg.deleted {
+                    classes.push("deleted");
+                }
+                out.push_str(&format!(
+                    "<span class=\"nick\" style=\"color: {}\">{}</span>: <span class=\"{}\">{}</span>",
+                    css_color(msg.nick_color()),
+                    escape_html(&msg.display_name),
+                    classes.join(" "),
+                    html_text(msg)
+                ));
+            }
+        }
+        out.push_str("</li>\n");
+    }
+    out.push_str("</ul>\n</body>\n</html>\n");
+    out
+}
+
+pub fn render_export_popup<'a>(count: usize) -> List<'a> {
+    let items: Vec<_> = EXPORT_FORMATS
+        .iter()
+        .map(|format| ListItem::new(Spans::from(vec![Span::raw(format.label())])))
+        .collect();
+
+    List::new(items)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .style(Style::default().fg(Color::White).bg(Color::Black))
+                .title(format!("Export {} messages", count))
+                .border_type(BorderType::Rounded),
+        )
+        .highlight_style(
+            Style::default()
+                .bg(Color::Yellow)
+                .fg(Color::Black)
+                .add_modifier(Modifier::BOLD),
+        )
+}
@@ -0,0 +1,141 @@
+use regex::Regex;
+use std::collections::{HashMap, HashSet};
+
+use crate::chat::ChatMessage;
+use crate::commands::ChatCommand;
+use crate::config::{FilterConfig, FilterRules};
+use crate::Error;
+
+struct CompiledRules {
+    users: HashSet<String>,
+    patterns: Vec<Regex>,
+}
+
+impl CompiledRules {
+    fn new(rules: &FilterRules) -> Result<CompiledRules, Error> {
+        Ok(CompiledRules {
+            users: rules
+                .ignored_users
+                .iter()
+                .map(|user| user.to_lowercase())
+                .collect(),
+            patterns: rules
+                .muted_patterns
+                .iter()
+                .map(|pattern| Regex::new(pattern))
+                .collect::<Result<_, _>>()?,
+        })
+    }
+
+    fn matches(&self, msg: &ChatMessage) -> bool {
+        self.users.contains(&msg.login) || self.patterns.iter().any(|p| p.is_match(&msg.text))
+    }
+}
+
+/// Decides which incoming messages never make it into a channel's scrollback.
+pub struct Filter {
+    global: CompiledRules,
+    channels: HashMap<String, CompiledRules>,
+    bots: Option<HashSet<String>>,
+}
+
+impl Filter {
+    pub fn new(config: &FilterConfig) -> Result<Filter, Error> {
+        Ok(Filter {
+            global: CompiledRules::new(&config.global)?,
+            channels: config
+                .channels
+                .iter()
+                .map(|(name, rules)| Ok((name.clone(), CompiledRules::new(rules)?)))
+                .collect::<Result<_, Error>>()?,
+            bots: if config.hide_bots {
+                Some(config.bots.iter().map(|bot| bot.to_lowercase()).collect())
+            } else {
+                None
+            },
+        })
+    }
+
+    pub fn is_filtered(&self, msg: &ChatMessage) -> bool {
+        self.global.matches(msg)
+            || self
+                .channels
+                .get(&msg.channel)
+                .is_some_and(|rules| rules.matches(msg))
+            || self
+                .bots
+                .as_ref()
+                .is_some_and(|bots| bots.contains(&msg.login))
+    }
+}
+
+/// Adds or removes `value` from `list`, returning whether anything changed.
+fn toggle_entry(list: &mut Vec<String>, value: &str, add: bool) -> bool {
+    let present = list.iter().position(|entry| entry == value);
+    match (present, add) {
+        (None, true) => list.push(value.to_string()),
+        (Some(index), false) => {
+            list.remove(index);
+        }
+        _ => return false,
+    }
+    true
+}
+
+/// Applies an ignore/mute command to the persisted rules, returning a line for the status bar.
+///
+/// `channel` is the joined channel currently selected, used when the command isn't global.
+pub fn apply_command(
+    config: &mut FilterConfig,
+    cmd: &ChatCommand,
+    channel: Option<&str>,
+) -> Result<String, Error> {
+    let (value, add, global, is_user) = match cmd {
+        ChatCommand::Ignore { user, global } => (user, true, *global, true),
+        ChatCommand::Unignore { user, global } => (user, false, *global, true),
+        ChatCommand::Mute { pattern, global } => {
+            Regex::new(pattern)?;
+            (pattern, true, *global, false)
+        }
+        ChatCommand::Unmute { pattern, global } => (pattern, false, *global, false),
+        ChatCommand::ToggleBots => {
+            config.hide_bots = !config.hide_bots;
+            let state = if config.hide_bots { "hidden" } else { "shown" };
+            return Ok(format!("bot messages {}", state));
+        }
+        other => {
+            return Err(Error::CommandError(format!(
+                "{:?} isn't a filter command",
+                other
+            )))
+        }
+    };
+
+    let (rules, scope) = if global {
+        (&mut config.global, String::from("everywhere"))
+    } else {
+        let channel = channel.ok_or_else(|| {
+            Error::CommandError(String::from("select a joined channel or pass -g"))
+        })?;
+        (
+            config.channels.entry(channel.to_string()).or_default(),
+            format!("in #{}", channel),
+        )
+    };
+    let list = if is_user {
+        &mut rules.ignored_users
+    } else {
+        &mut rules.muted_patterns
+    };
+    let verb = match (is_user, add) {
+        (true, true) => "ignoring",
+        (true, false) => "no longer ignoring",
+        (false, true) => "muting",
+        (false, false) => "no longer muting",
+    };
+    if toggle_entry(list, value, add) {
+        Ok(format!("{} '{}' {}", verb, value, scope))
+    } else {
+        Ok(format!("already {} '{}' {}", verb, value, scope))
+    }
+}
@@ -0,0 +1,305 @@
+use chrono::prelude::*;
+use serde::{Deserialize, Serialize};
+use tui::{
+    layout::Constraint,
+    style::{Color, Modifier, Style},
+    text::{Span, Spans},
+    widgets::{Block, BorderType, Borders, Cell, List, ListItem, Row, Table},
+};
+
+use crate::Pet;
+
+/// how far ahead the Home screen lists what's coming up
+pub const UPCOMING_DAYS: i64 = 30;
+
+/// A vet visit, vaccination or treatment.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct HealthRecord {
+    pub date: NaiveDate,
+    /// e.g. "rabies vaccine" or "checkup"; a later record of the same type replaces this one's due date
+    #[serde(rename = "type")]
+    pub kind: String,
+    #[serde(default)]
+    pub notes: String,
+    /// when it has to be done again, if ever
+    #[serde(default)]
+    pub next_due: Option<NaiveDate>,
+}
+
+/// A due date that's passed or coming up.
+#[derive(Clone, Debug)]
+pub struct Reminder {
+    pub pet: String,
+    pub kind: String,
+    pub due: NaiveDate,
+    /// negative once it's overdue
+    pub days_left: i64,
+}
+
+impl Reminder {
+    pub fn is_overdue(&self) -> bool {
+        self.days_left < 0
+    }
+}
+
+// whether no later record of the same type has replaced `record`'s due date
+fn is_latest(pet: &Pet, record: &HealthRecord) -> bool {
+    !pet.records
+        .iter()
+        .any(|later| later.kind.eq_ignore_ascii_case(&record.kind) && later.date > record.date)
+}
+
+/// Everything overdue or due within `UPCOMING_DAYS` of `today`, soonest first.
+pub fn reminders(pets: &[Pet], today: NaiveDate) -> Vec<Reminder> {
+    let mut reminders: Vec<_> = pets
+        .iter()
+        .flat_map(|pet| {
+            pet.records
+                .iter()
+                .filter(move |record| is_latest(pet, record))
+                .filter_map(move |record| {
+                    let due = record.next_due?;
+                    Some(Reminder {
+                        pet: pet.name.clone(),
+                        kind: record.kind.clone(),
+                        due,
+                        days_left: (due - today).num_days(),
+                    })
+                })
+        })
+        .filter(|reminder| reminder.days_left <= UPCOMING_DAYS)
+        .collect();
+    reminders.sort_by(|a, b| a.due.cmp(&b.due).then_with(|| a.pet.cmp(&b.pet)));
+    reminders
+}
+
+fn due_style(days_left: i64) -> Style {
+    if days_left < 0 {
+        Style::default().fg(Color::Red).add_modifier(Modifier::BOLD)
+    } else if days_left <= UPCOMING_DAYS {
+        Style::default().fg(Color::Yellow)
+    } else {
+        Style::default()
+    }
+}
+
+fn describe_days(days_left: i64) -> String {
+    match days_left {
+        0 => String::from("due today"),
+        1 => String::from("due tomorrow"),
+        -1 => String::from("1 day overdue"),
+        days if days < 0 => format!("{} days overdue", -days),
+        days => format!("due in {} days", days),
+    }
+}
+
+/// The pet's records, newest first, with due dates colored when they need attention.
+pub fn render_records<'a>(pet: &Pet, today: NaiveDate) -> Table<'a> {
+    let mut records: Vec<_> = pet.records.iter().collect();
+    records.sort_by_key(|record| std::cmp::Reverse(record.date));
+
+    let rows: Vec<_> = records
+        .into_iter()
+        .map(|record| {
+            let due = match record.next_due {
+                // a due date that a later record took care of isn't a worry any more
+                Some(due) if is_latest(pet, record) => Span::styled(
+                    format!("{} ({})", due, describe_days((due - today).num_days())),
+                    due_style((due - today).num_days()),
+                ),
+                Some(due) => Span::styled(due.to_string(), Style::default().fg(Color::DarkGray)),
+                None => Span::raw(""),
+            };
+            Row::new(vec![
+                Cell::from(Span::raw(record.date.to_string())),
+                Cell::from(Span::raw(record.kind.clone())),
+                Cell::from(Span::raw(record.notes.clone())),
+                Cell::from(due),
+            ])
+        })
+        .collect();
+
+    let bold = |title: &'a str| {
+        Cell::from(Span::styled(
+            title,
+            Style::default().add_modifier(Modifier::BOLD),
+        ))
+    };
+    Table::new(rows)
+        .header(Row::new(vec![
+            bold("Date"),
+            bold("Type"),
+            bold("Notes"),
+            bold("Next due"),
+        ]))
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .style(Style::default().fg(Color::White))
+                .title(match pet.records.len() {
+                    0 => String::from("Health records (none yet)"),
+                    n => format!("Health records ({})", n),
+                })
+                .border_type(BorderType::Plain),
+        )
+        .widths(&[
+            Constraint::Length(12),
+            Constraint::Percentage(25),
+            Constraint::Percentage(35),
+            Constraint::Percentage(30),
+        ])
+}
+
+/// Overdue and upcoming items for the Home screen, with `status` after the title if
+/// there's a problem to show.
+pub fn render_reminders<'a>(reminders: &[Reminder], status: &str) -> List<'a> {
+    let items: Vec<_> = if reminders.is_empty() {
+        vec![ListItem::new(Spans::from(vec![Span::styled(
+            format!("nothing due in the next {} days", UPCOMING_DAYS),
+            Style::default().fg(Color::DarkGray),
+        )]))]
+    } else {
+        reminders
+            .iter()
+            .map(|reminder| {
+                ListItem::new(Spans::from(vec![
+                    Span::styled(
+                        format!("{} ", reminder.due),
+                        Style::default().fg(Color::DarkGray),
+                    ),
+                    Span::styled(
+                        reminder.pet.clone(),
+                        Style::default().add_modifier(Modifier::BOLD),
+                    ),
+                    Span::raw(format!(": {} ", reminder.kind)),
+                    Span::styled(
+                        format!("({})", describe_days(reminder.days_left)),
+                        due_style(reminder.days_left),
+                    ),
+                ]))
+            })
+            .collect()
+    };
+
+    let overdue = reminders.iter().filter(|r| r.is_overdue()).count();
+    let mut title = format!("Health reminders ({} overdue)", overdue);
+    if !status.is_empty() {
+        title = format!("{} - {}", title, status);
+    }
+    List::new(items).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .style(Style::default().fg(Color::White))
+            .title(title)
+            .border_type(BorderType::Plain),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    fn record(kind: &str, date: NaiveDate, next_due: Option<NaiveDate>) -> HealthRecord {
+        HealthRecord {
+            date,
+            kind: kind.to_string(),
+            notes: String::new(),
+            next_due,
+        }
+    }
+
+    fn pet(name: &str, records: Vec<HealthRecord>) -> Pet {
+        let mut pet = Pet::new(1, name, "dogs", date(2018, 1, 1), false);
+        pet.records = records;
+        pet
+    }
+
+    fn summary(reminders: &[Reminder]) -> Vec<(&str, &str, i64)> {
+        reminders
+            .iter()
+            .map(|r| (r.pet.as_str(), r.kind.as_str(), r.days_left))
+            .collect()
+    }
+
+    // the clock the reminders are worked out against
+    fn today() -> NaiveDate {
+        date(2024, 3, 1)
+    }
+
+    #[test]
+    fn overdue_due_today_and_the_window_edge() {
+        let pets = vec![pet(
+            "Rex",
+            vec![
+                record("rabies", date(2023, 2, 1), Some(date(2024, 2, 20))),
+                record("checkup", date(2023, 3, 1), Some(today())),
+                record("dental", date(2023, 3, 1), Some(date(2024, 3, 31))),
+                record("worming", date(2023, 3, 1), Some(date(2024, 4, 1))),
+                record("grooming", date(2023, 3, 1), None),
+            ],
+        )];
+        let reminders = reminders(&pets, today());
+        // 30 days out is still listed, 31 isn't
+        assert_eq!(
+            summary(&reminders),
+            [
+                ("Rex", "rabies", -10),
+                ("Rex", "checkup", 0),
+                ("Rex", "dental", 30)
+            ]
+        );
+        assert_eq!(describe_days(-10), "10 days overdue");
+        assert_eq!(describe_days(0), "due today");
+    }
+
+    #[test]
+    fn a_later_record_replaces_the_due_date() {
+        let pets = vec![pet(
+            "Rex",
+            vec![
+                record("Rabies vaccine", date(2023, 2, 1), Some(date(2024, 2, 1))),
+                record("rabies VACCINE", date(2024, 2, 10), Some(date(2025, 2, 10))),
+                // a different type keeps its own due date
+                record("rabies booster", date(2023, 2, 1), Some(date(2024, 2, 1))),
+            ],
+        )];
+        assert_eq!(
+            summary(&reminders(&pets, today())),
+            [("Rex", "rabies booster", -29)]
+        );
+    }
+
+    #[test]
+    fn soonest_first_then_by_pet() {
+        let pets = vec![
+            pet(
+                "Rex",
+                vec![record("checkup", date(2023, 1, 1), Some(date(2024, 3, 5)))],
+            ),
+            pet(
+                "Bo",
+                vec![
+                    record("checkup", date(2023, 1, 1), Some(date(2024, 3, 5))),
+                    record("rabies", date(2023, 1, 1), Some(date(2024, 2, 1))),
+                ],
+            ),
+            pet(
+                "Ada",
+                vec![record("dental", date(2023, 1, 1), Some(date(2024, 3, 20)))],
+            ),
+        ];
+        assert_eq!(
+            summary(&reminders(&pets, today())),
+            [
+                ("Bo", "rabies", -29),
+                ("Bo", "checkup", 4),
+                ("Rex", "checkup", 4),
+                ("Ada", "dental", 19),
+            ]
+        );
+    }
+}
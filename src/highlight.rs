@@ -0,0 +1,151 @@
+use crossterm::{execute, style::Print, terminal::SetTitle};
+use regex::Regex;
+use std::io;
+use std::time::{Duration, Instant};
+use tui::style::Color;
+
+use crate::chat::{self, ChatMessage};
+use crate::config::{ChatConfig, HighlightPattern, NotifyConfig};
+use crate::{Error, TITLE};
+
+const DEFAULT_HIGHLIGHT: Color = Color::Rgb(0x5C, 0x16, 0xC5);
+// title flips every this many ticks while an alert is pending
+const FLASH_TICKS: usize = 5;
+
+enum Matcher {
+    Text(Regex),
+    User(String),
+}
+
+pub struct Highlighter {
+    rules: Vec<(Matcher, Color)>,
+}
+
+impl Highlighter {
+    pub fn new(config: &ChatConfig) -> Result<Highlighter, Error> {
+        let mut rules = vec![];
+        if let Some(nick) = &config.nick {
+            rules.push((Matcher::Text(keyword_regex(nick)?), DEFAULT_HIGHLIGHT));
+        }
+        for rule in &config.highlights {
+            let matcher = match &rule.pattern {
+                HighlightPattern::Keyword(keyword) => Matcher::Text(keyword_regex(keyword)?),
+                HighlightPattern::Regex(pattern) => Matcher::Text(Regex::new(pattern)?),
+                HighlightPattern::User(login) => Matcher::User(login.to_lowercase()),
+            };
+            let color = rule
+                .color
+                .as_deref()
+                .and_then(chat::parse_color)
+                .map(|(r, g, b)| Color::Rgb(r, g, b))
+                .unwrap_or(DEFAULT_HIGHLIGHT);
+            rules.push((matcher, color));
+        }
+        Ok(Highlighter { rules })
+    }
+
+    /// The color of the first rule matching this message, if any.
+    pub fn check(&self, msg: &ChatMessage) -> Option<Color> {
+        self.rules
+            .iter()
+            .find(|(matcher, _)| match matcher {
+                Matcher::Text(regex) => regex.is_match(&msg.text),
+                Matcher::User(login) => msg.login == *login,
+            })
+            .map(|(_, color)| *color)
+    }
+}
+
+// the keyword as a whole word, though a `\b` only goes next to a word character,
+// since one next to `@nick`'s `@` could never match after a space
+fn keyword_regex(keyword: &str) -> Result<Regex, regex::Error> {
+    let is_word = |c: Option<char>| c.is_some_and(|c| c.is_alphanumeric() || c == '_');
+    let boundary = |c| if is_word(c) { r"\b" } else { "" };
+    Regex::new(&format!(
+        "(?i){}{}{}",
+        boundary(keyword.chars().next()),
+        regex::escape(keyword),
+        boundary(keyword.chars().last())
+    ))
+}
+
+/// Rings the bell and flashes the title for mentions that arrive while the user is away.
+///
+/// crossterm can't tell us whether the terminal has focus, so "unfocused" means no key
+/// was pressed for `idle_secs`.
+pub struct Notifier {
+    config: NotifyConfig,
+    last_input: Instant,
+    alert: Option<String>,
+    ticks: usize,
+}
+
+impl Notifier {
+    pub fn new(config: &NotifyConfig) -> Notifier {
+        Notifier {
+            config: config.clone(),
+            last_input: Instant::now(),
+            alert: None,
+            ticks: 0,
+        }
+    }
+
+    pub fn input(&mut self) -> crossterm::Result<()> {
+        self.last_input = Instant::now();
+        if self.alert.take().is_some() {
+            execute!(io::stdout(), SetTitle(TITLE))?;
+        }
+        Ok(())
+    }
+
+    pub fn mention(&mut self, msg: &ChatMessage) -> crossterm::Result<()> {
+        if self.last_input.elapsed() < Duration::from_secs(self.config.idle_secs) {
+            return Ok(());
+        }
+        if self.config.bell {
+            execute!(io::stdout(), Print("\x07"))?;
+        }
+        if self.config.flash_title {
+            self.alert = Some(format!("* {} in #{}", msg.display_name, msg.channel));
+        }
+        Ok(())
+    }
+
+    pub fn tick(&mut self) -> crossterm::Result<()> {
+        if let Some(alert) = &self.alert {
+            self.ticks += 1;
+            if self.ticks.is_multiple_of(FLASH_TICKS) {
+                let title = if (self.ticks / FLASH_TICKS).is_multiple_of(2) {
+                    TITLE
+                } else {
+                    alert.as_str()
+                };
+                execute!(io::stdout(), SetTitle(title))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keywords_match_whole_words() {
+        let nick = keyword_regex("petbot").unwrap();
+        assert!(nick.is_match("hey PetBot, hi"));
+        assert!(!nick.is_match("petbots unite"));
+        assert!(!nick.is_match("notpetbot"));
+
+        let mention = keyword_regex("@petbot").unwrap();
+        assert!(mention.is_match("@petbot hi"));
+        assert!(mention.is_match("hi @PetBot!"));
+        assert!(!mention.is_match("hi @petbots"));
+        assert!(!mention.is_match("petbot"));
+
+        let smiley = keyword_regex(":)").unwrap();
+        assert!(smiley.is_match("nice :)"));
+        assert!(smiley.is_match("nice:)"));
+    }
+}
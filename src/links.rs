@@ -0,0 +1,149 @@
+use chrono::prelude::*;
+use crossterm::{execute, style::Print};
+use regex::Regex;
+use std::io;
+use std::process::{Command, Stdio};
+use std::sync::OnceLock;
+use std::thread;
+use tui::{
+    style::{Color, Modifier, Style},
+    text::{Span, Spans},
+    widgets::{Block, BorderType, Borders, List, ListItem},
+};
+
+use crate::chat::{Channel, ChatMessage, MessageKind};
+use crate::Error;
+
+const MAX_LINKS: usize = 200;
+// punctuation that usually ends the sentence around a link rather than the link
+const TRAILING: &[char] = &['.', ',', '!', '?', ':', ';', ')', ']', '\'', '"'];
+
+static URL: OnceLock<Regex> = OnceLock::new();
+
+#[derive(Clone, Debug)]
+pub struct Link {
+    pub url: String,
+    /// whoever posted it last
+    pub posted_by: String,
+    pub posted_at: DateTime<Utc>,
+    /// times it was posted
+    pub count: usize,
+}
+
+/// URLs posted in a channel, oldest first, each only once.
+#[derive(Default)]
+pub struct Links {
+    pub links: Vec<Link>,
+}
+
+impl Links {
+    pub fn collect(&mut self, msg: &ChatMessage) {
+        if msg.kind != MessageKind::Chat {
+            return;
+        }
+        for url in extract_urls(&msg.text) {
+            let count = match self.links.iter().position(|link| link.url == url) {
+                // a repost moves it back to the top
+                Some(index) => self.links.remove(index).count + 1,
+                None => 1,
+            };
+            if self.links.len() >= MAX_LINKS {
+                self.links.remove(0);
+            }
+            self.links.push(Link {
+                url,
+                posted_by: msg.display_name.clone(),
+                posted_at: msg.sent_at,
+                count,
+            });
+        }
+    }
+
+    /// Links newest first, the order they're listed in.
+    pub fn newest_first(&self) -> impl Iterator<Item = &Link> {
+        self.links.iter().rev()
+    }
+}
+
+/// `http(s)://` and `www.` links in `text`, without trailing punctuation.
+pub fn extract_urls(text: &str) -> Vec<String> {
+    let url = URL.get_or_init(|| {
+        Regex::new(r"(?i)\b(?:https?://|www\.)[^\s<>]+").expect("URL pattern compiles")
+    });
+    url.find_iter(text)
+        .map(|found| found.as_str().trim_end_matches(TRAILING).to_string())
+        .filter(|url| url.contains('.'))
+        .collect()
+}
+
+/// Puts `text` on the clipboard with an OSC 52 escape, which most terminals
+/// (and tmux with `set-clipboard on`) forward to the system clipboard.
+pub fn copy_to_clipboard(text: &str) -> crossterm::Result<()> {
+    execute!(
+        io::stdout(),
+        Print(format!("\x1b]52;c;{}\x07", base64::encode(text)))
+    )
+}
+
+/// Runs `command` with the URL as its last argument, e.g. `xdg-open` or `firefox --new-tab`.
+pub fn open_url(command: &str, url: &str) -> Result<(), Error> {
+    let mut words = command.split_whitespace();
+    let program = words
+        .next()
+        .ok_or_else(|| Error::CommandError(String::from("open_command is empty")))?;
+    let mut child = Command::new(program)
+        .args(words)
+        .arg(url)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    // reap it once it exits, or every opened link would leave a zombie behind
+    thread::spawn(move || child.wait());
+    Ok(())
+}
+
+pub fn render_links<'a>(channel: &Channel) -> List<'a> {
+    let items: Vec<_> = channel
+        .links
+        .newest_first()
+        .map(|link| {
+            let mut spans = vec![
+                Span::styled(
+                    format!(
+                        "{} {} ",
+                        link.posted_at.with_timezone(&Local).format("%H:%M"),
+                        link.posted_by
+                    ),
+                    Style::default().fg(Color::Gray),
+                ),
+                Span::styled(
+                    link.url.clone(),
+                    Style::default()
+                        .fg(Color::LightBlue)
+                        .add_modifier(Modifier::UNDERLINED),
+                ),
+            ];
+            if link.count > 1 {
+                spans.push(Span::styled(
+                    format!(" x{}", link.count),
+                    Style::default().fg(Color::DarkGray),
+                ));
+            }
+            ListItem::new(Spans::from(spans))
+        })
+        .collect();
+
+    List::new(items)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .style(Style::default().fg(Color::White).bg(Color::Black))
+                .title(format!(
+                    "Links in {} (c copies, o opens, Esc closes)",
+                    channel.title()
+                ))
+                .border_type(BorderType::Rounded),
+        )
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+}
@@ -0,0 +1,530 @@
+use chrono::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::chat::ChatMessage;
+use crate::Error;
+
+pub const LOGS_DIR: &str = "./data/logs";
+const INDEX_FILE: &str = "index.json";
+
+/// Appends `msg` to `data/logs/<channel>/<YYYY-MM-DD>.jsonl`, one JSON message per line.
+pub fn append(msg: &ChatMessage) -> Result<(), Error> {
+    let path = log_path(&msg.channel, msg.sent_at.naive_utc().date());
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut line = serde_json::to_vec(msg)?;
+    line.push(b'\n');
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?
+        .write_all(&line)?;
+    Ok(())
+}
+
+pub fn log_path(channel: &str, date: NaiveDate) -> PathBuf {
+    Path::new(LOGS_DIR)
+        .join(channel)
+        .join(format!("{}.jsonl", date.format("%Y-%m-%d")))
+}
+
+/// The day a log file covers, from its name.
+pub fn log_date(path: &Path) -> Option<NaiveDate> {
+    let stem = path.file_stem()?.to_str()?;
+    NaiveDate::parse_from_str(stem, "%Y-%m-%d").ok()
+}
+
+/// Every logged message of `channel` from `since` to `until` (both included), oldest
+/// first, and how many unreadable lines and files were skipped, e.g. a line still being written.
+pub fn read_channel(
+    channel: &str,
+    since: Option<NaiveDate>,
+    until: Option<NaiveDate>,
+) -> Result<(Vec<ChatMessage>, usize), Error> {
+    read_channel_in(Path::new(LOGS_DIR), channel, since, until)
+}
+
+fn read_channel_in(
+    dir: &Path,
+    channel: &str,
+    since: Option<NaiveDate>,
+    until: Option<NaiveDate>,
+) -> Result<(Vec<ChatMessage>, usize), Error> {
+    let mut messages = vec![];
+    let mut skipped = 0;
+    let channel_dir = dir.join(channel.trim_start_matches('#'));
+    for path in log_files(dir)?
+        .into_iter()
+        .filter(|path| path.starts_with(&channel_dir))
+    {
+        let in_range = log_date(&path).is_some_and(|date| {
+            since.is_none_or(|since| date >= since) && until.is_none_or(|until| date <= until)
+        });
+        if !in_range {
+            continue;
+        }
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(_) => {
+                skipped += 1;
+                continue;
+            }
+        };
+        for line in content.lines() {
+            match serde_json::from_str(line) {
+                Ok(msg) => messages.push(msg),
+                Err(_) => skipped += 1,
+            }
+        }
+    }
+    Ok((messages, skipped))
+}
+
+/// Up to `radius` messages either side of the one at `offset`, with the index of that one.
+pub fn read_context(
+    path: &Path,
+    offset: u64,
+    radius: usize,
+) -> Result<(Vec<ChatMessage>, usize), Error> {
+    let content = fs::read_to_string(path)?;
+    let mut lines = vec![];
+    let mut hit = None;
+    let mut at = 0;
+    for line in content.split_inclusive('\n') {
+        if at == offset {
+            hit = Some(lines.len());
+        }
+        at += line.len() as u64;
+        lines.push(line);
+    }
+    let hit = hit.ok_or_else(|| {
+        Error::CommandError(String::from(
+            "the log has changed since the search, search again",
+        ))
+    })?;
+    let start = hit.saturating_sub(radius);
+    let messages = lines[start..(hit + radius + 1).min(lines.len())]
+        .iter()
+        .map(|line| serde_json::from_str(line))
+        .collect::<Result<_, _>>()?;
+    Ok((messages, hit - start))
+}
+
+#[derive(Serialize, Deserialize)]
+struct IndexedFile {
+    /// relative to the logs directory
+    path: String,
+    /// bytes of the file already in `postings`, always ending on a line break
+    len: u64,
+    /// the file's length and modification time when it was last indexed
+    seen: Option<(u64, SystemTime)>,
+}
+
+/// A word index over every log file, caught up with whatever was appended
+/// since the last search and kept in `data/logs/index.json` between sessions.
+#[derive(Serialize, Deserialize, Default)]
+pub struct LogIndex {
+    // the logs directory, `LOGS_DIR` outside of tests
+    #[serde(skip)]
+    dir: PathBuf,
+    // whether anything was indexed since the index was loaded or saved
+    #[serde(skip)]
+    unsaved: bool,
+    files: Vec<IndexedFile>,
+    // lowercased word, or "@login" for the author -> (file, byte offset of the line)
+    postings: BTreeMap<String, Vec<(usize, u64)>>,
+}
+
+impl LogIndex {
+    /// Loads the saved index; a missing or unreadable one is rebuilt from scratch.
+    pub fn open() -> LogIndex {
+        LogIndex::open_in(Path::new(LOGS_DIR))
+    }
+
+    fn open_in(dir: &Path) -> LogIndex {
+        let mut index: LogIndex = fs::read_to_string(dir.join(INDEX_FILE))
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default();
+        index.dir = dir.to_path_buf();
+        index
+    }
+
+    /// Indexes lines added to the logs since the last update.
+    pub fn update(&mut self) -> Result<(), Error> {
+        for path in log_files(&self.dir)? {
+            let name = path
+                .strip_prefix(&self.dir)
+                .unwrap_or(&path)
+                .to_string_lossy()
+                .to_string();
+            let file = match self.files.iter().position(|file| file.path == name) {
+                Some(file) => file,
+                None => {
+                    self.files.push(IndexedFile {
+                        path: name,
+                        len: 0,
+                        seen: None,
+                    });
+                    self.files.len() - 1
+                }
+            };
+            self.unsaved |= self.index_file(file, &path)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the index out if it changed, so the next session starts from it. Called
+    /// on quit rather than after every search, the whole file being rewritten each time.
+    pub fn save(&mut self) -> Result<(), Error> {
+        if self.unsaved {
+            fs::write(self.dir.join(INDEX_FILE), &serde_json::to_vec(self)?)?;
+            self.unsaved = false;
+        }
+        Ok(())
+    }
+
+    fn index_file(&mut self, file: usize, path: &Path) -> Result<bool, Error> {
+        let mut handle = fs::File::open(path)?;
+        let metadata = handle.metadata()?;
+        let seen = Some((metadata.len(), metadata.modified()?));
+        if self.files[file].seen == seen {
+            return Ok(false);
+        }
+        // logs only grow, anything else means the file was rewritten and its offsets are stale
+        if self.files[file]
+            .seen
+            .is_some_and(|(len, _)| metadata.len() <= len)
+            || metadata.len() < self.files[file].len
+        {
+            self.forget(file);
+        }
+        self.files[file].seen = seen;
+        let start = self.files[file].len;
+        if metadata.len() <= start {
+            return Ok(true);
+        }
+        handle.seek(SeekFrom::Start(start))?;
+        let mut new = String::new();
+        handle.read_to_string(&mut new)?;
+        // leave a line that's still being written for next time
+        let complete = match new.rfind('\n') {
+            Some(end) => &new[..=end],
+            None => return Ok(true),
+        };
+
+        let mut offset = start;
+        for line in complete.split_inclusive('\n') {
+            if let Ok(msg) = serde_json::from_str::<ChatMessage>(line) {
+                let mut words = words(&msg.text);
+                words.push(format!("@{}", msg.login));
+                words.sort();
+                words.dedup();
+                for word in words {
+                    self.postings.entry(word).or_default().push((file, offset));
+                }
+            }
+            offset += line.len() as u64;
+        }
+        self.files[file].len = offset;
+        Ok(true)
+    }
+
+    // drops the postings of `file` so it's indexed again from the start
+    fn forget(&mut self, file: usize) {
+        for postings in self.postings.values_mut() {
+            postings.retain(|(indexed, _)| *indexed != file);
+        }
+        self.postings.retain(|_, postings| !postings.is_empty());
+        self.files[file].len = 0;
+    }
+
+    /// Lines that contain a word starting with each of `terms` and were written by
+    /// `author` if given, as (log file, byte offset), limited to files for which
+    /// `keep_file` holds.
+    pub fn candidates(
+        &self,
+        terms: &[String],
+        author: Option<&str>,
+        keep_file: impl Fn(&Path) -> bool,
+    ) -> Vec<(PathBuf, u64)> {
+        let mut sets = terms
+            .iter()
+            .map(|term| self.prefixed(term))
+            .collect::<Vec<_>>();
+        if let Some(author) = author {
+            let mut by = self
+                .postings
+                .get(&format!("@{}", author.to_lowercase()))
+                .cloned()
+                .unwrap_or_default();
+            by.sort_unstable();
+            sets.push(by);
+        }
+        let mut sets = sets.into_iter();
+        let mut found = match sets.next() {
+            Some(first) => first,
+            None => return vec![],
+        };
+        for set in sets {
+            found.retain(|posting| set.binary_search(posting).is_ok());
+        }
+
+        found
+            .into_iter()
+            .map(|(file, offset)| (self.dir.join(&self.files[file].path), offset))
+            .filter(|(path, _)| keep_file(path))
+            .collect()
+    }
+
+    // postings of every word starting with `prefix`, sorted
+    fn prefixed(&self, prefix: &str) -> Vec<(usize, u64)> {
+        let mut found: Vec<_> = self
+            .postings
+            .range(prefix.to_string()..)
+            .take_while(|(word, _)| word.starts_with(prefix))
+            .flat_map(|(_, postings)| postings.iter().copied())
+            .collect();
+        found.sort_unstable();
+        found.dedup();
+        found
+    }
+}
+
+/// Lowercased words of `text`, the way the index splits them.
+pub fn words(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// Reads the messages on the lines starting at each of `offsets`, opening the file once.
+pub fn read_messages(path: &Path, offsets: &[u64]) -> Result<Vec<ChatMessage>, Error> {
+    let mut file = BufReader::new(fs::File::open(path)?);
+    let mut messages = vec![];
+    let mut line = String::new();
+    for &offset in offsets {
+        file.seek(SeekFrom::Start(offset))?;
+        line.clear();
+        file.read_line(&mut line)?;
+        messages.push(serde_json::from_str(&line)?);
+    }
+    Ok(messages)
+}
+
+fn log_files(dir: &Path) -> Result<Vec<PathBuf>, Error> {
+    let mut files = vec![];
+    let channels = match fs::read_dir(dir) {
+        Ok(channels) => channels,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(files),
+        Err(e) => return Err(e.into()),
+    };
+    for channel in channels {
+        let channel = channel?.path();
+        if !channel.is_dir() {
+            continue;
+        }
+        for log in fs::read_dir(channel)? {
+            let log = log?.path();
+            if log.extension().is_some_and(|ext| ext == "jsonl") {
+                files.push(log);
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // an empty logs directory of its own for each test
+    fn logs_dir(test: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("logs-{}-{}", std::process::id(), test));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("petshop")).unwrap();
+        dir
+    }
+
+    fn lines(messages: &[(&str, &str)]) -> String {
+        let at = Utc.with_ymd_and_hms(2024, 3, 1, 12, 0, 0).unwrap();
+        messages
+            .iter()
+            .map(|(login, text)| {
+                serde_json::to_string(&ChatMessage::chat(login, text, at)).unwrap() + "\n"
+            })
+            .collect()
+    }
+
+    // the text of every candidate, in file order
+    fn found(index: &LogIndex, terms: &[&str], author: Option<&str>) -> Vec<String> {
+        let terms: Vec<_> = terms.iter().map(|term| term.to_string()).collect();
+        index
+            .candidates(&terms, author, |_| true)
+            .into_iter()
+            .map(|(path, offset)| read_messages(&path, &[offset]).unwrap().remove(0).text)
+            .collect()
+    }
+
+    #[test]
+    fn words_are_found_by_their_start() {
+        let dir = logs_dir("prefixes");
+        let log = dir.join("petshop").join("2024-03-01.jsonl");
+        fs::write(
+            &log,
+            lines(&[
+                ("ann", "Hello there!"),
+                ("bob", "well, hello"),
+                ("ann", "yellow"),
+            ]),
+        )
+        .unwrap();
+        let mut index = LogIndex::open_in(&dir);
+        index.update().unwrap();
+
+        assert_eq!(
+            found(&index, &["hel"], None),
+            ["Hello there!", "well, hello"]
+        );
+        // the middle of a word isn't the start of one
+        assert!(found(&index, &["ello"], None).is_empty());
+        // every term, in any order
+        assert_eq!(found(&index, &["hel", "we"], None), ["well, hello"]);
+        assert_eq!(found(&index, &["the", "hello"], None), ["Hello there!"]);
+        assert_eq!(found(&index, &[], Some("Ann")), ["Hello there!", "yellow"]);
+        assert_eq!(found(&index, &["hello"], Some("bob")), ["well, hello"]);
+        assert!(found(&index, &[], None).is_empty());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn appended_lines_are_indexed_once_complete() {
+        let dir = logs_dir("appends");
+        let log = dir.join("petshop").join("2024-03-01.jsonl");
+        fs::write(&log, lines(&[("ann", "first")])).unwrap();
+        let mut index = LogIndex::open_in(&dir);
+        index.update().unwrap();
+
+        let second = lines(&[("bob", "second")]);
+        let (written, rest) = second.split_at(20);
+        OpenOptions::new()
+            .append(true)
+            .open(&log)
+            .unwrap()
+            .write_all(written.as_bytes())
+            .unwrap();
+        index.update().unwrap();
+        assert!(found(&index, &["second"], None).is_empty());
+
+        OpenOptions::new()
+            .append(true)
+            .open(&log)
+            .unwrap()
+            .write_all(rest.as_bytes())
+            .unwrap();
+        index.update().unwrap();
+        assert_eq!(found(&index, &["second"], None), ["second"]);
+        assert_eq!(found(&index, &["first"], None), ["first"]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn rewritten_files_are_indexed_again() {
+        let dir = logs_dir("rewrites");
+        let log = dir.join("petshop").join("2024-03-01.jsonl");
+        fs::write(&log, lines(&[("ann", "old news"), ("ann", "older news")])).unwrap();
+        let mut index = LogIndex::open_in(&dir);
+        index.update().unwrap();
+
+        fs::write(&log, lines(&[("bob", "fresh")])).unwrap();
+        index.update().unwrap();
+        assert!(found(&index, &["news"], None).is_empty());
+        assert_eq!(found(&index, &["fresh"], None), ["fresh"]);
+
+        // a hit from before the rewrite doesn't open on some other line
+        assert!(read_context(&log, 1000, 5).is_err());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn the_index_is_only_written_when_saved() {
+        let dir = logs_dir("saves");
+        let log = dir.join("petshop").join("2024-03-01.jsonl");
+        fs::write(&log, lines(&[("ann", "kept")])).unwrap();
+        let mut index = LogIndex::open_in(&dir);
+        index.update().unwrap();
+        assert!(!dir.join(INDEX_FILE).exists());
+
+        index.save().unwrap();
+        let reopened = LogIndex::open_in(&dir);
+        assert_eq!(found(&reopened, &["kept"], None), ["kept"]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn exports_skip_bad_lines() {
+        let dir = logs_dir("exports");
+        let day = |d| NaiveDate::from_ymd_opt(2024, 3, d).unwrap();
+        let mut first = lines(&[("ann", "one"), ("bob", "two")]);
+        first.insert_str(first.find('\n').unwrap() + 1, "not json\n");
+        fs::write(dir.join("petshop").join("2024-03-01.jsonl"), first).unwrap();
+        // cut off while being written
+        let second = lines(&[("ann", "three"), ("bob", "four")]);
+        fs::write(
+            dir.join("petshop").join("2024-03-02.jsonl"),
+            &second[..second.len() - 10],
+        )
+        .unwrap();
+        fs::write(
+            dir.join("petshop").join("2024-03-03.jsonl"),
+            lines(&[("cy", "five")]),
+        )
+        .unwrap();
+
+        let texts = |(messages, skipped): (Vec<ChatMessage>, usize)| {
+            let texts: Vec<_> = messages.into_iter().map(|msg| msg.text).collect();
+            (texts, skipped)
+        };
+        assert_eq!(
+            texts(read_channel_in(&dir, "#petshop", None, Some(day(2))).unwrap()),
+            (
+                vec![
+                    String::from("one"),
+                    String::from("two"),
+                    String::from("three")
+                ],
+                2
+            )
+        );
+        assert_eq!(
+            texts(read_channel_in(&dir, "petshop", Some(day(3)), None).unwrap()),
+            (vec![String::from("five")], 0)
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn context_around_a_line() {
+        let dir = logs_dir("context");
+        let log = dir.join("petshop").join("2024-03-01.jsonl");
+        let content = lines(&[("a", "one"), ("b", "two"), ("c", "three"), ("d", "four")]);
+        fs::write(&log, &content).unwrap();
+        let offset = content.find("\n").unwrap() as u64 + 1;
+
+        let (messages, hit) = read_context(&log, offset, 1).unwrap();
+        let texts: Vec<_> = messages.iter().map(|msg| msg.text.as_str()).collect();
+        assert_eq!(texts, ["one", "two", "three"]);
+        assert_eq!(hit, 1);
+        // the middle of a line isn't a line
+        assert!(read_context(&log, offset + 1, 1).is_err());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -1,14 +1,15 @@
 use chrono::prelude::*;
 use crossterm::{
-    event::{self, Event as CEvent, KeyCode},
+    event::{self, Event as CEvent, KeyCode, KeyModifiers},
     terminal::{disable_raw_mode, enable_raw_mode, size as ctsize, SetTitle, SetSize},
     execute,
 };
 use rand::{distributions::Alphanumeric, prelude::*};
 use serde::{Deserialize, Serialize};
-use std::{fs::{self, DirEntry}, path::PathBuf};
+use std::collections::BTreeMap;
+use std::fs;
 use std::io;
-use std::sync::mpsc;
+use std::sync::mpsc::{self, Sender};
 use std::thread;
 use std::time::{Duration, Instant};
 use thiserror::Error;
@@ -16,16 +17,73 @@ use tui::{
     backend::CrosstermBackend,
     layout::{Alignment, Constraint, Direction, Layout},
     style::{Color, Modifier, Style},
-    text::{Span, Spans},
+    text::{Span, Spans, Text},
     widgets::{
-        Block, BorderType, Borders, Cell, List, ListItem, ListState, Paragraph, Row, Table, Tabs,
+        Block, BorderType, Borders, Cell, Clear, List, ListItem, ListState, Paragraph, Row, Table,
+        Tabs,
     },
     Terminal,
 };
-use std::path::Path;
-use std::env::current_dir;
+
+mod activity;
+mod age;
+mod audit;
+mod bench;
+mod bot;
+mod categories;
+mod chat;
+mod cli;
+mod commands;
+mod config;
+mod dashboard;
+mod duplicates;
+mod events;
+mod export;
+mod filter;
+mod health;
+mod highlight;
+mod links;
+mod logs;
+mod moderation;
+mod pet_view;
+mod poll;
+mod prompt;
+mod query;
+mod replay;
+mod schema;
+mod search;
+mod store;
+mod trash;
+mod triggers;
+mod viewers;
+mod whispers;
+
+use age::{Age, PetSort};
+use audit::{Action, AuditEntry, History, Source};
+use bot::{Bot, BotAction};
+use chat::{Channel, ChannelKind, ChatMessage, ChatSource, MessageKind, TwitchIrc};
+use commands::ChatCommand;
+use cli::{Command, ExportOptions, PetsCommand};
+use config::{ChatConfig, PollConfig};
+use dashboard::Dashboard;
+use query::Query;
+use duplicates::{Candidate, Compare};
+use events::ChatEvent;
+use export::EXPORT_FORMATS;
+use filter::Filter;
+use highlight::{Highlighter, Notifier};
+use moderation::MOD_ACTIONS;
+use pet_view::PetView;
+use poll::Poll;
+use replay::{Recorder, Replay};
+use schema::Db;
+use search::{HitLocation, Search};
+use store::Store;
+use triggers::Triggers;
+use tui_input::Input;
 
 const DB_PATH: &str = "./data/db.json";
+const TITLE: &str = "taken over by Rust";
 
 #[derive(Error, Debug)]
 pub enum Error {
@@ -33,11 +91,20 @@ pub enum Error {
     ReadDBError(#[from] io::Error),
     #[error("error parsing the DB file: {0}")]
     ParseDBError(#[from] serde_json::Error),
+    #[error("invalid pattern: {0}")]
+    PatternError(#[from] regex::Error),
+    #[error("{0}")]
+    CommandError(String),
+    #[error("unsupported DB file: {0}")]
+    SchemaError(String),
+    #[error("column {column}: {message}")]
+    QueryError { column: usize, message: String },
 }
 
 enum Event<I> {
     Input(I),
     Tick,
+    Chat(String),
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -45,8 +112,77 @@ struct Pet {
     id: usize,
     name: String,
     category: String,
-    age: usize,
+    birthdate: NaiveDate,
+    /// the birthdate was worked out from an age rather than known
+    #[serde(default)]
+    birthdate_estimated: bool,
     created_at: DateTime<Utc>,
+    #[serde(default)]
+    breed: Option<String>,
+    #[serde(default)]
+    sex: Option<Sex>,
+    /// in kilograms
+    #[serde(default)]
+    weight: Option<f64>,
+    #[serde(default)]
+    color: Option<String>,
+    #[serde(default)]
+    owner: Option<String>,
+    #[serde(default)]
+    notes: Option<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    updated_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    records: Vec<health::HealthRecord>,
+    /// when it was moved to the trash
+    #[serde(default)]
+    deleted_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum Sex {
+    Female,
+    Male,
+}
+
+impl Pet {
+    /// A pet added now; `id` comes from `next_pet_id`.
+    fn new(id: usize, name: &str, category: &str, birthdate: NaiveDate, birthdate_estimated: bool) -> Pet {
+        let now = Utc::now();
+        Pet {
+            id,
+            name: name.to_owned(),
+            category: category.to_owned(),
+            birthdate,
+            birthdate_estimated,
+            created_at: now,
+            breed: None,
+            sex: None,
+            weight: None,
+            color: None,
+            owner: None,
+            notes: None,
+            tags: vec![],
+            updated_at: Some(now),
+            records: vec![],
+            deleted_at: None,
+        }
+    }
+
+    fn age(&self, today: NaiveDate) -> Age {
+        Age::between(self.birthdate, today)
+    }
+
+    /// The age as shown, `~` marking an estimate.
+    fn age_label(&self, today: NaiveDate) -> String {
+        match self.birthdate_estimated {
+            true => format!("~{}", self.age(today)),
+            false => self.age(today).to_string(),
+        }
+    }
 }
 
 #[derive(Copy, Clone, Debug)]
@@ -54,6 +190,19 @@ enum MenuItem {
     Home,
     Pets,
     Channels,
+    Categories,
+    Dashboard,
+    History,
+    Trash,
+    Duplicates,
+}
+
+/// Which part of the Channels tab the arrow keys move through.
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum ChatFocus {
+    Channels,
+    Messages,
+    Viewers,
 }
 
 impl From<MenuItem> for usize {
@@ -61,7 +210,12 @@ impl From<MenuItem> for usize {
         match input {
             MenuItem::Home => 0,
             MenuItem::Pets => 1,
-            MenuItem::Channels=> 2,
+            MenuItem::Channels => 2,
+            MenuItem::Categories => 3,
+            MenuItem::Dashboard => 4,
+            MenuItem::History => 5,
+            MenuItem::Trash => 6,
+            MenuItem::Duplicates => 7,
         }
     }
 }
@@ -69,10 +223,58 @@ impl From<MenuItem> for usize {
 
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let options = match cli::parse_args(std::env::args().skip(1)) {
+        Ok(Command::Tui(options)) => options,
+        Ok(Command::Export(options)) => {
+            if let Err(e) = export_logs(&options) {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+            return Ok(());
+        }
+        Ok(Command::Pets(command)) => {
+            if let Err(e) = run_pets_command(&command) {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+            return Ok(());
+        }
+        Ok(Command::Bench(options)) => {
+            if let Err(e) = bench::run(&options) {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+            return Ok(());
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(2);
+        }
+    };
     let (cols, rows) = ctsize()?;
+    let mut chat_config = config::read_config()?;
+    purge_expired_trash(chat_config.trash.retention_days)?;
+    let mut recorder = options.record.as_deref().map(Recorder::create).transpose()?;
+    let mut channel_names = chat_config.channels.clone();
+    let source: Box<dyn ChatSource> = match &options.replay {
+        Some(path) => {
+            let replay = Replay::open(path, options.speed)?;
+            // only for this run, the config keeps the channels it had
+            let recorded = replay.channels();
+            if !recorded.is_empty() {
+                channel_names = recorded;
+            }
+            Box::new(replay)
+        }
+        None => Box::new(TwitchIrc { config: chat_config.clone() }),
+    };
+    let highlighter = Highlighter::new(&chat_config)?;
+    let mut filter = Filter::new(&chat_config.filters)?;
+    let mut notifier = Notifier::new(&chat_config.notify);
     enable_raw_mode().expect("can run in raw mode");
 
     let (tx, rx) = mpsc::channel();
+    let chat_tx = tx.clone();
     let tick_rate = Duration::from_millis(200);
     thread::spawn(move || {
         let mut last_tick = Instant::now();
@@ -87,54 +289,161 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                 }
             }
 
-            if last_tick.elapsed() >= tick_rate {
-                if let Ok(_) = tx.send(Event::Tick) {
-                    last_tick = Instant::now();
-                }
+            if last_tick.elapsed() >= tick_rate && tx.send(Event::Tick).is_ok() {
+                last_tick = Instant::now();
             }
         }
     });
 
+    let mut channels: Vec<Channel> = channel_names.iter().map(|name| Channel::new(name)).collect();
+    channels.push(Channel::mentions());
+    channels.push(Channel::bot_log());
+    // a bad whisper file shouldn't keep the rest of the app from starting
+    let whispers_status = match whispers::load_conversations() {
+        Ok((conversations, skipped)) => {
+            channels.extend(conversations);
+            match skipped {
+                0 => String::new(),
+                n => format!("skipped {} unreadable whisper lines or files", n),
+            }
+        }
+        Err(e) => format!("couldn't load earlier whispers: {}", e),
+    };
+    let mut bot = Bot::new(&chat_config.bot);
+    let mut triggers = Triggers::load()?;
+    let mut log_index = logs::LogIndex::open();
+    let (out_tx, out_rx) = mpsc::channel();
+    thread::spawn(move || {
+        // without a connection the Channels tab just stays empty
+        let _ = source.run(&chat_tx, out_rx);
+    });
+
     let stdout = io::stdout();
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
     terminal.clear()?;
 
-    let menu_titles = vec![
+    let menu_titles = [
         "Home(F1)",
         "Channels(F2)",
         "Pets(F3)",
+        "Categories(F5)",
+        "Dashboard(F6)",
+        "History(F7)",
+        "Trash(F8)",
+        "Duplicates(F9)",
         "Quit(F4)"
     ];
-    let pets_submenu_actions = vec![
+    let pets_submenu_actions = [
         "Add",
-        "Delete"
+        "Delete",
+        "Sort",
+        "History",
+        "Go to"
     ];
     let mut active_menu_item = MenuItem::Home;
-    let mut pet_list_state = ListState::default();
+    let mut pet_view = PetView::default();
+    // list rows on screen as of the last frame, how far a page goes
+    let mut pet_rows = 1;
+    // the row number being typed to jump to
+    let mut pet_jump: Option<Input> = None;
+    let mut pet_sort = PetSort::Added;
+    // the applied filter, as typed and parsed
+    let mut pet_query: Option<(String, Query)> = None;
+    let mut pet_filter_input: Option<Input> = None;
+    // where the query being typed went wrong, and why
+    let mut pet_filter_problem: Option<(usize, String)> = None;
+    // the store as the Categories and Trash tabs show it
+    let mut store = Store::default();
+    // the Home tab's reminders and the day they're for, worked out again when the store or the day changes
+    let mut reminders: Option<(NaiveDate, Vec<health::Reminder>)> = None;
+    let mut reminders_status = String::new();
+    let mut categories_state = ListState::default();
+    // the name being typed, for the category being renamed or a new one
+    let mut category_input: Option<(Option<String>, Input)> = None;
+    // the category waiting for a target to be merged into
+    let mut category_merge: Option<String> = None;
+    let mut category_status = String::new();
+    // why the last add or delete on the Pets tab failed
+    let mut pet_status = String::new();
+    let mut dashboard = Dashboard::default();
+    let mut history = History::default();
+    let mut history_state = ListState::default();
+    // the pet the History tab is narrowed to, by id and name
+    let mut history_pet: Option<(usize, String)> = None;
+    let mut history_status = String::new();
+    let mut trash_state = ListState::default();
+    let mut trash_status = String::new();
+    let mut duplicate_candidates: Vec<Candidate> = vec![];
+    let mut duplicates_state = ListState::default();
+    // the pair being merged
+    let mut compare: Option<Compare> = None;
+    let mut duplicates_status = String::new();
     let mut chans_list_state = ListState::default();
-    pet_list_state.select(Some(0));
+    let mut prompt: Option<Input> = None;
+    let mut prompt_status = whispers_status;
+    let mut chat_focus = ChatFocus::Channels;
+    // message rows on screen as of the last frame
+    let mut chat_rows = 1;
+    let mut mod_popup: Option<ListState> = None;
+    let mut show_viewers = false;
+    let mut viewer_query = String::new();
+    // whether typed characters go to `viewer_query`, opened with `/`
+    let mut viewer_typing = false;
+    let mut viewer_selected: Option<usize> = None;
+    let mut viewer_list_state = ListState::default();
+    let mut user_popup: Option<String> = None;
+    let mut search: Option<Search> = None;
+    let mut links_popup: Option<ListState> = None;
+    // the format list, with the messages to export and a title for them
+    let mut export_popup: Option<(ListState, Vec<ChatMessage>, String)> = None;
     chans_list_state.select(Some(0));
-    let mut cwd = current_dir().unwrap();
-    // let cur1 = terminal.get_cursor().unwrap_or_else(|_e|(u16::MAX, u16::MAX)).0;
-    // let cur2 = terminal.get_cursor().unwrap_or_else(|_e|(u16::MAX, u16::MAX)).1;
-    // terminal.show_cursor().unwrap_or_else(|e| cur1 = e.to_string());
-    // terminal.set_cursor(50, 50).unwrap_or_else(|e| cur1 = e.to_string());
-    // NOTE: does not 'freeze' terminal
-    let cur1 = String::from("N/A");
-    let cur2 = String::from("N/A");
-    // let c1: u16;
-    // let c2: u16;
-    // (c1, c2) = terminal.get_cursor().unwrap_or_else(|e| {
-    //     cur1 = String::from("could not get cursor");
-    //     cur2 = e.to_string();
-    //     (u16::MAX, u16::MAX)
-    // });
-    // cur1 = c1.to_string();
-    // cur2 = c2.to_string();
-
-    execute!(std::io::stdout(), SetTitle("taken over by Rust"))?;
+    categories_state.select(Some(0));
+    history_state.select(Some(0));
+    trash_state.select(Some(0));
+
+    execute!(std::io::stdout(), SetTitle(TITLE))?;
     loop {
+        if let (MenuItem::Channels, Some(selected)) = (active_menu_item, chans_list_state.selected()) {
+            channels[selected].unread = 0;
+        }
+        if let MenuItem::Pets = active_menu_item {
+            pet_view.refresh(pet_sort, pet_query.as_ref(), Local::now().date_naive()).expect("can fetch pet list");
+        }
+        if let MenuItem::Home = active_menu_item {
+            match store.refresh() {
+                Ok(true) => {
+                    reminders = None;
+                    reminders_status.clear();
+                }
+                Ok(false) => {}
+                Err(e) => reminders_status = format!("can't read the store: {}", e),
+            }
+            let today = Local::now().date_naive();
+            if reminders.as_ref().is_none_or(|(day, _)| *day != today) {
+                reminders = Some((today, health::reminders(&store.db.pets, today)));
+            }
+        }
+        if let MenuItem::Categories | MenuItem::Trash = active_menu_item {
+            if let Err(e) = store.refresh() {
+                let status = format!("can't read the store: {}", e);
+                match active_menu_item {
+                    MenuItem::Categories => category_status = status,
+                    _ => trash_status = status,
+                }
+            }
+            // an edit can leave the selection past the end
+            categories_state.select(Some(categories_state.selected().unwrap_or(0).min(store.db.categories.len().saturating_sub(1))));
+            trash_state.select(Some(trash_state.selected().unwrap_or(0).min(store.db.trash.len().saturating_sub(1))));
+        }
+        if let MenuItem::History = active_menu_item {
+            if let Err(e) = history.refresh(history_pet.as_ref().map(|(id, _)| *id)) {
+                history_status = format!("can't read the audit log: {}", e);
+            }
+        }
+        if let MenuItem::Dashboard = active_menu_item {
+            dashboard.refresh(Local::now().date_naive()).expect("can fetch pet list");
+        }
         terminal.draw(|rect| {
             let size = rect.size();
             let chunks = Layout::default()
@@ -150,19 +459,14 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                 )
                 .split(size);
 
-            let copyright_text = std::format!("area: {} | top: {} | bottom: {} | left: {} | right: {} | cursor: {},{}", 
-                size.area(), size.top(), size.bottom(), size.left(), size.right(), cur1, cur2);
-
-            let copyright = Paragraph::new(copyright_text)
-                .style(Style::default().fg(Color::LightCyan))
-                .alignment(Alignment::Center)
-                .block(
-                    Block::default()
-                        .borders(Borders::ALL)
-                        .style(Style::default().fg(Color::White))
-                        .title("Stats")
-                        .border_type(BorderType::Plain),
-                );
+            let stats_chunks = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
+                .split(chunks[2]);
+            let stats_chan = &channels[chans_list_state.selected().unwrap_or(0)];
+            let now = Utc::now();
+            let today = now.with_timezone(&Local).date_naive();
+            let per_minute = stats_chan.activity.per_minute(now, stats_chunks[0].width.saturating_sub(2) as usize);
 
             let menu = menu_titles
                 .iter()
@@ -189,12 +493,20 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
             rect.render_widget(tabs, chunks[0]);
             match active_menu_item {
-                MenuItem::Home => rect.render_widget(render_home(), chunks[1]),
+                MenuItem::Home => {
+                    let home_chunks = Layout::default()
+                        .direction(Direction::Vertical)
+                        .constraints([Constraint::Length(10), Constraint::Min(3)].as_ref())
+                        .split(chunks[1]);
+                    rect.render_widget(render_home(), home_chunks[0]);
+                    let shown = reminders.as_ref().map_or(&[][..], |(_, reminders)| reminders.as_slice());
+                    rect.render_widget(health::render_reminders(shown, &reminders_status), home_chunks[1]);
+                }
                 MenuItem::Pets => {
                     let pets_menu_chunks = Layout::default()
                         .direction(Direction::Vertical)
                         .constraints(
-                            [Constraint::Length(3), Constraint::Min(10)].as_ref(),
+                            [Constraint::Length(3), Constraint::Length(3), Constraint::Min(10)].as_ref(),
                         )
                         .split(chunks[1]);
                     //@@@
@@ -208,80 +520,762 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                         ])
                     }).collect();
                     let pets_tabs = Tabs::new(pets_menu)
-                        .block(Block::default().title("Pets - Actions"))
+                        .block(Block::default().title(match pet_status.as_str() {
+                            "" => String::from("Pets - Actions"),
+                            status => format!("Pets - Actions - {}", status),
+                        }))
                         .divider(Span::raw(":"));
                     rect.render_widget(pets_tabs, pets_menu_chunks[0]);
 
+                    let filter_area = pets_menu_chunks[1];
+                    rect.render_widget(
+                        query::render_filter_bar(
+                            pet_filter_input.as_ref(),
+                            pet_query.as_ref().map(|(text, _)| text.as_str()),
+                            pet_filter_problem.as_ref().map(|(column, message)| (*column, message.as_str())),
+                            filter_area.width,
+                        ),
+                        filter_area,
+                    );
+                    if let Some(input) = &pet_filter_input {
+                        let inner_width = filter_area.width.saturating_sub(4) as usize;
+                        let cursor = input.visual_cursor() - input.visual_scroll(inner_width);
+                        rect.set_cursor(filter_area.x + 3 + cursor as u16, filter_area.y + 1);
+                    }
+
                     let pets_chunks = Layout::default()
                         .direction(Direction::Horizontal)
                         .constraints(
                             [Constraint::Percentage(20), Constraint::Percentage(80)].as_ref(),
                         )
-                        .split(pets_menu_chunks[1]);
-                    let (left, right) = render_pets(&pet_list_state);
-                    rect.render_stateful_widget(left, pets_chunks[0], &mut pet_list_state);
-                    rect.render_widget(right, pets_chunks[1]);
+                        .split(pets_menu_chunks[2]);
+                    let detail_chunks = Layout::default()
+                        .direction(Direction::Vertical)
+                        .constraints([Constraint::Length(4), Constraint::Min(3)].as_ref())
+                        .split(pets_chunks[1]);
+                    pet_rows = pets_chunks[0].height.saturating_sub(2) as usize;
+                    let (left, mut window_state, right, records) = render_pets(&mut pet_view, pet_rows, pet_sort, pet_query.is_some(), today);
+                    rect.render_stateful_widget(left, pets_chunks[0], &mut window_state);
+                    rect.render_widget(right, detail_chunks[0]);
+                    rect.render_widget(records, detail_chunks[1]);
+                    if let Some(input) = &pet_jump {
+                        let area = moderation::centered_rect(50, 3, pets_menu_chunks[2]);
+                        rect.render_widget(Clear, area);
+                        rect.render_widget(pet_view::render_jump(input, pet_view.len(), area.width), area);
+                        let inner_width = area.width.saturating_sub(4) as usize;
+                        let cursor = input.visual_cursor() - input.visual_scroll(inner_width);
+                        rect.set_cursor(area.x + 3 + cursor as u16, area.y + 1);
+                    }
                 },
+                MenuItem::Dashboard => dashboard::render(rect, chunks[1], &dashboard),
+                MenuItem::History => {
+                    let history_chunks = Layout::default()
+                        .direction(Direction::Vertical)
+                        .constraints([Constraint::Min(3), Constraint::Length(3)].as_ref())
+                        .split(chunks[1]);
+                    let panes = Layout::default()
+                        .direction(Direction::Horizontal)
+                        .constraints([Constraint::Percentage(55), Constraint::Percentage(45)].as_ref())
+                        .split(history_chunks[0]);
+                    let selected = history_state.selected().and_then(|selected| history.shown.get(selected));
+                    rect.render_stateful_widget(
+                        audit::render_history(&history.shown, history_pet.as_ref().map(|(_, name)| name.as_str()), history.skipped),
+                        panes[0],
+                        &mut history_state,
+                    );
+                    rect.render_widget(audit::render_entry(selected), panes[1]);
+                    rect.render_widget(audit::render_help(&history_status), history_chunks[1]);
+                }
+                MenuItem::Trash => {
+                    let trash_chunks = Layout::default()
+                        .direction(Direction::Vertical)
+                        .constraints([Constraint::Min(3), Constraint::Length(3)].as_ref())
+                        .split(chunks[1]);
+                    rect.render_stateful_widget(
+                        trash::render_trash(&trash::sorted(&store.db), now, chat_config.trash.retention_days),
+                        trash_chunks[0],
+                        &mut trash_state,
+                    );
+                    rect.render_widget(trash::render_help(&trash_status), trash_chunks[1]);
+                }
+                MenuItem::Duplicates => {
+                    let duplicate_chunks = Layout::default()
+                        .direction(Direction::Vertical)
+                        .constraints([Constraint::Min(3), Constraint::Length(3)].as_ref())
+                        .split(chunks[1]);
+                    match &compare {
+                        Some(compare) => rect.render_widget(duplicates::render_compare(compare), duplicate_chunks[0]),
+                        None => rect.render_stateful_widget(
+                            duplicates::render_candidates(&duplicate_candidates),
+                            duplicate_chunks[0],
+                            &mut duplicates_state,
+                        ),
+                    }
+                    rect.render_widget(
+                        duplicates::render_help(&duplicates_status, compare.is_some()),
+                        duplicate_chunks[1],
+                    );
+                }
+                MenuItem::Categories => {
+                    let category_chunks = Layout::default()
+                        .direction(Direction::Vertical)
+                        .constraints([Constraint::Min(3), Constraint::Length(3)].as_ref())
+                        .split(chunks[1]);
+                    rect.render_stateful_widget(
+                        categories::render_categories(&store.db, category_merge.as_deref()),
+                        category_chunks[0],
+                        &mut categories_state,
+                    );
+                    rect.render_widget(
+                        categories::render_help(&category_status, category_merge.is_some()),
+                        category_chunks[1],
+                    );
+                    if let Some((renaming, input)) = &category_input {
+                        let area = moderation::centered_rect(50, 3, category_chunks[0]);
+                        let title = match renaming {
+                            Some(key) => format!("Rename {}", key),
+                            None => String::from("New category"),
+                        };
+                        rect.render_widget(Clear, area);
+                        rect.render_widget(categories::render_name_input(input, &title, area.width), area);
+                        let inner_width = area.width.saturating_sub(4) as usize;
+                        let cursor = input.visual_cursor() - input.visual_scroll(inner_width);
+                        rect.set_cursor(area.x + 3 + cursor as u16, area.y + 1);
+                    }
+                }
                 MenuItem::Channels => {
                     let files_chunks = Layout::default()
                         .direction(Direction::Horizontal)
-                        .constraints(
-                            [Constraint::Percentage(20), Constraint::Percentage(80)].as_ref()
-                        )
+                        .constraints(if show_viewers {
+                            vec![Constraint::Percentage(20), Constraint::Min(20), Constraint::Length(24)]
+                        } else {
+                            vec![Constraint::Percentage(20), Constraint::Percentage(80)]
+                        })
                         .split(chunks[1]);
-                    rect.render_stateful_widget(render_files_list(&chans_list_state), files_chunks[0], &mut chans_list_state);
-                    rect.render_widget(render_chans_contents(), files_chunks[1]);
+                    rect.render_stateful_widget(render_chans_list(&channels), files_chunks[0], &mut chans_list_state);
+                    let selected_chan = &channels[chans_list_state.selected().unwrap_or(0)];
+                    let poll_height = if selected_chan.poll.is_some() { 10 } else { 0 };
+                    let chat_chunks = Layout::default()
+                        .direction(Direction::Vertical)
+                        .constraints([Constraint::Length(poll_height), Constraint::Min(3), Constraint::Length(3)].as_ref())
+                        .split(files_chunks[1]);
+                    if let Some(poll) = &selected_chan.poll {
+                        let labels = poll.labels();
+                        let data: Vec<(&str, u64)> = labels.iter().map(String::as_str).zip(poll.tally()).collect();
+                        rect.render_widget(poll::render_poll(poll, &data, chat_chunks[0].width), chat_chunks[0]);
+                    }
+                    chat_rows = chat_chunks[1].height.saturating_sub(2) as usize;
+                    rect.render_widget(
+                        render_chans_contents(
+                            selected_chan,
+                            chat_rows,
+                            chat_config.hide_deleted,
+                        ),
+                        chat_chunks[1],
+                    );
+                    if let (Some(state), Some(msg)) = (mod_popup.as_mut(), selected_chan.selected_message()) {
+                        let area = moderation::centered_rect(30, MOD_ACTIONS.len() as u16 + 2, chat_chunks[1]);
+                        rect.render_widget(Clear, area);
+                        rect.render_stateful_widget(moderation::render_mod_popup(msg), area, state);
+                    }
+                    if show_viewers {
+                        let (list, row) = viewers::render_viewers(selected_chan, &viewer_query, viewer_typing, viewer_selected);
+                        viewer_list_state.select(row);
+                        rect.render_stateful_widget(list, files_chunks[2], &mut viewer_list_state);
+                    }
+                    if let Some(login) = &user_popup {
+                        let area = moderation::centered_rect(70, 12, chat_chunks[1]);
+                        rect.render_widget(Clear, area);
+                        rect.render_widget(viewers::render_user_popup(selected_chan, login, 10), area);
+                    }
+                    if let Some(state) = links_popup.as_mut() {
+                        let area = moderation::centered_rect(
+                            chat_chunks[1].width.saturating_sub(4),
+                            chat_chunks[1].height.saturating_sub(2),
+                            chat_chunks[1],
+                        );
+                        rect.render_widget(Clear, area);
+                        rect.render_stateful_widget(links::render_links(selected_chan), area, state);
+                    }
+                    if let Some(search) = search.as_mut() {
+                        let area = moderation::centered_rect(
+                            chat_chunks[1].width.saturating_sub(4),
+                            chat_chunks[1].height.saturating_sub(2),
+                            chat_chunks[1],
+                        );
+                        let search_chunks = Layout::default()
+                            .direction(Direction::Vertical)
+                            .constraints([Constraint::Length(3), Constraint::Min(3)].as_ref())
+                            .split(area);
+                        rect.render_widget(Clear, area);
+                        rect.render_widget(search::render_search_input(search, area.width), search_chunks[0]);
+                        if search.context.is_some() {
+                            rect.render_widget(search::render_context(search), search_chunks[1]);
+                        } else {
+                            rect.render_stateful_widget(search::render_hits(search), search_chunks[1], &mut search.list);
+                            let inner_width = area.width.saturating_sub(4) as usize;
+                            let cursor = search.input.visual_cursor() - search.input.visual_scroll(inner_width);
+                            rect.set_cursor(area.x + 3 + cursor as u16, area.y + 1);
+                        }
+                    }
+                    if let Some((state, messages, _)) = export_popup.as_mut() {
+                        let area = moderation::centered_rect(30, EXPORT_FORMATS.len() as u16 + 2, chat_chunks[1]);
+                        rect.render_widget(Clear, area);
+                        rect.render_stateful_widget(export::render_export_popup(messages.len()), area, state);
+                    }
+                    let prompt_area = chat_chunks[2];
+                    rect.render_widget(
+                        prompt::render_prompt(
+                            prompt.as_ref(),
+                            &prompt_status,
+                            prompt_area.width,
+                            Some(selected_chan.name.as_str()).filter(|_| selected_chan.kind == ChannelKind::Whisper),
+                        ),
+                        prompt_area,
+                    );
+                    if let Some(input) = &prompt {
+                        let inner_width = prompt_area.width.saturating_sub(4) as usize;
+                        let cursor = input.visual_cursor() - input.visual_scroll(inner_width);
+                        rect.set_cursor(prompt_area.x + 3 + cursor as u16, prompt_area.y + 1);
+                    }
                 }
             }
-            rect.render_widget(copyright, chunks[2]);
+            rect.render_widget(activity::render_sparkline(&per_minute), stats_chunks[0]);
+            rect.render_widget(activity::render_stats(stats_chan, now), stats_chunks[1]);
         })?;
 
-        match rx.recv()? {
+        let event = rx.recv()?;
+        if let Event::Input(_) = event {
+            if let Err(e) = notifier.input() {
+                prompt_status = format!("couldn't reset the title: {}", e);
+            }
+        }
+        let selected_chan = chans_list_state.selected().unwrap_or(0);
+        match event {
+            Event::Input(key) if mod_popup.is_some() => match key.code {
+                KeyCode::Esc => mod_popup = None,
+                KeyCode::Up | KeyCode::Down => {
+                    if let Some(state) = mod_popup.as_mut() {
+                        let selected = state.selected().unwrap_or(0);
+                        state.select(Some(match key.code {
+                            KeyCode::Up => (selected + MOD_ACTIONS.len() - 1) % MOD_ACTIONS.len(),
+                            _ => (selected + 1) % MOD_ACTIONS.len(),
+                        }));
+                    }
+                }
+                KeyCode::Enter => {
+                    let action = MOD_ACTIONS[mod_popup.take().and_then(|state| state.selected()).unwrap_or(0)];
+                    prompt_status = format!("can't {}: {}", action.label().to_lowercase(), moderation::IRC_COMMANDS_RETIRED);
+                }
+                _ => {}
+            },
+            Event::Input(key) if user_popup.is_some() => {
+                if let KeyCode::Esc | KeyCode::Enter = key.code {
+                    user_popup = None;
+                }
+            }
+            Event::Input(key) if chat_focus == ChatFocus::Viewers
+                && matches!(active_menu_item, MenuItem::Channels)
+                // other keys fall through to the usual bindings unless the search line is open
+                && match viewer_typing {
+                    true => matches!(key.code, KeyCode::Char(_) | KeyCode::Backspace | KeyCode::Esc | KeyCode::Up | KeyCode::Down | KeyCode::Enter),
+                    false => matches!(key.code, KeyCode::Char('/') | KeyCode::Up | KeyCode::Down | KeyCode::Enter),
+                } =>
+            {
+                let hits = channels[selected_chan].viewers.search(&viewer_query);
+                match key.code {
+                    KeyCode::Char('/') if !viewer_typing => viewer_typing = true,
+                    KeyCode::Char(c) => {
+                        viewer_query.push(c);
+                        viewer_selected = Some(0);
+                    }
+                    KeyCode::Backspace => {
+                        viewer_query.pop();
+                        viewer_selected = Some(0);
+                    }
+                    KeyCode::Esc => {
+                        viewer_query.clear();
+                        viewer_typing = false;
+                    }
+                    KeyCode::Up => viewer_selected = viewer_selected.map(|i| i.saturating_sub(1)),
+                    KeyCode::Down => {
+                        viewer_selected = viewer_selected.map(|i| (i + 1).min(hits.len().saturating_sub(1)));
+                    }
+                    _ => {
+                        viewer_typing = false;
+                        user_popup = viewer_selected
+                            .and_then(|i| hits.get(i))
+                            .map(|(_, login)| login.to_string());
+                    }
+                }
+            }
+            Event::Input(key) if export_popup.is_some() => match key.code {
+                KeyCode::Esc => export_popup = None,
+                KeyCode::Up | KeyCode::Down => {
+                    if let Some((state, _, _)) = export_popup.as_mut() {
+                        let selected = state.selected().unwrap_or(0);
+                        state.select(Some(match key.code {
+                            KeyCode::Up => (selected + EXPORT_FORMATS.len() - 1) % EXPORT_FORMATS.len(),
+                            _ => (selected + 1) % EXPORT_FORMATS.len(),
+                        }));
+                    }
+                }
+                KeyCode::Enter => {
+                    if let Some((state, messages, title)) = export_popup.take() {
+                        let format = EXPORT_FORMATS[state.selected().unwrap_or(0)];
+                        prompt_status = match export::write_transcript(&messages, &title, format) {
+                            Ok(path) => format!("exported {} messages to {}", messages.len(), path.display()),
+                            Err(e) => format!("couldn't export: {}", e),
+                        };
+                    }
+                }
+                _ => {}
+            },
+            Event::Input(key) if links_popup.is_some() => {
+                let channel = &channels[selected_chan];
+                let selected = links_popup.as_ref().and_then(|state| state.selected());
+                let link = selected.and_then(|i| channel.links.newest_first().nth(i));
+                match key.code {
+                    KeyCode::Esc => links_popup = None,
+                    KeyCode::Up | KeyCode::Down => {
+                        let len = channel.links.links.len();
+                        if let (Some(state), Some(selected)) = (links_popup.as_mut(), selected) {
+                            state.select(Some(match key.code {
+                                KeyCode::Up => selected.saturating_sub(1),
+                                _ => (selected + 1).min(len.saturating_sub(1)),
+                            }));
+                        }
+                    }
+                    KeyCode::Char('c') | KeyCode::Enter => {
+                        if let Some(link) = link {
+                            prompt_status = match links::copy_to_clipboard(&link.url) {
+                                Ok(()) => format!("copied {}", link.url),
+                                Err(e) => format!("couldn't copy {}: {}", link.url, e),
+                            };
+                        }
+                    }
+                    KeyCode::Char('o') => {
+                        prompt_status = match (link, &chat_config.open_command) {
+                            (Some(link), Some(command)) => match links::open_url(command, &link.url) {
+                                Ok(()) => format!("opened {}", link.url),
+                                Err(e) => format!("couldn't run {}: {}", command, e),
+                            },
+                            (Some(_), None) => String::from("set open_command in data/chat.json to open links"),
+                            (None, _) => String::from("no link selected"),
+                        };
+                    }
+                    _ => {}
+                }
+            }
+            Event::Input(key) if search.is_some() => {
+                if let Some(state) = search.as_mut() {
+                    match key.code {
+                        KeyCode::Esc if state.context.is_some() => state.context = None,
+                        KeyCode::Esc => search = None,
+                        KeyCode::Up => state.select_next(false),
+                        KeyCode::Down => state.select_next(true),
+                        KeyCode::Enter if state.context.is_some() => {
+                            let found = state.selected_hit().filter(|hit| matches!(hit.location, HitLocation::Scrollback)).map(|hit| hit.find_in(&channels));
+                            match found {
+                                Some(None) => {
+                                    state.status = String::from("the message has left the scrollback since the search");
+                                    state.context = None;
+                                }
+                                Some(Some((channel, index))) => {
+                                    chans_list_state.select(Some(channel));
+                                    channels[channel].selected = Some(index);
+                                    chat_focus = ChatFocus::Messages;
+                                    search = None;
+                                }
+                                None => {}
+                            }
+                        }
+                        KeyCode::Char('e') if key.modifiers.contains(KeyModifiers::CONTROL) && state.is_current() => {
+                            let messages = state.hits.iter().map(|hit| hit.msg.clone()).collect();
+                            let mut list = ListState::default();
+                            list.select(Some(0));
+                            export_popup = Some((list, messages, format!("search {}", state.input.value())));
+                        }
+                        KeyCode::Enter if state.is_current() => state.open_context(&channels),
+                        KeyCode::Enter => state.run(&channels, &mut log_index),
+                        _ if state.context.is_none() => {
+                            if let Some(req) = prompt::to_input_request(&key) {
+                                state.input.handle(req);
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            Event::Input(key) if prompt.is_some() => match key.code {
+                KeyCode::Esc => prompt = None,
+                KeyCode::Enter => {
+                    let line = prompt.take().map(|input| input.value().to_string()).unwrap_or_default();
+                    let selected = &mut channels[selected_chan];
+                    let result = match selected.kind {
+                        ChannelKind::Whisper if !line.starts_with('/') => send_whisper(&line),
+                        ChannelKind::Joined => run_chat_command(&line, &mut chat_config, &mut filter, &mut triggers, Some(selected)),
+                        _ => run_chat_command(&line, &mut chat_config, &mut filter, &mut triggers, None),
+                    };
+                    prompt_status = match result {
+                        Ok(status) => status,
+                        Err(e) => e.to_string(),
+                    };
+                }
+                _ => {
+                    if let (Some(input), Some(req)) = (prompt.as_mut(), prompt::to_input_request(&key)) {
+                        input.handle(req);
+                    }
+                }
+            },
+            Event::Input(key) if pet_filter_input.is_some() => match key.code {
+                KeyCode::Esc => {
+                    pet_filter_input = None;
+                    pet_filter_problem = None;
+                }
+                KeyCode::Enter => {
+                    let text = pet_filter_input.as_ref().map(|input| input.value().trim().to_string()).unwrap_or_default();
+                    let parsed = match text.is_empty() {
+                        true => Ok(None),
+                        false => Query::parse(&text).map(|query| Some((text, query))),
+                    };
+                    match parsed {
+                        Ok(query) => {
+                            pet_query = query;
+                            pet_filter_input = None;
+                            pet_filter_problem = None;
+                            pet_view.select(0);
+                        }
+                        Err(Error::QueryError { column, message }) => pet_filter_problem = Some((column, message)),
+                        Err(e) => pet_filter_problem = Some((1, e.to_string())),
+                    }
+                }
+                _ => {
+                    if let (Some(input), Some(req)) = (pet_filter_input.as_mut(), prompt::to_input_request(&key)) {
+                        input.handle(req);
+                        pet_filter_problem = None;
+                    }
+                }
+            },
+            Event::Input(key) if pet_jump.is_some() => match key.code {
+                KeyCode::Esc => pet_jump = None,
+                KeyCode::Enter => {
+                    // rows are numbered from 1 on screen; anything else keeps the line open
+                    let row = pet_jump.as_ref().and_then(|input| input.value().trim().parse::<usize>().ok());
+                    if let Some(row) = row.filter(|row| *row > 0) {
+                        pet_view.select(row - 1);
+                        pet_jump = None;
+                    }
+                }
+                _ => {
+                    if let (Some(input), Some(req)) = (pet_jump.as_mut(), prompt::to_input_request(&key)) {
+                        input.handle(req);
+                    }
+                }
+            },
+            Event::Input(key) if category_input.is_some() => match key.code {
+                KeyCode::Esc => category_input = None,
+                KeyCode::Enter => {
+                    if let Some((renaming, input)) = category_input.take() {
+                        let name = input.value().to_string();
+                        category_status = edit_categories(|db| match &renaming {
+                            Some(key) => categories::rename(db, key, &name),
+                            None => categories::add(db, &name),
+                        });
+                    }
+                }
+                _ => {
+                    if let (Some((_, input)), Some(req)) = (category_input.as_mut(), prompt::to_input_request(&key)) {
+                        input.handle(req);
+                    }
+                }
+            },
+            Event::Input(event) if matches!(active_menu_item, MenuItem::Categories) && matches!(event.code, KeyCode::Char('a' | 'r' | 'm' | 'd' | 'c' | 'i') | KeyCode::Esc) => {
+                let selected = categories_state.selected().and_then(|selected| store.db.categories.get(selected));
+                let selected = selected.map(|category| category.key.clone());
+                category_status = String::new();
+                match (event.code, selected) {
+                    (KeyCode::Char('a'), _) => category_input = Some((None, Input::default())),
+                    (KeyCode::Char('r'), Some(key)) => {
+                        let name = categories::find(&store.db.categories, &key)
+                            .map(|category| category.name.clone())
+                            .unwrap_or_default();
+                        category_input = Some((Some(key), Input::new(name)));
+                    }
+                    (KeyCode::Char('m'), Some(key)) => match category_merge.take() {
+                        Some(from) => category_status = edit_categories(|db| categories::merge(db, &from, &key)),
+                        None => category_merge = Some(key),
+                    },
+                    (KeyCode::Char('d'), Some(key)) => category_status = edit_categories(|db| categories::delete(db, &key)),
+                    (KeyCode::Char('c'), Some(key)) => category_status = edit_categories(|db| {
+                        categories::cycle_style(db, &key, false);
+                        Ok(String::new())
+                    }),
+                    (KeyCode::Char('i'), Some(key)) => category_status = edit_categories(|db| {
+                        categories::cycle_style(db, &key, true);
+                        Ok(String::new())
+                    }),
+                    (KeyCode::Esc, _) => category_merge = None,
+                    _ => {}
+                }
+            }
+            Event::Input(event) if matches!(active_menu_item, MenuItem::History) && matches!(event.code, KeyCode::Char('f' | 'r')) => {
+                let selected = history_state.selected().and_then(|selected| history.shown.get(selected));
+                history_status = String::new();
+                match (event.code, selected) {
+                    (KeyCode::Char('f'), _) if history_pet.is_some() => history_pet = None,
+                    (KeyCode::Char('f'), Some(entry)) => history_pet = Some((entry.pet_id, entry.pet_name.clone())),
+                    (KeyCode::Char('r'), Some(entry)) => {
+                        history_status = restore_pet(entry, Source::Tui).unwrap_or_else(|e| e.to_string());
+                    }
+                    _ => {}
+                }
+                history_state.select(Some(0));
+            }
+            Event::Input(event) if matches!(active_menu_item, MenuItem::Trash) && matches!(event.code, KeyCode::Char('r' | 'p')) => {
+                let trashed = trash::sorted(&store.db);
+                if let Some(id) = trash_state.selected().and_then(|selected| trashed.get(selected)).map(|pet| pet.id) {
+                    trash_status = match event.code {
+                        KeyCode::Char('r') => edit_trash(|db| {
+                            let pet = trash::restore(db, id, Utc::now())?;
+                            Ok(AuditEntry::new(Action::Restore, Source::Tui, None, Some(&pet)))
+                        }),
+                        _ => edit_trash(|db| {
+                            let pet = trash::purge(db, id)?;
+                            Ok(AuditEntry::new(Action::Purge, Source::Tui, Some(&pet), None))
+                        }),
+                    };
+                }
+            }
+            Event::Input(key) if compare.is_some() && matches!(active_menu_item, MenuItem::Duplicates) => {
+                duplicates_status = String::new();
+                match key.code {
+                    KeyCode::Esc => compare = None,
+                    KeyCode::Enter => {
+                        if let Some(merging) = compare.take() {
+                            duplicates_status = merge_duplicates(&merging).unwrap_or_else(|e| e.to_string());
+                            duplicate_candidates = duplicates::scan(&read_db().expect("can fetch pet list"));
+                            duplicates_state.select(Some(0));
+                        }
+                    }
+                    code => {
+                        if let Some(compare) = compare.as_mut() {
+                            match code {
+                                KeyCode::Up => compare.move_row(false),
+                                KeyCode::Down => compare.move_row(true),
+                                KeyCode::Left => compare.pick(false),
+                                KeyCode::Right => compare.pick(true),
+                                _ => {}
+                            }
+                        }
+                    }
+                }
+            }
+            Event::Input(event) if matches!(active_menu_item, MenuItem::Duplicates) && matches!(event.code, KeyCode::Enter | KeyCode::Char('s')) => {
+                duplicates_status = String::new();
+                match event.code {
+                    KeyCode::Enter => {
+                        let selected = duplicates_state.selected().and_then(|selected| duplicate_candidates.get(selected));
+                        if let Some(candidate) = selected {
+                            match Compare::new(candidate.clone()) {
+                                Ok(opened) => compare = Some(opened),
+                                Err(e) => duplicates_status = e.to_string(),
+                            }
+                        }
+                    }
+                    _ => {
+                        duplicate_candidates = duplicates::scan(&read_db().expect("can fetch pet list"));
+                        duplicates_state.select(Some(0));
+                    }
+                }
+            }
             Event::Input(event) => match event.code {
                 KeyCode::F(4) => {
                     /*quit*/
                     disable_raw_mode()?;
                     terminal.show_cursor()?;
+                    log_index.save()?;
                     break;
                 }
                 KeyCode::F(1) => active_menu_item = MenuItem::Home,
                 KeyCode::F(2) => active_menu_item = MenuItem::Pets,
                 KeyCode::F(3) => active_menu_item = MenuItem::Channels,
+                KeyCode::F(5) => active_menu_item = MenuItem::Categories,
+                KeyCode::F(6) => active_menu_item = MenuItem::Dashboard,
+                KeyCode::F(7) => active_menu_item = MenuItem::History,
+                KeyCode::F(8) => active_menu_item = MenuItem::Trash,
+                KeyCode::F(9) => {
+                    active_menu_item = MenuItem::Duplicates;
+                    duplicate_candidates = duplicates::scan(&read_db().expect("can fetch pet list"));
+                    duplicates_state.select(Some(0));
+                    duplicates_status = String::new();
+                    compare = None;
+                }
+                KeyCode::Char('/') if matches!(active_menu_item, MenuItem::Pets) => {
+                    let text = pet_query.as_ref().map(|(text, _)| text.clone()).unwrap_or_default();
+                    pet_filter_input = Some(Input::new(text));
+                }
+                KeyCode::Char('/') if matches!(active_menu_item, MenuItem::Channels) => {
+                    prompt = Some(Input::default());
+                }
+                KeyCode::Char('l') if matches!(active_menu_item, MenuItem::Channels) => {
+                    let mut state = ListState::default();
+                    if !channels[selected_chan].links.links.is_empty() {
+                        state.select(Some(0));
+                    }
+                    links_popup = Some(state);
+                }
+                KeyCode::Char('m') if matches!(active_menu_item, MenuItem::Channels) && chat_focus == ChatFocus::Messages => {
+                    let channel = &mut channels[selected_chan];
+                    channel.mark = match channel.mark {
+                        Some(_) => None,
+                        None => channel.selected,
+                    };
+                }
+                KeyCode::Char('e') if matches!(active_menu_item, MenuItem::Channels) => {
+                    let channel = &channels[selected_chan];
+                    let (messages, title) = match channel.marked_range() {
+                        Some(range) => (channel.messages.range(range).cloned().collect(), format!("{} excerpt", channel.title())),
+                        None => (channel.messages.iter().cloned().collect(), channel.title()),
+                    };
+                    let mut state = ListState::default();
+                    state.select(Some(0));
+                    export_popup = Some((state, messages, title));
+                }
+                KeyCode::Char('E') if matches!(active_menu_item, MenuItem::Channels) => {
+                    let channel = &mut channels[selected_chan];
+                    channel.events_only = !channel.events_only;
+                    if channel.selected.is_some_and(|selected| !channel.shows(&channel.messages[selected])) {
+                        channel.selected = channel.last_shown();
+                    }
+                    channel.mark = None;
+                }
+                KeyCode::Char('f') if matches!(active_menu_item, MenuItem::Channels) => {
+                    search = Some(Search::new());
+                }
+                KeyCode::Char('v') if matches!(active_menu_item, MenuItem::Channels) => {
+                    show_viewers = !show_viewers;
+                    if !show_viewers && chat_focus == ChatFocus::Viewers {
+                        chat_focus = ChatFocus::Channels;
+                        viewer_typing = false;
+                    }
+                }
+                KeyCode::Tab if matches!(active_menu_item, MenuItem::Channels) => {
+                    chat_focus = match chat_focus {
+                        ChatFocus::Channels => ChatFocus::Messages,
+                        ChatFocus::Messages if show_viewers => ChatFocus::Viewers,
+                        _ => ChatFocus::Channels,
+                    };
+                    let channel = &mut channels[selected_chan];
+                    channel.selected = match chat_focus {
+                        ChatFocus::Messages => channel.last_shown(),
+                        _ => None,
+                    };
+                    viewer_selected = match chat_focus {
+                        ChatFocus::Viewers => Some(0),
+                        _ => None,
+                    };
+                    viewer_typing = false;
+                }
+                KeyCode::Enter if matches!(active_menu_item, MenuItem::Channels) && chat_focus == ChatFocus::Messages => {
+                    let channel = &channels[selected_chan];
+                    if !channel.is_mod {
+                        prompt_status = format!("not a moderator in {}", channel.title());
+                    } else if channel.selected_message().is_some() {
+                        let mut state = ListState::default();
+                        state.select(Some(0));
+                        mod_popup = Some(state);
+                    }
+                }
                 KeyCode::Char('a') => {
                     match active_menu_item {
                         MenuItem::Channels => {
                             //add a channel to the sidebar and stuff
                         }
                         MenuItem::Pets => {
-                            add_random_pet_to_db().expect("can add new random pet");
+                            pet_status = match add_random_pet_to_db() {
+                                Ok(_) => String::new(),
+                                Err(e) => e.to_string(),
+                            };
                         }
                         _ => {}
                     }
                     
                 }
                 KeyCode::Char('d') => {
-                    match active_menu_item {
-                        MenuItem::Pets => {
-                            remove_pet_at_index(&mut pet_list_state).expect("can remove pet");
-                        }
-                        _ => {}
+                    if let MenuItem::Pets = active_menu_item {
+                        pet_status = match remove_selected_pet(&mut pet_view) {
+                            Ok(()) => String::new(),
+                            Err(e) => e.to_string(),
+                        };
                     }
                 }
+                KeyCode::Char('h') if matches!(active_menu_item, MenuItem::Pets) => {
+                    if let Some(pet) = pet_view.selected_pet() {
+                        history_pet = Some((pet.id, pet.name.clone()));
+                        history_status = String::new();
+                        history_state.select(Some(0));
+                        active_menu_item = MenuItem::History;
+                    }
+                }
+                KeyCode::Char('s') if matches!(active_menu_item, MenuItem::Pets) => {
+                    pet_sort = pet_sort.next();
+                    pet_view.select(0);
+                }
+                KeyCode::Char('g') if matches!(active_menu_item, MenuItem::Pets) => {
+                    pet_jump = Some(Input::default());
+                }
+                KeyCode::PageDown if matches!(active_menu_item, MenuItem::Pets) => pet_view.page(pet_rows, true),
+                KeyCode::PageUp if matches!(active_menu_item, MenuItem::Pets) => pet_view.page(pet_rows, false),
+                KeyCode::Home if matches!(active_menu_item, MenuItem::Pets) => pet_view.select(0),
+                KeyCode::End if matches!(active_menu_item, MenuItem::Pets) => pet_view.select(pet_view.len().saturating_sub(1)),
+                KeyCode::PageDown if matches!(active_menu_item, MenuItem::Channels) && chat_focus == ChatFocus::Messages => {
+                    channels[selected_chan].page_selection(chat_rows, true);
+                }
+                KeyCode::PageUp if matches!(active_menu_item, MenuItem::Channels) && chat_focus == ChatFocus::Messages => {
+                    channels[selected_chan].page_selection(chat_rows, false);
+                }
+                KeyCode::Home if matches!(active_menu_item, MenuItem::Channels) && chat_focus == ChatFocus::Messages => {
+                    let channel = &mut channels[selected_chan];
+                    channel.selected = channel.first_shown();
+                }
+                KeyCode::End if matches!(active_menu_item, MenuItem::Channels) && chat_focus == ChatFocus::Messages => {
+                    let channel = &mut channels[selected_chan];
+                    channel.selected = channel.last_shown();
+                }
                 KeyCode::Down => {
                     match active_menu_item {
-                        MenuItem::Pets => {
-                            if let Some(selected) = pet_list_state.selected() {
-                                let amount_pets = read_db().expect("can fetch pet list").len();
-                                if selected >= amount_pets - 1 {
-                                    pet_list_state.select(Some(0));
-                                } else {
-                                    pet_list_state.select(Some(selected + 1));
-                                }
+                        MenuItem::Pets => pet_view.step(true),
+                        MenuItem::Categories => {
+                            let len = store.db.categories.len();
+                            if let Some(selected) = categories_state.selected() {
+                                categories_state.select(Some(if selected + 1 >= len { 0 } else { selected + 1 }));
                             }
                         }
+                        MenuItem::History => {
+                            let len = history.shown.len();
+                            if let Some(selected) = history_state.selected() {
+                                history_state.select(Some(if selected + 1 >= len { 0 } else { selected + 1 }));
+                            }
+                        }
+                        MenuItem::Trash => {
+                            let len = store.db.trash.len();
+                            if let Some(selected) = trash_state.selected() {
+                                trash_state.select(Some(if selected + 1 >= len { 0 } else { selected + 1 }));
+                            }
+                        }
+                        MenuItem::Duplicates => {
+                            let len = duplicate_candidates.len();
+                            if let Some(selected) = duplicates_state.selected() {
+                                duplicates_state.select(Some(if selected + 1 >= len { 0 } else { selected + 1 }));
+                            }
+                        }
+                        MenuItem::Channels if chat_focus == ChatFocus::Messages => {
+                            channels[selected_chan].move_selection(true);
+                        }
                         MenuItem::Channels => {
                             if let Some(selected) = chans_list_state.selected() {
-                                let amount_files = get_chans_list().len();
+                                let amount_files = channels.len();
                                 if selected >= amount_files - 1 {
                                     chans_list_state.select(Some(0));
                                 } else {
@@ -293,19 +1287,37 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                 }
                 KeyCode::Up => {
                     match active_menu_item {
-                        MenuItem::Pets => {
-                            if let Some(selected) = pet_list_state.selected() {
-                                let amount_pets = read_db().expect("can fetch pet list").len();
-                                if selected > 0 {
-                                    pet_list_state.select(Some(selected - 1));
-                                } else {
-                                    pet_list_state.select(Some(amount_pets - 1));
-                                }
+                        MenuItem::Pets => pet_view.step(false),
+                        MenuItem::Categories => {
+                            let len = store.db.categories.len();
+                            if let Some(selected) = categories_state.selected() {
+                                categories_state.select(Some(if selected == 0 { len.saturating_sub(1) } else { selected - 1 }));
+                            }
+                        }
+                        MenuItem::History => {
+                            let len = history.shown.len();
+                            if let Some(selected) = history_state.selected() {
+                                history_state.select(Some(if selected == 0 { len.saturating_sub(1) } else { selected - 1 }));
                             }
                         }
+                        MenuItem::Trash => {
+                            let len = store.db.trash.len();
+                            if let Some(selected) = trash_state.selected() {
+                                trash_state.select(Some(if selected == 0 { len.saturating_sub(1) } else { selected - 1 }));
+                            }
+                        }
+                        MenuItem::Duplicates => {
+                            let len = duplicate_candidates.len();
+                            if let Some(selected) = duplicates_state.selected() {
+                                duplicates_state.select(Some(if selected == 0 { len.saturating_sub(1) } else { selected - 1 }));
+                            }
+                        }
+                        MenuItem::Channels if chat_focus == ChatFocus::Messages => {
+                            channels[selected_chan].move_selection(false);
+                        }
                         MenuItem::Channels=> {
                             if let Some(selected) = chans_list_state.selected() {
-                                let amount_files = get_chans_list().len();
+                                let amount_files = channels.len();
                                 if selected > 0 {
                                     chans_list_state.select(Some(selected - 1));
                                 } else {
@@ -318,7 +1330,52 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                 }
                 _ => {}
             },
-            Event::Tick => {}
+            Event::Chat(line) => {
+                if let Some(recorder) = recorder.as_mut() {
+                    if let Err(e) = recorder.record(&line) {
+                        prompt_status = format!("couldn't record: {}", e);
+                    }
+                }
+                if let Some(msg) = chat::handle_line(&mut channels, &highlighter, &filter, &line) {
+                    let logged = match msg.kind {
+                        MessageKind::Whisper => whispers::append(&msg),
+                        _ if chat_config.log_messages => logs::append(&msg),
+                        _ => Ok(()),
+                    };
+                    if let Err(e) = logged {
+                        prompt_status = format!("couldn't write the chat log: {}", e);
+                    }
+                    if msg.highlight.is_some() {
+                        if let Err(e) = notifier.mention(&msg) {
+                            prompt_status = format!("couldn't notify: {}", e);
+                        }
+                    }
+                    if let Some(poll) = chat::joined_mut(&mut channels, &msg.channel).and_then(|c| c.poll.as_mut()) {
+                        poll.vote(&msg);
+                    }
+                    let actions = bot.handle(&msg).into_iter().chain(triggers.handle(&msg));
+                    for action in actions {
+                        run_bot_action(action, &msg.channel, &mut channels, &out_tx);
+                    }
+                }
+            }
+            Event::Tick => {
+                if let Err(e) = notifier.tick() {
+                    prompt_status = format!("couldn't flash the title: {}", e);
+                }
+                let now = Utc::now();
+                for channel in channels.iter_mut().filter(|c| c.poll.as_ref().is_some_and(|poll| poll.is_due(now))) {
+                    finish_poll(channel, &chat_config.polls, &out_tx);
+                }
+                if let Some(reloaded) = triggers.reload_if_changed() {
+                    let text = match reloaded {
+                        Ok(()) => String::from("reloaded triggers"),
+                        Err(e) => format!("kept the old triggers: {}", e),
+                    };
+                    let log = ChatMessage::notice("", text, BTreeMap::new());
+                    chat::push_to(&mut channels, ChannelKind::BotLog, log);
+                }
+            }
         }
     }
     // "Clean up when you're done" -the docs
@@ -359,84 +1416,168 @@ fn render_home<'a>() -> Paragraph<'a> {
     home
 }
 
-fn render_chans_contents<'a>() -> Paragraph<'a> {
-    let files = Paragraph::new(vec![
-        Spans::from(vec![Span::raw("")]),
-        Spans::from(vec![Span::raw("Channels:")]),
-        Spans::from(vec![Span::styled(
-            "~~todo~~",
-            Style::default().fg(Color::LightBlue),
-        )]),
-        Spans::from(vec![Span::raw("")]),
-    ])
-    .alignment(Alignment::Center)
-    .block(
+fn render_chans_contents<'a>(channel: &Channel, height: usize, hide_deleted: bool) -> List<'a> {
+    let item = |index: usize, msg: &ChatMessage| {
+        let text = match ChatEvent::from_message(msg) {
+            Some(event) if !(msg.deleted && hide_deleted) => events::render_card(msg, &event),
+            _ => {
+                let mut line = chat::render_message(msg, hide_deleted);
+                if channel.kind == ChannelKind::Mentions {
+                    line.0.insert(1, Span::styled(
+                        format!("#{} ", msg.channel),
+                        Style::default().fg(Color::Gray),
+                    ));
+                }
+                Text::from(line)
+            }
+        };
+        let mut style = match msg.highlight {
+                Some(color) => Style::default().bg(color),
+                None => Style::default(),
+            };
+        if channel.selected == Some(index) {
+            style = style.add_modifier(Modifier::REVERSED);
+        } else if channel.marked_range().is_some_and(|range| range.contains(&index)) {
+            style = style.bg(Color::DarkGray);
+        }
+        ListItem::new(text).style(style)
+    };
+    let shown = || channel.messages.iter().enumerate().filter(|(_, msg)| channel.shows(msg));
+
+    // follow the newest messages unless the selection is further up, event cards take more than a line;
+    // only the messages that fit get rendered
+    let mut start = None;
+    let mut lines = 0;
+    for (index, msg) in shown().rev() {
+        let item_height = item(index, msg).height();
+        if start.is_some() && lines + item_height > height {
+            break;
+        }
+        lines += item_height;
+        start = Some(index);
+    }
+    if let (Some(first), Some(selected)) = (start, channel.selected) {
+        start = Some(first.min(selected));
+    }
+    let mut items = vec![];
+    let mut lines = 0;
+    for (index, msg) in shown().skip_while(|(index, _)| Some(*index) < start) {
+        if start.is_none() || lines >= height {
+            break;
+        }
+        let item = item(index, msg);
+        lines += item.height();
+        items.push(item);
+    }
+    if items.is_empty() {
+        items.push(ListItem::new(Spans::from(vec![Span::styled(
+            if channel.events_only { "no events yet" } else { "waiting for messages..." },
+            Style::default().fg(Color::DarkGray),
+        )])));
+    }
+
+    let mut title = channel.title();
+    let modes = channel.room.describe();
+    if !modes.is_empty() {
+        title = format!("{} [{}]", title, modes.join(", "));
+    }
+    if channel.is_mod {
+        title.push_str(" (mod)");
+    }
+    if channel.filtered > 0 {
+        title = format!("{} ({} filtered)", title, channel.filtered);
+    }
+    if channel.events_only {
+        title.push_str(" (events only)");
+    }
+
+    List::new(items).block(
         Block::default()
             .borders(Borders::ALL)
             .style(Style::default().fg(Color::White))
-            .title("Channels")
+            .title(title)
             .border_type(BorderType::Plain),
-    );
-    files
+    )
 }
 
-fn render_files_list<'a>(chans_list_state: &ListState) -> List<'a> {
-    let files_list: Block = Block::default()
+fn render_chans_list<'a>(channels: &[Channel]) -> List<'a> {
+    let chans_list: Block = Block::default()
         .borders(Borders::ALL)
         .style(Style::default().fg(Color::DarkGray))
-        .title("Your files")
+        .title("Channels")
         .border_type(BorderType::Double);
 
-    let chan_names_list = vec!["strager", "het_tanis"];
-    let items: Vec<_> = chan_names_list
+    let items: Vec<_> = channels
         .iter()
         .map(|chan| {
-            let name = chan.to_string();
-            ListItem::new(Spans::from(
-                vec![Span::styled(name, Style::default())]
-            ))
+            let style = match chan.kind {
+                ChannelKind::Joined => Style::default(),
+                ChannelKind::Mentions | ChannelKind::BotLog => Style::default().add_modifier(Modifier::ITALIC),
+                ChannelKind::Whisper => Style::default().fg(Color::LightCyan),
+            };
+            let mut spans = vec![Span::styled(chan.title(), style)];
+            if chan.unread > 0 {
+                spans.push(Span::styled(
+                    format!(" ({})", chan.unread),
+                    Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD),
+                ));
+            }
+            ListItem::new(Spans::from(spans))
         })
         .collect();
 
-    let selected_file = chan_names_list
-        .get(chans_list_state
-            .selected()
-            .expect("There is always '.' and '..' in any directory.")
-        )
-        .expect("exists..")
-        .clone();
-
-    let list = List::new(items).block(files_list).highlight_symbol(">> ");
-    list
+    List::new(items).block(chans_list).highlight_symbol(">> ")
+}
 
+/// The pets as the Pets list shows them: sorted by `sort`, then narrowed and reordered by the filter.
+fn shown_pets(mut pets: Vec<Pet>, sort: PetSort, query: Option<&Query>, today: NaiveDate) -> Vec<Pet> {
+    sort.apply(&mut pets);
+    match query {
+        Some(query) => query.run(pets, today),
+        None => pets,
+    }
 }
 
-fn render_pets<'a>(pet_list_state: &ListState) -> (List<'a>, Table<'a>) {
+/// The Pets list, detail and health records. Only the `rows` pets that fit are turned into
+/// list items; the returned state selects among those.
+fn render_pets<'a>(view: &mut PetView, rows: usize, sort: PetSort, filtered: bool, today: NaiveDate) -> (List<'a>, ListState, Table<'a>, Table<'a>) {
+    let window = view.window(rows);
+    let mut window_state = ListState::default();
+    if !view.shown.is_empty() {
+        window_state.select(Some(view.selected - window.start));
+    }
+
+    let position = match view.len() {
+        0 => String::from("none"),
+        len => format!("{}/{}", view.selected + 1, len),
+    };
     let pets = Block::default()
         .borders(Borders::ALL)
         .style(Style::default().fg(Color::White))
-        .title("Pets")
+        .title(match filtered {
+            true => format!("Pets ({} of {}, {}) {}", view.len(), view.store.db.pets.len(), sort.label(), position),
+            false => format!("Pets ({}) {}", sort.label(), position),
+        })
         .border_type(BorderType::Plain);
 
-    let pet_list = read_db().expect("can fetch pet list");
-    let items: Vec<_> = pet_list
+    let categories = &view.store.db.categories;
+    let category_of = |pet: &Pet| categories.iter().find(|category| category.key == pet.category);
+    let items: Vec<_> = view.shown[window]
         .iter()
         .map(|pet| {
-            ListItem::new(Spans::from(vec![Span::styled(
+            let icon = match category_of(pet) {
+                Some(category) => Span::styled(format!("{} ", category.icon), category.style()),
+                None => Span::raw("? "),
+            };
+            ListItem::new(Spans::from(vec![icon, Span::styled(
                 pet.name.clone(),
                 Style::default(),
             )]))
         })
         .collect();
 
-    let selected_pet = pet_list
-        .get(
-            pet_list_state
-                .selected()
-                .expect("there is always a selected pet"),
-        )
-        .expect("exists")
-        .clone();
+    // nothing is selected when the filter matches no pets
+    let selected_pet = view.selected_pet();
 
     let list = List::new(items).block(pets).highlight_style(
         Style::default()
@@ -445,13 +1586,34 @@ fn render_pets<'a>(pet_list_state: &ListState) -> (List<'a>, Table<'a>) {
             .add_modifier(Modifier::BOLD),
     );
 
-    let pet_detail = Table::new(vec![Row::new(vec![
-        Cell::from(Span::raw(selected_pet.id.to_string())),
-        Cell::from(Span::raw(selected_pet.name)),
-        Cell::from(Span::raw(selected_pet.category)),
-        Cell::from(Span::raw(selected_pet.age.to_string())),
-        Cell::from(Span::raw(selected_pet.created_at.to_string())),
-    ])])
+    let records = match selected_pet {
+        Some(pet) => health::render_records(pet, today),
+        None => Table::new(vec![]).block(
+            Block::default()
+                .borders(Borders::ALL)
+                .style(Style::default().fg(Color::White))
+                .title("Health records")
+                .border_type(BorderType::Plain),
+        ),
+    };
+    let detail_rows: Vec<_> = selected_pet
+        .into_iter()
+        .map(|pet| {
+            let age = pet.age_label(today);
+            let category = match category_of(pet) {
+                Some(category) => Span::styled(category.label(), category.style()),
+                None => Span::raw(format!("{} (unregistered)", pet.category)),
+            };
+            Row::new(vec![
+                Cell::from(Span::raw(pet.id.to_string())),
+                Cell::from(Span::raw(pet.name.clone())),
+                Cell::from(category),
+                Cell::from(Span::raw(age)),
+                Cell::from(Span::raw(pet.created_at.to_string())),
+            ])
+        })
+        .collect();
+    let pet_detail = Table::new(detail_rows)
     .header(Row::new(vec![
         Cell::from(Span::styled(
             "ID",
@@ -489,65 +1651,288 @@ fn render_pets<'a>(pet_list_state: &ListState) -> (List<'a>, Table<'a>) {
         Constraint::Percentage(20),
     ]);
 
-    (list, pet_detail)
+    (list, window_state, pet_detail, records)
 }
 
-fn read_db() -> Result<Vec<Pet>, Error> {
+/// Reads `db.json`, upgrading a file written by an older version on the way;
+/// the file it was upgraded from is kept next to it as `db.json.v<N>.bak`.
+fn read_store() -> Result<Db, Error> {
     let db_content = fs::read_to_string(DB_PATH)?;
-    let parsed: Vec<Pet> = serde_json::from_str(&db_content)?;
-    Ok(parsed)
+    let (db, version) = schema::load(&db_content)?;
+    if version < schema::SCHEMA_VERSION {
+        fs::write(format!("{}.v{}.bak", DB_PATH, version), &db_content)?;
+        write_store(&db)?;
+    }
+    Ok(db)
+}
+
+fn write_store(db: &Db) -> Result<(), Error> {
+    fs::write(DB_PATH, schema::save(db)?)?;
+    Ok(())
+}
+
+/// Saves `db` and appends how its pets differ from `before` to the audit log.
+fn write_store_audited(before: &[Pet], db: &Db, source: Source) -> Result<(), Error> {
+    write_store(db)?;
+    audit::append(&audit::changes(before, &db.pets, source)?)
+}
+
+fn read_db() -> Result<Vec<Pet>, Error> {
+    Ok(read_store()?.pets)
+}
+
+/// One past the highest id any pet has had, in the store, the trash or the audit log,
+/// so a new pet never takes over the history of a purged one.
+fn next_pet_id(db: &Db) -> Result<usize, Error> {
+    let logged = audit::history(None)?.into_iter().map(|entry| entry.pet_id).max();
+    let stored = db.pets.iter().chain(&db.trash).map(|pet| pet.id).max();
+    Ok(logged.max(stored).map_or(1, |id| id + 1))
 }
 
 fn add_random_pet_to_db() -> Result<Vec<Pet>, Error> {
     let mut rng = rand::thread_rng();
-    let db_content = fs::read_to_string(DB_PATH)?;
-    let mut parsed: Vec<Pet> = serde_json::from_str(&db_content)?;
-    let catsdogs = match rng.gen_range(0, 1) {
-        0 => "cats",
-        _ => "dogs",
-    };
+    let mut db = read_store()?;
+    let category = db
+        .categories
+        .choose(&mut rng)
+        .map(|category| category.key.clone())
+        .ok_or_else(|| Error::CommandError(String::from("add a category first")))?;
 
-    let random_pet = Pet {
-        id: rng.gen_range(0, 9999999),
-        name: rng.sample_iter(Alphanumeric).take(10).collect(),
-        category: catsdogs.to_owned(),
-        age: rng.gen_range(1, 15),
-        created_at: Utc::now(),
-    };
+    let name: String = rng.sample_iter(Alphanumeric).take(10).collect();
+    let today = Local::now().date_naive();
+    let birthdate = age::estimated_birthdate(rng.gen_range(1, 15), today).unwrap_or(today);
+    let random_pet = Pet::new(next_pet_id(&db)?, &name, &category, birthdate, true);
+
+    let before = db.pets.clone();
+    db.pets.push(random_pet);
+    write_store_audited(&before, &db, Source::Tui)?;
+    Ok(db.pets)
+}
+
+/// Adds a pet to one of the registered categories, given by key or name.
+fn add_pet_to_db(name: &str, category: &str, birthdate: NaiveDate, estimated: bool, source: Source) -> Result<Pet, Error> {
+    let mut db = read_store()?;
+    let category = categories::lookup(&db.categories, category)?.key.clone();
+    let pet = Pet::new(next_pet_id(&db)?, name, &category, birthdate, estimated);
+
+    let before = db.pets.clone();
+    db.pets.push(pet.clone());
+    write_store_audited(&before, &db, source)?;
+    Ok(pet)
+}
+
+/// Applies `edit` to the stored categories and saves them if it worked, returning the status line.
+fn edit_categories(edit: impl FnOnce(&mut Db) -> Result<String, Error>) -> String {
+    let result = read_store().and_then(|mut db| {
+        let before = db.pets.clone();
+        let status = edit(&mut db)?;
+        write_store_audited(&before, &db, Source::Tui)?;
+        Ok(status)
+    });
+    match result {
+        Ok(status) => status,
+        Err(e) => e.to_string(),
+    }
+}
 
-    parsed.push(random_pet);
-    fs::write(DB_PATH, &serde_json::to_vec(&parsed)?)?;
-    Ok(parsed)
+/// Applies `edit` to the trash, saves it and logs the entry it returns, giving the status line.
+fn edit_trash(edit: impl FnOnce(&mut Db) -> Result<AuditEntry, Error>) -> String {
+    let result = read_store().and_then(|mut db| {
+        let entry = edit(&mut db)?;
+        write_store(&db)?;
+        audit::append(std::slice::from_ref(&entry))?;
+        Ok(format!("{} {}", entry.action.label(), entry.pet_name))
+    });
+    match result {
+        Ok(status) => status,
+        Err(e) => e.to_string(),
+    }
 }
 
-// need state
-fn remove_channel(chans_list_state: &mut ListState) -> Result<(), Error> {
-    let selection = chans_list_state.selected();
-    if let Some(selected) = chans_list_state.selected() {
-        println!("{}", Some(selection));
-        //bot.remove()
+/// Merges the pair `compare` shows, logging the merge and the duplicate going to the trash.
+fn merge_duplicates(compare: &Compare) -> Result<String, Error> {
+    let mut db = read_store()?;
+    let (before, merged, absorbed) = duplicates::merge(&mut db, compare, Utc::now())?;
+    write_store(&db)?;
+    audit::append(&[
+        AuditEntry::new(Action::Merge, Source::Tui, Some(&before), Some(&merged)),
+        AuditEntry::new(Action::Delete, Source::Tui, Some(&absorbed), None),
+    ])?;
+    Ok(format!("merged {} into {}, it's in the trash", absorbed.name, merged.name))
+}
+
+/// Purges pets that have been in the trash longer than `retention_days`, logging each one.
+fn purge_expired_trash(retention_days: u64) -> Result<(), Error> {
+    let mut db = read_store()?;
+    let purged = trash::purge_expired(&mut db, Utc::now(), retention_days);
+    if !purged.is_empty() {
+        write_store(&db)?;
+        let entries: Vec<_> = purged
+            .iter()
+            .map(|pet| AuditEntry::new(Action::Purge, Source::Retention, Some(pet), None))
+            .collect();
+        audit::append(&entries)?;
     }
     Ok(())
 }
 
-fn remove_pet_at_index(pet_list_state: &mut ListState) -> Result<(), Error> {
-    if let Some(selected) = pet_list_state.selected() {
-        let db_content = fs::read_to_string(DB_PATH)?;
-        let mut parsed: Vec<Pet> = serde_json::from_str(&db_content)?;
-        parsed.remove(selected);
-        fs::write(DB_PATH, &serde_json::to_vec(&parsed)?)?;
-        let amount_pets = read_db().expect("can fetch pet list").len();
-        if selected > 0 {
-            pet_list_state.select(Some(selected - 1));
-        } else {
-            pet_list_state.select(Some(0));
+/// Puts back the pet `entry` deleted, returning the status line.
+fn restore_pet(entry: &audit::AuditEntry, source: Source) -> Result<String, Error> {
+    let mut db = read_store()?;
+    let restored = audit::restore(&mut db, entry, source)?;
+    write_store(&db)?;
+    audit::append(&[restored])?;
+    Ok(format!("restored {} (#{})", entry.pet_name, entry.pet_id))
+}
+
+/// The `pets` subcommands, printing to stdout.
+fn run_pets_command(command: &PetsCommand) -> Result<(), Error> {
+    match command {
+        PetsCommand::History(pet_id) => {
+            for entry in audit::history(*pet_id)?.iter().rev() {
+                println!(
+                    "{}  {:<9} {} #{} via {}",
+                    entry.at.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S"),
+                    entry.action.label(),
+                    entry.pet_name,
+                    entry.pet_id,
+                    entry.source.label()
+                );
+            }
         }
+        PetsCommand::Restore(pet_id) => println!("{}", restore_pet(&audit::last_entry(*pet_id)?, Source::Cli)?),
+        PetsCommand::Query(text) => {
+            let query = Query::parse(text).map_err(|e| match e {
+                Error::QueryError { column, message } => Error::CommandError(query::explain(text, column, &message)),
+                e => e,
+            })?;
+            let today = Local::now().date_naive();
+            for pet in query.run(read_db()?, today) {
+                println!(
+                    "{:<8} {:<12} {:<10} {:<9} born {}  added {}",
+                    pet.id,
+                    pet.name,
+                    pet.category,
+                    pet.age_label(today),
+                    pet.birthdate,
+                    pet.created_at.format("%Y-%m-%d")
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The `export` subcommand: a transcript of the persisted logs, to a file or stdout.
+fn export_logs(options: &ExportOptions) -> Result<(), Error> {
+    let (messages, skipped) = logs::read_channel(&options.channel, options.since, options.until)?;
+    if skipped > 0 {
+        // stderr, the transcript itself may be going to stdout
+        eprintln!("skipped {} unreadable log lines or files", skipped);
+    }
+    let title = format!("#{}", options.channel);
+    let transcript = export::render_transcript(&messages, &title, options.format)?;
+    match &options.out {
+        Some(path) => fs::write(path, transcript)?,
+        None => print!("{}", transcript),
     }
     Ok(())
 }
 
-fn get_chans_list() -> Vec<String> {
-    vec![String::from("strager"), String::from("het_tanis")]
+/// Replying to a whisper, which twitch no longer lets a chat connection do.
+fn send_whisper(text: &str) -> Result<String, Error> {
+    if text.trim().is_empty() {
+        return Ok(String::new());
+    }
+    Err(Error::CommandError(format!("can't whisper: {}", moderation::IRC_COMMANDS_RETIRED)))
+}
+
+/// Sends the bot's reply to `channel`, if it has one, and records it in the Bot log.
+fn run_bot_action(action: BotAction, channel: &str, channels: &mut [Channel], out_tx: &Sender<String>) {
+    if let Some(reply) = &action.reply {
+        let target = format!("#{}", channel);
+        let _ = out_tx.send(chat::encode(twitchchat::commands::privmsg(&target, reply)));
+    }
+    let log = ChatMessage::notice(channel, action.log, BTreeMap::new());
+    chat::push_to(channels, ChannelKind::BotLog, log);
+}
+
+/// Closes the channel's poll, then posts and exports the results as configured.
+fn finish_poll(channel: &mut Channel, config: &PollConfig, out_tx: &Sender<String>) {
+    let poll = match channel.poll.as_mut() {
+        Some(poll) => poll,
+        None => return,
+    };
+    poll.closed = true;
+    let summary = poll.summary();
+    if config.post_results {
+        let target = format!("#{}", channel.name);
+        let _ = out_tx.send(chat::encode(twitchchat::commands::privmsg(&target, &summary)));
+    }
+    let mut text = summary;
+    if config.export {
+        match poll.export(&channel.name) {
+            Ok(path) => text = format!("{}, saved to {}", text, path),
+            Err(e) => text = format!("{}, couldn't save it: {}", text, e),
+        }
+    }
+    let notice = ChatMessage::notice(&channel.name, text, BTreeMap::new());
+    channel.push(notice);
+}
+
+fn run_chat_command(
+    line: &str,
+    config: &mut ChatConfig,
+    filter: &mut Filter,
+    triggers: &mut Triggers,
+    channel: Option<&mut Channel>,
+) -> Result<String, Error> {
+    let cmd = ChatCommand::parse(line)?;
+    let no_channel = || Error::CommandError(String::from("select a joined channel first"));
+    match &cmd {
+        ChatCommand::DryRun => {
+            return Ok(match triggers.toggle_dry_run() {
+                true => String::from("triggers will only log what they'd send"),
+                false => String::from("triggers will send replies"),
+            });
+        }
+        ChatCommand::Poll { secs, question, options } => {
+            let channel = channel.ok_or_else(no_channel)?;
+            channel.poll = Some(Poll::new(question, options, chrono::Duration::seconds(*secs as i64)));
+            return Ok(format!("poll running for {}s, vote with !vote <number>", secs));
+        }
+        ChatCommand::EndPoll => {
+            let channel = channel.ok_or_else(no_channel)?;
+            return Ok(match &mut channel.poll {
+                Some(poll) if !poll.closed => {
+                    poll.ends_at = Utc::now();
+                    String::from("ending the poll")
+                }
+                Some(_) => {
+                    channel.poll = None;
+                    String::from("dismissed the poll results")
+                }
+                None => String::from("no poll in this channel"),
+            });
+        }
+        _ => {}
+    }
+    let channel = channel.map(|channel| channel.name.as_str());
+    let status = filter::apply_command(&mut config.filters, &cmd, channel)?;
+    *filter = Filter::new(&config.filters)?;
+    config::write_config(config)?;
+    Ok(status)
+}
+
+fn remove_selected_pet(view: &mut PetView) -> Result<(), Error> {
+    if let Some(id) = view.selected_pet().map(|pet| pet.id) {
+        let mut db = read_store()?;
+        let before = db.pets.clone();
+        trash::trash(&mut db, id, Utc::now())?;
+        write_store_audited(&before, &db, Source::Tui)?;
+        view.select(view.selected.saturating_sub(1));
+    }
+    Ok(())
 }
 
 // fn get_files_list(dir: std::path::PathBuf) -> Vec<DirEntry> {
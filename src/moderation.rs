@@ -0,0 +1,66 @@
+use tui::{
+    layout::Rect,
+    style::{Color, Modifier, Style},
+    text::{Span, Spans},
+    widgets::{Block, BorderType, Borders, List, ListItem},
+};
+
+use crate::chat::ChatMessage;
+
+#[derive(Copy, Clone, Debug)]
+pub enum ModAction {
+    Timeout,
+    Ban,
+    Delete,
+}
+
+pub const MOD_ACTIONS: [ModAction; 3] = [ModAction::Timeout, ModAction::Ban, ModAction::Delete];
+
+/// Why moderating and whispering are turned off: twitch stopped taking `/timeout`, `/ban`,
+/// `/delete` and `/w` as chat messages in February 2023, they only work through the Helix API now.
+pub const IRC_COMMANDS_RETIRED: &str =
+    "twitch no longer accepts this over chat since February 2023, use the twitch website";
+
+impl ModAction {
+    pub fn label(self) -> &'static str {
+        match self {
+            ModAction::Timeout => "Timeout (10m)",
+            ModAction::Ban => "Ban",
+            ModAction::Delete => "Delete message",
+        }
+    }
+}
+
+pub fn render_mod_popup<'a>(msg: &ChatMessage) -> List<'a> {
+    let items: Vec<_> = MOD_ACTIONS
+        .iter()
+        .map(|action| ListItem::new(Spans::from(vec![Span::raw(action.label())])))
+        .collect();
+
+    List::new(items)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .style(Style::default().fg(Color::White).bg(Color::Black))
+                .title(format!("Moderate {} (unavailable)", msg.display_name))
+                .border_type(BorderType::Rounded),
+        )
+        .highlight_style(
+            Style::default()
+                .bg(Color::Yellow)
+                .fg(Color::Black)
+                .add_modifier(Modifier::BOLD),
+        )
+}
+
+/// A box of the given size centered in `area`, clamped to fit.
+pub fn centered_rect(width: u16, height: u16, area: Rect) -> Rect {
+    let width = width.min(area.width);
+    let height = height.min(area.height);
+    Rect::new(
+        area.x + (area.width - width) / 2,
+        area.y + (area.height - height) / 2,
+        width,
+        height,
+    )
+}
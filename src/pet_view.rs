@@ -0,0 +1,203 @@
+use chrono::prelude::*;
+use std::ops::Range;
+use tui::{
+    style::{Color, Style},
+    text::{Span, Spans},
+    widgets::{Block, BorderType, Borders, Paragraph},
+};
+use tui_input::Input;
+
+use crate::age::PetSort;
+use crate::query::Query;
+use crate::schema::Db;
+use crate::store::Store;
+use crate::{Error, Pet};
+
+/// The Pets list as it's shown, kept between frames. The store is only read again when
+/// `db.json` changes, and the order and filter only applied again when they or the day
+/// change, so a frame costs the same with a hundred pets or a hundred thousand.
+#[derive(Default)]
+pub struct PetView {
+    // what `shown` was worked out for
+    built_for: Option<(PetSort, Option<String>, NaiveDate)>,
+    pub store: Store,
+    /// the pets in list order
+    pub shown: Vec<Pet>,
+    /// the row the selection is on
+    pub selected: usize,
+    /// the first row on screen
+    offset: usize,
+}
+
+impl PetView {
+    /// A view of `db` that's never read from disk.
+    pub fn new(db: Db) -> PetView {
+        PetView {
+            store: Store::new(db),
+            ..PetView::default()
+        }
+    }
+
+    /// Reads the store again if it changed on disk, then brings the list up to date.
+    pub fn refresh(
+        &mut self,
+        sort: PetSort,
+        query: Option<&(String, Query)>,
+        today: NaiveDate,
+    ) -> Result<(), Error> {
+        if self.store.refresh()? {
+            self.built_for = None;
+        }
+        self.build(sort, query, today);
+        Ok(())
+    }
+
+    /// Sorts and filters the pets unless that's already been done for these settings.
+    pub fn build(&mut self, sort: PetSort, query: Option<&(String, Query)>, today: NaiveDate) {
+        let key = (sort, query.map(|(text, _)| text.clone()), today);
+        if self.built_for.as_ref() == Some(&key) {
+            return;
+        }
+        self.shown = crate::shown_pets(
+            self.store.db.pets.clone(),
+            sort,
+            query.map(|(_, query)| query),
+            today,
+        );
+        self.built_for = Some(key);
+        self.select(self.selected);
+    }
+
+    pub fn len(&self) -> usize {
+        self.shown.len()
+    }
+
+    pub fn selected_pet(&self) -> Option<&Pet> {
+        self.shown.get(self.selected)
+    }
+
+    /// Selects row `index`, or the last one if it's past the end.
+    pub fn select(&mut self, index: usize) {
+        self.selected = index.min(self.len().saturating_sub(1));
+    }
+
+    /// One row up or down, wrapping around at either end.
+    pub fn step(&mut self, forward: bool) {
+        let len = self.len();
+        if len == 0 {
+            return;
+        }
+        self.selected = match forward {
+            true => (self.selected + 1) % len,
+            false => (self.selected + len - 1) % len,
+        };
+    }
+
+    /// `rows` up or down, stopping at either end.
+    pub fn page(&mut self, rows: usize, forward: bool) {
+        match forward {
+            true => self.select(self.selected.saturating_add(rows)),
+            false => self.select(self.selected.saturating_sub(rows)),
+        }
+    }
+
+    /// Scrolls just enough for the selection to be among `height` rows, and returns them.
+    pub fn window(&mut self, height: usize) -> Range<usize> {
+        let height = height.max(1);
+        if self.selected < self.offset {
+            self.offset = self.selected;
+        } else if self.selected >= self.offset + height {
+            self.offset = self.selected + 1 - height;
+        }
+        // a shorter list (after a delete or a filter) shouldn't leave rows empty at the bottom
+        self.offset = self.offset.min(self.len().saturating_sub(height));
+        self.offset..(self.offset + height).min(self.len())
+    }
+}
+
+/// The "go to" line for jumping to a row by number.
+pub fn render_jump<'a>(input: &Input, len: usize, width: u16) -> Paragraph<'a> {
+    let scroll = input.visual_scroll(width.saturating_sub(3) as usize);
+    Paragraph::new(Spans::from(vec![
+        Span::styled("> ", Style::default().fg(Color::Yellow)),
+        Span::raw(input.value().chars().skip(scroll).collect::<String>()),
+    ]))
+    .block(
+        Block::default()
+            .borders(Borders::ALL)
+            .style(Style::default().fg(Color::White).bg(Color::Black))
+            .title(format!("Go to pet 1-{} (Enter jumps, Esc cancels)", len))
+            .border_type(BorderType::Rounded),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn view_of(count: usize) -> PetView {
+        let today = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
+        let pets = (0..count)
+            .map(|i| Pet::new(i + 1, &format!("Pet {}", i), "dogs", today, false))
+            .collect();
+        let mut view = PetView::new(Db {
+            pets,
+            categories: vec![],
+            trash: vec![],
+        });
+        view.build(PetSort::Added, None, today);
+        view
+    }
+
+    #[test]
+    fn empty_list() {
+        let mut view = view_of(0);
+        assert_eq!(view.window(10), 0..0);
+        view.page(10, true);
+        view.step(true);
+        view.select(5);
+        assert_eq!(view.selected, 0);
+        assert!(view.selected_pet().is_none());
+        assert_eq!(view.window(10), 0..0);
+    }
+
+    #[test]
+    fn shorter_than_the_window() {
+        let mut view = view_of(3);
+        assert_eq!(view.window(10), 0..3);
+        view.page(10, true);
+        assert_eq!(view.selected, 2);
+        assert_eq!(view.window(10), 0..3);
+        view.page(10, false);
+        assert_eq!(view.selected, 0);
+    }
+
+    #[test]
+    fn scrolls_with_the_selection() {
+        let mut view = view_of(20);
+        view.page(12, true);
+        assert_eq!(view.window(5), 8..13);
+        view.page(3, false);
+        assert_eq!(view.window(5), 8..13);
+        view.page(3, false);
+        assert_eq!(view.window(5), 6..11);
+    }
+
+    #[test]
+    fn selection_past_the_end_after_a_delete() {
+        let mut view = view_of(20);
+        view.select(19);
+        assert_eq!(view.window(5), 15..20);
+
+        view.store.db.pets.truncate(12);
+        view.built_for = None;
+        view.build(
+            PetSort::Added,
+            None,
+            NaiveDate::from_ymd_opt(2024, 3, 1).unwrap(),
+        );
+        assert_eq!(view.selected, 11);
+        assert_eq!(view.selected_pet().map(|pet| pet.id), Some(12));
+        assert_eq!(view.window(5), 7..12);
+    }
+}
@@ -0,0 +1,202 @@
+use chrono::prelude::*;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use tui::{
+    style::{Color, Modifier, Style},
+    widgets::{BarChart, Block, BorderType, Borders},
+};
+
+use crate::chat::{ChatMessage, MessageKind};
+use crate::Error;
+
+const POLLS_DIR: &str = "./data/polls";
+
+/// A poll in one channel, voted on with `!vote <number>` or `!vote <option>`.
+pub struct Poll {
+    pub question: String,
+    pub options: Vec<String>,
+    // option index by login, only the first vote of each user counts
+    votes: HashMap<String, usize>,
+    pub started_at: DateTime<Utc>,
+    pub ends_at: DateTime<Utc>,
+    /// past `ends_at`, the results stay up until the next poll or `/endpoll`
+    pub closed: bool,
+}
+
+#[derive(Serialize)]
+struct PollResult<'a> {
+    option: &'a str,
+    votes: u64,
+}
+
+#[derive(Serialize)]
+struct PollExport<'a> {
+    channel: &'a str,
+    question: &'a str,
+    started_at: DateTime<Utc>,
+    ended_at: DateTime<Utc>,
+    results: Vec<PollResult<'a>>,
+    /// the option each login voted for
+    votes: BTreeMap<&'a str, &'a str>,
+}
+
+impl Poll {
+    pub fn new(question: &str, options: &[String], duration: chrono::Duration) -> Poll {
+        let started_at = Utc::now();
+        Poll {
+            question: question.to_string(),
+            options: options.to_vec(),
+            votes: HashMap::new(),
+            started_at,
+            ends_at: started_at + duration,
+            closed: false,
+        }
+    }
+
+    /// Counts `msg` if it's a `!vote` for one of the options, returning whether it did.
+    pub fn vote(&mut self, msg: &ChatMessage) -> bool {
+        if self.closed || msg.kind != MessageKind::Chat || self.votes.contains_key(&msg.login) {
+            return false;
+        }
+        let mut words = msg.text.trim().splitn(2, ' ');
+        if words.next() != Some("!vote") {
+            return false;
+        }
+        let choice = words.next().unwrap_or("").trim();
+        let index = match choice.parse::<usize>() {
+            Ok(number) if (1..=self.options.len()).contains(&number) => number - 1,
+            _ => match self
+                .options
+                .iter()
+                .position(|option| option.eq_ignore_ascii_case(choice))
+            {
+                Some(index) => index,
+                None => return false,
+            },
+        };
+        self.votes.insert(msg.login.clone(), index);
+        true
+    }
+
+    /// Votes per option, in option order.
+    pub fn tally(&self) -> Vec<u64> {
+        let mut counts = vec![0; self.options.len()];
+        for index in self.votes.values() {
+            counts[*index] += 1;
+        }
+        counts
+    }
+
+    pub fn is_due(&self, now: DateTime<Utc>) -> bool {
+        !self.closed && now >= self.ends_at
+    }
+
+    /// One line with every option's count, leading with the winner(s).
+    pub fn summary(&self) -> String {
+        let tally = self.tally();
+        let best = tally.iter().copied().max().unwrap_or(0);
+        let counts: Vec<_> = self
+            .options
+            .iter()
+            .zip(&tally)
+            .map(|(option, votes)| format!("{} {}", option, votes))
+            .collect();
+        let winners: Vec<_> = self
+            .options
+            .iter()
+            .zip(&tally)
+            .filter(|(_, votes)| best > 0 && **votes == best)
+            .map(|(option, _)| option.as_str())
+            .collect();
+        let outcome = match winners.len() {
+            0 => String::from("no votes"),
+            1 => format!("{} wins", winners[0]),
+            _ => format!("tie between {}", winners.join(" and ")),
+        };
+        format!(
+            "Poll \"{}\": {} ({})",
+            self.question,
+            outcome,
+            counts.join(", ")
+        )
+    }
+
+    /// Writes the results to `data/polls`, returning the file's path.
+    pub fn export(&self, channel: &str) -> Result<String, Error> {
+        let tally = self.tally();
+        let export = PollExport {
+            channel,
+            question: &self.question,
+            started_at: self.started_at,
+            ended_at: self.ends_at,
+            results: self
+                .options
+                .iter()
+                .zip(tally)
+                .map(|(option, votes)| PollResult {
+                    option: option.as_str(),
+                    votes,
+                })
+                .collect(),
+            votes: self
+                .votes
+                .iter()
+                .map(|(login, index)| (login.as_str(), self.options[*index].as_str()))
+                .collect(),
+        };
+        fs::create_dir_all(POLLS_DIR)?;
+        let path = format!(
+            "{}/{}-{}.json",
+            POLLS_DIR,
+            channel,
+            self.started_at.format("%Y%m%d-%H%M%S")
+        );
+        fs::write(&path, &serde_json::to_vec_pretty(&export)?)?;
+        Ok(path)
+    }
+
+    /// Bar labels, "1 cats" style so they still line up with `!vote 1` when cut short.
+    pub fn labels(&self) -> Vec<String> {
+        self.options
+            .iter()
+            .enumerate()
+            .map(|(index, option)| format!("{} {}", index + 1, option))
+            .collect()
+    }
+}
+
+/// The live results as one bar per option; `data` comes from `labels` and `tally`.
+pub fn render_poll<'a>(poll: &Poll, data: &'a [(&'a str, u64)], width: u16) -> BarChart<'a> {
+    let total: u64 = data.iter().map(|(_, votes)| votes).sum();
+    let state = if poll.closed {
+        String::from("closed")
+    } else {
+        let left = (poll.ends_at - Utc::now()).num_seconds().max(0);
+        format!("{}s left", left)
+    };
+    let bars = data.len().max(1) as u16;
+    let bar_width = (width.saturating_sub(2) / bars)
+        .saturating_sub(1)
+        .clamp(3, 20);
+
+    BarChart::default()
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .style(Style::default().fg(Color::White))
+                .title(format!("{} ({} votes, {})", poll.question, total, state))
+                .border_type(BorderType::Rounded),
+        )
+        .data(data)
+        .bar_width(bar_width)
+        .bar_gap(1)
+        .bar_style(Style::default().fg(Color::LightMagenta))
+        .value_style(
+            Style::default()
+                .fg(Color::Black)
+                .bg(Color::LightMagenta)
+                .add_modifier(Modifier::BOLD),
+        )
+        .label_style(Style::default().fg(Color::Yellow))
+}
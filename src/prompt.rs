@@ -0,0 +1,66 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use tui::{
+    style::{Color, Style},
+    text::{Span, Spans},
+    widgets::{Block, BorderType, Borders, Paragraph},
+};
+use tui_input::{Input, InputRequest};
+
+/// Maps a crossterm key to an edit on the prompt, `None` for keys the prompt doesn't handle.
+pub fn to_input_request(key: &KeyEvent) -> Option<InputRequest> {
+    let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+    Some(match key.code {
+        KeyCode::Backspace if ctrl => InputRequest::DeletePrevWord,
+        KeyCode::Backspace => InputRequest::DeletePrevChar,
+        KeyCode::Delete => InputRequest::DeleteNextChar,
+        KeyCode::Left if ctrl => InputRequest::GoToPrevWord,
+        KeyCode::Left => InputRequest::GoToPrevChar,
+        KeyCode::Right if ctrl => InputRequest::GoToNextWord,
+        KeyCode::Right => InputRequest::GoToNextChar,
+        KeyCode::Home => InputRequest::GoToStart,
+        KeyCode::End => InputRequest::GoToEnd,
+        KeyCode::Char('u') if ctrl => InputRequest::DeleteLine,
+        KeyCode::Char('k') if ctrl => InputRequest::DeleteTillEnd,
+        KeyCode::Char(c) => InputRequest::InsertChar(c),
+        _ => return None,
+    })
+}
+
+/// The prompt line, or the result of the last command while it's closed.
+///
+/// In a whisper conversation it doubles as the compose line for `whisper_to`.
+pub fn render_prompt<'a>(
+    input: Option<&Input>,
+    status: &str,
+    width: u16,
+    whisper_to: Option<&str>,
+) -> Paragraph<'a> {
+    let line = match input {
+        Some(input) => {
+            let scroll = input.visual_scroll(width.saturating_sub(3) as usize);
+            Spans::from(vec![
+                Span::styled("> ", Style::default().fg(Color::Yellow)),
+                Span::raw(input.value().chars().skip(scroll).collect::<String>()),
+            ])
+        }
+        None if status.is_empty() => Spans::from(vec![Span::styled(
+            match whisper_to {
+                Some(login) => format!("press '/' to write to {}", login),
+                None => String::from("press '/' to enter a command"),
+            },
+            Style::default().fg(Color::DarkGray),
+        )]),
+        None => Spans::from(vec![Span::raw(status.to_string())]),
+    };
+
+    Paragraph::new(line).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .style(Style::default().fg(Color::White))
+            .title(match whisper_to {
+                Some(login) => format!("Whisper to @{}", login),
+                None => String::from("Command"),
+            })
+            .border_type(BorderType::Plain),
+    )
+}
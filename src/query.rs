@@ -0,0 +1,793 @@
+use chrono::prelude::*;
+use std::cmp::Ordering;
+use tui::{
+    style::{Color, Modifier, Style},
+    text::{Span, Spans},
+    widgets::{Block, BorderType, Borders, Paragraph},
+};
+use tui_input::Input;
+
+use crate::categories;
+use crate::{Error, Pet};
+
+/// A pet field a query can filter or order by.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Field {
+    Id,
+    Name,
+    Category,
+    /// in whole years
+    Age,
+    Birthdate,
+    CreatedAt,
+    Breed,
+    Sex,
+    Weight,
+    Color,
+    Owner,
+    Notes,
+    Tags,
+}
+
+const FIELDS: [(&str, Field); 13] = [
+    ("id", Field::Id),
+    ("name", Field::Name),
+    ("category", Field::Category),
+    ("age", Field::Age),
+    ("birthdate", Field::Birthdate),
+    ("created_at", Field::CreatedAt),
+    ("breed", Field::Breed),
+    ("sex", Field::Sex),
+    ("weight", Field::Weight),
+    ("color", Field::Color),
+    ("owner", Field::Owner),
+    ("notes", Field::Notes),
+    ("tags", Field::Tags),
+];
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Kind {
+    Text,
+    Number,
+    Date,
+    /// matches if any item does
+    List,
+}
+
+impl Field {
+    fn name(self) -> &'static str {
+        FIELDS
+            .iter()
+            .find(|(_, field)| *field == self)
+            .map_or("?", |(name, _)| name)
+    }
+
+    fn kind(self) -> Kind {
+        match self {
+            Field::Id | Field::Age | Field::Weight => Kind::Number,
+            Field::Birthdate | Field::CreatedAt => Kind::Date,
+            Field::Tags => Kind::List,
+            _ => Kind::Text,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    /// case-insensitive substring, text fields only
+    Contains,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Literal {
+    Text(String),
+    Number(f64),
+    Date(NaiveDate),
+}
+
+/// The `where` part of a query.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Expr {
+    Compare {
+        field: Field,
+        op: Op,
+        value: Literal,
+    },
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+}
+
+/// A parsed query: `[filter] [order by field [asc|desc], ...] [limit n]`. Values with
+/// spaces go in single or double quotes; an apostrophe inside a word like `O'Brien` needn't.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Query {
+    pub filter: Option<Expr>,
+    /// (field, descending)
+    pub order: Vec<(Field, bool)>,
+    pub limit: Option<usize>,
+}
+
+fn error(column: usize, message: String) -> Error {
+    Error::QueryError { column, message }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Word(String),
+    /// a quoted string
+    Text(String),
+    Op(Op),
+    Open,
+    Close,
+    Comma,
+}
+
+impl Token {
+    fn describe(&self) -> String {
+        match self {
+            Token::Word(word) => format!("'{}'", word),
+            Token::Text(text) => format!("\"{}\"", text),
+            Token::Op(_) => String::from("a comparison"),
+            Token::Open => String::from("'('"),
+            Token::Close => String::from("')'"),
+            Token::Comma => String::from("','"),
+        }
+    }
+}
+
+/// The tokens of `input`, each with the 1-based column it starts at.
+fn tokenize(input: &str) -> Result<Vec<(Token, usize)>, Error> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = vec![];
+    let mut at = 0;
+    while at < chars.len() {
+        let column = at + 1;
+        let c = chars[at];
+        let next = chars.get(at + 1).copied();
+        let (token, len) = match c {
+            c if c.is_whitespace() => {
+                at += 1;
+                continue;
+            }
+            '(' => (Token::Open, 1),
+            ')' => (Token::Close, 1),
+            ',' => (Token::Comma, 1),
+            '~' => (Token::Op(Op::Contains), 1),
+            '=' if next == Some('=') => (Token::Op(Op::Eq), 2),
+            '=' => (Token::Op(Op::Eq), 1),
+            '!' if next == Some('=') => (Token::Op(Op::Ne), 2),
+            '<' if next == Some('=') => (Token::Op(Op::Le), 2),
+            '<' if next == Some('>') => (Token::Op(Op::Ne), 2),
+            '<' => (Token::Op(Op::Lt), 1),
+            '>' if next == Some('=') => (Token::Op(Op::Ge), 2),
+            '>' => (Token::Op(Op::Gt), 1),
+            '"' | '\'' => {
+                let end = chars[at + 1..]
+                    .iter()
+                    .position(|quote| *quote == c)
+                    .ok_or_else(|| error(column, String::from("this string is never closed")))?;
+                let text: String = chars[at + 1..at + 1 + end].iter().collect();
+                (Token::Text(text), end + 2)
+            }
+            c if is_word_char(c) => {
+                // an apostrophe inside a word is part of it, so `name = O'Brien` works unquoted
+                let len = 1 + chars[at + 1..]
+                    .iter()
+                    .take_while(|c| is_word_char(**c) || **c == '\'')
+                    .count();
+                (Token::Word(chars[at..at + len].iter().collect()), len)
+            }
+            other => return Err(error(column, format!("unexpected '{}'", other))),
+        };
+        tokens.push((token, column));
+        at += len;
+    }
+    Ok(tokens)
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '_' | '-' | '.' | ':')
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    at: usize,
+    /// the column just past the end, for "expected ... at the end" errors
+    end: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.at).map(|(token, _)| token)
+    }
+
+    fn column(&self) -> usize {
+        self.tokens
+            .get(self.at)
+            .map_or(self.end, |(_, column)| *column)
+    }
+
+    fn found(&self) -> String {
+        self.peek()
+            .map_or(String::from("the end"), |token| token.describe())
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.peek().cloned();
+        self.at += 1;
+        token
+    }
+
+    fn at_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(Token::Word(word)) if word.eq_ignore_ascii_case(keyword))
+    }
+
+    fn keyword(&mut self, keyword: &str) -> bool {
+        let found = self.at_keyword(keyword);
+        if found {
+            self.at += 1;
+        }
+        found
+    }
+
+    fn query(&mut self) -> Result<Query, Error> {
+        let filter = match self.peek() {
+            None => None,
+            Some(_) if self.at_keyword("order") || self.at_keyword("limit") => None,
+            Some(_) => Some(self.or()?),
+        };
+
+        let mut order = vec![];
+        if self.keyword("order") {
+            if !self.keyword("by") {
+                return Err(error(
+                    self.column(),
+                    format!("expected 'by' after 'order', found {}", self.found()),
+                ));
+            }
+            loop {
+                let field = self.field()?;
+                let descending = if self.keyword("desc") {
+                    true
+                } else {
+                    self.keyword("asc");
+                    false
+                };
+                order.push((field, descending));
+                if self.peek() != Some(&Token::Comma) {
+                    break;
+                }
+                self.at += 1;
+            }
+        }
+
+        let mut limit = None;
+        if self.keyword("limit") {
+            let column = self.column();
+            limit = match self.next() {
+                Some(Token::Word(n)) => n.parse().ok(),
+                _ => None,
+            };
+            if limit.is_none() {
+                return Err(error(column, String::from("limit needs a whole number")));
+            }
+        }
+
+        if self.peek().is_some() {
+            return Err(error(
+                self.column(),
+                format!(
+                    "expected 'and', 'or', 'order by' or 'limit', found {}",
+                    self.found()
+                ),
+            ));
+        }
+        Ok(Query {
+            filter,
+            order,
+            limit,
+        })
+    }
+
+    fn or(&mut self) -> Result<Expr, Error> {
+        let mut expr = self.and()?;
+        while self.keyword("or") {
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr, Error> {
+        let mut expr = self.not()?;
+        while self.keyword("and") {
+            expr = Expr::And(Box::new(expr), Box::new(self.not()?));
+        }
+        Ok(expr)
+    }
+
+    fn not(&mut self) -> Result<Expr, Error> {
+        if self.keyword("not") {
+            return Ok(Expr::Not(Box::new(self.not()?)));
+        }
+        if self.peek() == Some(&Token::Open) {
+            let open = self.column();
+            self.at += 1;
+            let expr = self.or()?;
+            if self.next() != Some(Token::Close) {
+                return Err(error(open, String::from("this '(' is never closed")));
+            }
+            return Ok(expr);
+        }
+        self.comparison()
+    }
+
+    fn field(&mut self) -> Result<Field, Error> {
+        let column = self.column();
+        let name = match self.next() {
+            Some(Token::Word(name)) => name,
+            _ => {
+                self.at -= 1;
+                return Err(error(
+                    column,
+                    format!("expected a field, found {}", self.found()),
+                ));
+            }
+        };
+        FIELDS
+            .iter()
+            .find(|(known, _)| known.eq_ignore_ascii_case(&name))
+            .map(|(_, field)| *field)
+            .ok_or_else(|| {
+                let known: Vec<_> = FIELDS.iter().map(|(known, _)| *known).collect();
+                error(
+                    column,
+                    format!("unknown field '{}', use one of: {}", name, known.join(", ")),
+                )
+            })
+    }
+
+    fn comparison(&mut self) -> Result<Expr, Error> {
+        let field = self.field()?;
+        let column = self.column();
+        let op = match self.next() {
+            Some(Token::Op(op)) => op,
+            _ => {
+                self.at -= 1;
+                return Err(error(
+                    column,
+                    format!(
+                        "expected =, !=, <, <=, >, >= or ~ after {}, found {}",
+                        field.name(),
+                        self.found()
+                    ),
+                ));
+            }
+        };
+        let kind = field.kind();
+        if op == Op::Contains && !matches!(kind, Kind::Text | Kind::List) {
+            return Err(error(
+                column,
+                format!("~ only works on text, {} isn't", field.name()),
+            ));
+        }
+        if kind == Kind::List && !matches!(op, Op::Eq | Op::Ne | Op::Contains) {
+            return Err(error(
+                column,
+                format!("{} only supports =, != and ~", field.name()),
+            ));
+        }
+
+        let column = self.column();
+        let raw = match self.next() {
+            Some(Token::Word(value)) | Some(Token::Text(value)) => value,
+            _ => {
+                self.at -= 1;
+                return Err(error(
+                    column,
+                    format!(
+                        "expected a value to compare {} with, found {}",
+                        field.name(),
+                        self.found()
+                    ),
+                ));
+            }
+        };
+        let value = match kind {
+            Kind::Number => Literal::Number(raw.parse().map_err(|_| {
+                error(
+                    column,
+                    format!("{} is a number, '{}' isn't", field.name(), raw),
+                )
+            })?),
+            Kind::Date => {
+                Literal::Date(NaiveDate::parse_from_str(&raw, "%Y-%m-%d").map_err(|_| {
+                    error(
+                        column,
+                        format!("{} needs a YYYY-MM-DD date, not '{}'", field.name(), raw),
+                    )
+                })?)
+            }
+            // pets store the key, so "Cats" and "cats" find the same ones
+            _ if field == Field::Category => Literal::Text(categories::key_for(&raw)),
+            _ => Literal::Text(raw.to_lowercase()),
+        };
+        Ok(Expr::Compare { field, op, value })
+    }
+}
+
+impl Query {
+    pub fn parse(input: &str) -> Result<Query, Error> {
+        let mut parser = Parser {
+            tokens: tokenize(input)?,
+            at: 0,
+            end: input.chars().count() + 1,
+        };
+        parser.query()
+    }
+
+    /// The matching pets, ordered and limited. Pets that tie keep the order they came in.
+    pub fn run(&self, pets: Vec<Pet>, today: NaiveDate) -> Vec<Pet> {
+        let mut pets: Vec<Pet> = pets
+            .into_iter()
+            .filter(|pet| {
+                self.filter
+                    .as_ref()
+                    .is_none_or(|filter| filter.matches(pet, today))
+            })
+            .collect();
+        if !self.order.is_empty() {
+            pets.sort_by(|a, b| {
+                self.order
+                    .iter()
+                    .map(|(field, descending)| {
+                        compare_slots(
+                            &slot(a, *field, today),
+                            &slot(b, *field, today),
+                            *descending,
+                        )
+                    })
+                    .find(|ordering| *ordering != Ordering::Equal)
+                    .unwrap_or(Ordering::Equal)
+            });
+        }
+        if let Some(limit) = self.limit {
+            pets.truncate(limit);
+        }
+        pets
+    }
+}
+
+// a field's value on one pet
+enum Slot {
+    Text(Option<String>),
+    List(Vec<String>),
+    Number(Option<f64>),
+    Date(Option<NaiveDate>),
+}
+
+fn slot(pet: &Pet, field: Field, today: NaiveDate) -> Slot {
+    let text = |value: &Option<String>| Slot::Text(value.as_ref().map(|v| v.to_lowercase()));
+    match field {
+        Field::Id => Slot::Number(Some(pet.id as f64)),
+        Field::Name => Slot::Text(Some(pet.name.to_lowercase())),
+        Field::Category => Slot::Text(Some(pet.category.clone())),
+        Field::Age => Slot::Number(Some(pet.age(today).years as f64)),
+        Field::Birthdate => Slot::Date(Some(pet.birthdate)),
+        Field::CreatedAt => Slot::Date(Some(pet.created_at.with_timezone(&Local).date_naive())),
+        Field::Breed => text(&pet.breed),
+        Field::Sex => Slot::Text(pet.sex.map(|sex| format!("{:?}", sex).to_lowercase())),
+        Field::Weight => Slot::Number(pet.weight),
+        Field::Color => text(&pet.color),
+        Field::Owner => text(&pet.owner),
+        Field::Notes => text(&pet.notes),
+        Field::Tags => Slot::List(pet.tags.iter().map(|tag| tag.to_lowercase()).collect()),
+    }
+}
+
+fn test<T: PartialOrd>(value: &T, op: Op, other: &T) -> bool {
+    match op {
+        Op::Eq => value == other,
+        Op::Ne => value != other,
+        Op::Lt => value < other,
+        Op::Le => value <= other,
+        Op::Gt => value > other,
+        Op::Ge => value >= other,
+        Op::Contains => false,
+    }
+}
+
+fn test_text(value: &str, op: Op, other: &str) -> bool {
+    match op {
+        Op::Contains => value.contains(other),
+        op => test(&value, op, &other),
+    }
+}
+
+impl Expr {
+    pub fn matches(&self, pet: &Pet, today: NaiveDate) -> bool {
+        match self {
+            Expr::And(a, b) => a.matches(pet, today) && b.matches(pet, today),
+            Expr::Or(a, b) => a.matches(pet, today) || b.matches(pet, today),
+            Expr::Not(expr) => !expr.matches(pet, today),
+            Expr::Compare { field, op, value } => match (slot(pet, *field, today), value) {
+                (Slot::Text(Some(text)), Literal::Text(other)) => test_text(&text, *op, other),
+                (Slot::List(items), Literal::Text(other)) => match op {
+                    Op::Ne => !items.iter().any(|item| item == other),
+                    op => items.iter().any(|item| test_text(item, *op, other)),
+                },
+                (Slot::Number(Some(number)), Literal::Number(other)) => test(&number, *op, other),
+                (Slot::Date(Some(date)), Literal::Date(other)) => test(&date, *op, other),
+                // a missing value is only ever "not equal"
+                _ => *op == Op::Ne,
+            },
+        }
+    }
+}
+
+// missing values go last whichever way it's ordered
+fn missing_last<T>(
+    a: Option<T>,
+    b: Option<T>,
+    descending: bool,
+    compare: impl Fn(&T, &T) -> Ordering,
+) -> Ordering {
+    match (a, b) {
+        (Some(a), Some(b)) if descending => compare(&b, &a),
+        (Some(a), Some(b)) => compare(&a, &b),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    }
+}
+
+fn compare_slots(a: &Slot, b: &Slot, descending: bool) -> Ordering {
+    match (a, b) {
+        (Slot::Text(a), Slot::Text(b)) => {
+            missing_last(a.as_ref(), b.as_ref(), descending, Ord::cmp)
+        }
+        (Slot::List(a), Slot::List(b)) => missing_last(a.first(), b.first(), descending, Ord::cmp),
+        (Slot::Number(a), Slot::Number(b)) => missing_last(*a, *b, descending, |a, b| {
+            a.partial_cmp(b).unwrap_or(Ordering::Equal)
+        }),
+        (Slot::Date(a), Slot::Date(b)) => missing_last(*a, *b, descending, Ord::cmp),
+        _ => Ordering::Equal,
+    }
+}
+
+/// `query` with a caret under `column` and the problem next to it, for the command line.
+pub fn explain(query: &str, column: usize, message: &str) -> String {
+    format!(
+        "{}\n{}^ {}",
+        query,
+        " ".repeat(column.saturating_sub(1)),
+        message
+    )
+}
+
+/// The filter line above the Pets list: the query being typed, with the character
+/// a parse error points at in red, or the one that's applied.
+pub fn render_filter_bar<'a>(
+    input: Option<&Input>,
+    applied: Option<&str>,
+    problem: Option<(usize, &str)>,
+    width: u16,
+) -> Paragraph<'a> {
+    let (line, title) = match input {
+        Some(input) => {
+            let scroll = input.visual_scroll(width.saturating_sub(3) as usize);
+            let mut spans = vec![Span::styled("> ", Style::default().fg(Color::Yellow))];
+            let chars: Vec<char> = input.value().chars().collect();
+            // an error at the end points just past the last character
+            let marked = problem.map(|(column, _)| column - 1);
+            for (at, c) in chars.iter().enumerate().skip(scroll) {
+                let style = match Some(at) == marked {
+                    true => Style::default().bg(Color::Red).fg(Color::White),
+                    false => Style::default(),
+                };
+                spans.push(Span::styled(c.to_string(), style));
+            }
+            if marked == Some(chars.len()) {
+                spans.push(Span::styled(" ", Style::default().bg(Color::Red)));
+            }
+            let title = match problem {
+                Some((column, message)) => Span::styled(
+                    format!("column {}: {}", column, message),
+                    Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+                ),
+                None => Span::raw("Filter (Enter applies, empty clears, Esc cancels)"),
+            };
+            (Spans::from(spans), title)
+        }
+        None => {
+            let line = match applied {
+                Some(query) => Spans::from(vec![Span::raw(query.to_string())]),
+                None => Spans::from(vec![Span::styled(
+                    "e.g. category = cats and age >= 3 order by created_at desc limit 10",
+                    Style::default().fg(Color::DarkGray),
+                )]),
+            };
+            (line, Span::raw("Filter (/)"))
+        }
+    };
+
+    Paragraph::new(line).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .style(Style::default().fg(Color::White))
+            .title(title)
+            .border_type(BorderType::Rounded),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn today() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 3, 1).unwrap()
+    }
+
+    fn name_is(name: &str) -> Expr {
+        Expr::Compare {
+            field: Field::Name,
+            op: Op::Eq,
+            value: Literal::Text(name.to_string()),
+        }
+    }
+
+    fn filter(input: &str) -> Expr {
+        Query::parse(input).unwrap().filter.unwrap()
+    }
+
+    // the column and message of the error `input` fails with
+    fn problem(input: &str) -> (usize, String) {
+        match Query::parse(input) {
+            Err(Error::QueryError { column, message }) => (column, message),
+            Err(e) => panic!("{} failed with a non-query error: {}", input, e),
+            Ok(query) => panic!("{} parsed as {:?}", input, query),
+        }
+    }
+
+    fn pet(id: usize, name: &str, weight: Option<f64>) -> Pet {
+        let mut pet = Pet::new(id, name, "cats", today(), false);
+        pet.weight = weight;
+        pet
+    }
+
+    fn ids(pets: Vec<Pet>) -> Vec<usize> {
+        pets.into_iter().map(|pet| pet.id).collect()
+    }
+
+    #[test]
+    fn precedence() {
+        // and binds tighter than or, not tighter than and
+        assert_eq!(
+            filter("name = a or name = b and not name = c"),
+            Expr::Or(
+                Box::new(name_is("a")),
+                Box::new(Expr::And(
+                    Box::new(name_is("b")),
+                    Box::new(Expr::Not(Box::new(name_is("c"))))
+                ))
+            )
+        );
+        assert_eq!(
+            filter("(name = a or name = b) and name = c"),
+            Expr::And(
+                Box::new(Expr::Or(Box::new(name_is("a")), Box::new(name_is("b")))),
+                Box::new(name_is("c"))
+            )
+        );
+        assert_eq!(
+            filter("not (name = a and name = b)"),
+            Expr::Not(Box::new(Expr::And(
+                Box::new(name_is("a")),
+                Box::new(name_is("b"))
+            )))
+        );
+        // left to right within the same level
+        assert_eq!(
+            filter("name = a or name = b or name = c"),
+            Expr::Or(
+                Box::new(Expr::Or(Box::new(name_is("a")), Box::new(name_is("b")))),
+                Box::new(name_is("c"))
+            )
+        );
+    }
+
+    #[test]
+    fn words_and_strings() {
+        assert_eq!(filter("name = O'Brien"), name_is("o'brien"));
+        assert_eq!(filter("name = \"Mr Whiskers\""), name_is("mr whiskers"));
+        assert_eq!(filter("name = 'Mr Whiskers'"), name_is("mr whiskers"));
+        assert_eq!(filter("name = \"O'Brien\""), name_is("o'brien"));
+    }
+
+    #[test]
+    fn errors_point_at_their_column() {
+        let cases = [
+            ("name = \"Tom", 8, "this string is never closed"),
+            ("name = 'Tom", 8, "this string is never closed"),
+            ("nmae = Tom", 1, "unknown field 'nmae'"),
+            (
+                "name =",
+                7,
+                "expected a value to compare name with, found the end",
+            ),
+            ("name = (", 8, "expected a value"),
+            (
+                "name Tom",
+                6,
+                "expected =, !=, <, <=, >, >= or ~ after name",
+            ),
+            (
+                "birthdate < 2024-13-01",
+                13,
+                "birthdate needs a YYYY-MM-DD date",
+            ),
+            ("age = old", 7, "age is a number, 'old' isn't"),
+            ("age ~ 3", 5, "~ only works on text"),
+            ("tags < a", 6, "tags only supports =, != and ~"),
+            ("limit", 6, "limit needs a whole number"),
+            ("limit ten", 7, "limit needs a whole number"),
+            ("limit -1", 7, "limit needs a whole number"),
+            ("order name", 7, "expected 'by' after 'order'"),
+            ("order by", 9, "expected a field, found the end"),
+            (
+                "name = a name = b",
+                10,
+                "expected 'and', 'or', 'order by' or 'limit', found 'name'",
+            ),
+            (
+                "name = a limit 3 x",
+                18,
+                "expected 'and', 'or', 'order by' or 'limit'",
+            ),
+            ("(name = a", 1, "this '(' is never closed"),
+            ("name = a;", 9, "unexpected ';'"),
+        ];
+        for (input, column, message) in cases.iter() {
+            let (found_column, found) = problem(input);
+            assert_eq!(found_column, *column, "{}: {}", input, found);
+            assert!(found.starts_with(message), "{}: {}", input, found);
+        }
+    }
+
+    #[test]
+    fn ordering_puts_missing_values_last_and_keeps_ties() {
+        let pets = vec![
+            pet(1, "b", None),
+            pet(2, "a", Some(4.0)),
+            pet(3, "c", Some(2.0)),
+            pet(4, "a", None),
+            pet(5, "d", Some(4.0)),
+        ];
+        let run = |query: &str| ids(Query::parse(query).unwrap().run(pets.clone(), today()));
+
+        assert_eq!(run("order by weight"), vec![3, 2, 5, 1, 4]);
+        assert_eq!(run("order by weight desc"), vec![2, 5, 3, 1, 4]);
+        assert_eq!(run("order by weight desc, name desc"), vec![5, 2, 3, 1, 4]);
+        assert_eq!(run("order by name"), vec![2, 4, 1, 3, 5]);
+        assert_eq!(run("weight > 3 or name = b"), vec![1, 2, 5]);
+    }
+
+    #[test]
+    fn limit_applies_after_ordering() {
+        let pets: Vec<_> = (1..=5)
+            .map(|id| pet(id, &format!("pet {}", id), Some(id as f64)))
+            .collect();
+        let run = |query: &str| ids(Query::parse(query).unwrap().run(pets.clone(), today()));
+
+        assert_eq!(run("limit 2"), vec![1, 2]);
+        assert_eq!(run("order by weight desc limit 2"), vec![5, 4]);
+        assert_eq!(run("weight >= 2 limit 10"), vec![2, 3, 4, 5]);
+        assert_eq!(run("limit 0"), Vec::<usize>::new());
+    }
+}
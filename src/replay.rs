@@ -0,0 +1,222 @@
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::mpsc::{Receiver, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+use twitchchat::messages::Commands;
+
+use crate::chat::{self, ChatSource};
+use crate::{Error, Event};
+
+/// One line of a recording, stored as a JSON object per line.
+#[derive(Serialize, Deserialize)]
+pub struct RecordedLine {
+    /// milliseconds since the recording started
+    pub at_ms: u64,
+    /// the raw IRC line, without the `\r\n`
+    pub line: String,
+}
+
+/// Writes every line the main loop receives to a file, for `--replay` later.
+pub struct Recorder {
+    file: File,
+    started: Instant,
+}
+
+impl Recorder {
+    pub fn create(path: &Path) -> Result<Recorder, Error> {
+        Ok(Recorder {
+            file: File::create(path)?,
+            started: Instant::now(),
+        })
+    }
+
+    pub fn record(&mut self, line: &str) -> Result<(), Error> {
+        let recorded = RecordedLine {
+            at_ms: self.started.elapsed().as_millis() as u64,
+            line: line.to_string(),
+        };
+        let mut json = serde_json::to_vec(&recorded)?;
+        json.push(b'\n');
+        self.file.write_all(&json)?;
+        Ok(())
+    }
+}
+
+/// Plays a recording back with the original gaps between lines, divided by `speed`.
+pub struct Replay {
+    lines: Vec<RecordedLine>,
+    /// 2.0 plays twice as fast, 0 sends everything at once
+    speed: f64,
+}
+
+impl Replay {
+    pub fn open(path: &Path, speed: f64) -> Result<Replay, Error> {
+        let lines = read_recording(&fs::read_to_string(path)?)?;
+        Ok(Replay { lines, speed })
+    }
+
+    /// Channels with messages in the recording, in the order they first show up.
+    pub fn channels(&self) -> Vec<String> {
+        let mut channels: Vec<String> = vec![];
+        for recorded in &self.lines {
+            let channel = match chat::parse_line(&recorded.line) {
+                Some(Commands::Privmsg(msg)) => msg.channel().to_string(),
+                Some(Commands::Join(join)) => join.channel().to_string(),
+                Some(Commands::UserNotice(notice)) => notice.channel().to_string(),
+                _ => continue,
+            };
+            let channel = channel.trim_start_matches('#');
+            if !channels.iter().any(|known| known == channel) {
+                channels.push(channel.to_string());
+            }
+        }
+        channels
+    }
+}
+
+impl ChatSource for Replay {
+    fn run(
+        &self,
+        tx: &Sender<Event<crossterm::event::KeyEvent>>,
+        outgoing: Receiver<String>,
+    ) -> io::Result<()> {
+        // nothing to send replies to
+        drop(outgoing);
+        let started = Instant::now();
+        for recorded in &self.lines {
+            if self.speed > 0.0 {
+                let due = Duration::from_millis(recorded.at_ms).div_f64(self.speed);
+                if let Some(wait) = due.checked_sub(started.elapsed()) {
+                    thread::sleep(wait);
+                }
+            }
+            if tx.send(Event::Chat(recorded.line.clone())).is_err() {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Parses a recording, skipping blank lines.
+pub fn read_recording(content: &str) -> Result<Vec<RecordedLine>, Error> {
+    content
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| Ok(serde_json::from_str(line)?))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::prelude::*;
+    use std::sync::mpsc;
+    use tui::{backend::TestBackend, buffer::Buffer, style::Modifier, Terminal};
+
+    use crate::chat::Channel;
+    use crate::config::ChatConfig;
+    use crate::filter::Filter;
+    use crate::highlight::Highlighter;
+
+    const FIXTURE: &str = r#"{"at_ms": 0, "line": ":justinfan12345!justinfan12345@justinfan12345.tmi.twitch.tv JOIN #petstore"}
+{"at_ms": 800, "line": "@badges=broadcaster/1;color=#FF4500;display-name=PetStore;emotes=;id=r-1;tmi-sent-ts=1700000000800 :petstore!petstore@petstore.tmi.twitch.tv PRIVMSG #petstore :welcome!"}
+
+{"at_ms": 1900, "line": "@badges=moderator/1;color=#1E90FF;display-name=Alice;emotes=25:3-7;id=r-2;tmi-sent-ts=1700000001900 :alice!alice@alice.tmi.twitch.tv PRIVMSG #petstore :hi Kappa"}
+{"at_ms": 3100, "line": "@badges=;color=;display-name=Bob;emotes=;id=r-3;tmi-sent-ts=1700000003100 :bob!bob@bob.tmi.twitch.tv PRIVMSG #petstore :\u0001ACTION waves\u0001"}
+{"at_ms": 4000, "line": "@target-msg-id=r-3;tmi-sent-ts=1700000004000 :tmi.twitch.tv CLEARMSG #petstore :waves"}
+"#;
+
+    // the chat view once the fixture has played
+    fn replayed(width: u16, height: u16) -> Buffer {
+        let replay = Replay {
+            lines: read_recording(FIXTURE).unwrap(),
+            speed: 0.0,
+        };
+        assert_eq!(replay.channels(), ["petstore"]);
+        let (tx, rx) = mpsc::channel();
+        let (_outgoing_tx, outgoing) = mpsc::channel();
+        replay.run(&tx, outgoing).unwrap();
+        drop(tx);
+
+        let config = ChatConfig::default();
+        let highlighter = Highlighter::new(&config).unwrap();
+        let filter = Filter::new(&config.filters).unwrap();
+        let mut channels = vec![Channel::new("petstore")];
+        for event in rx {
+            if let Event::Chat(line) = event {
+                chat::handle_line(&mut channels, &highlighter, &filter, &line);
+            }
+        }
+
+        let mut terminal = Terminal::new(TestBackend::new(width, height)).unwrap();
+        terminal
+            .draw(|rect| {
+                let list = crate::render_chans_contents(
+                    &channels[0],
+                    height as usize - 2,
+                    config.hide_deleted,
+                );
+                rect.render_widget(list, rect.size());
+            })
+            .unwrap();
+        terminal.backend().buffer().clone()
+    }
+
+    fn rows(buffer: &Buffer) -> Vec<String> {
+        (0..buffer.area.height)
+            .map(|y| {
+                (0..buffer.area.width)
+                    .map(|x| buffer.get(x, y).symbol.as_str())
+                    .collect::<String>()
+            })
+            .collect()
+    }
+
+    fn time(millis: i64) -> String {
+        Utc.timestamp_millis_opt(millis)
+            .unwrap()
+            .with_timezone(&Local)
+            .format("%H:%M")
+            .to_string()
+    }
+
+    #[test]
+    fn chat_view_snapshot() {
+        let buffer = replayed(40, 6);
+        let row = |text: String| format!("│{:<38}│", text);
+        assert_eq!(
+            rows(&buffer),
+            [
+                format!("┌#petstore{}┐", "─".repeat(29)),
+                row(format!("{} ◉ PetStore: welcome!", time(1_700_000_000_800))),
+                row(format!("{} ⚔ Alice: hi [Kappa]", time(1_700_000_001_900))),
+                row(format!("{} Bob waves", time(1_700_000_003_100))),
+                row(String::new()),
+                format!("└{}┘", "─".repeat(38)),
+            ]
+        );
+        // the CLEARMSG strikes Bob's message out rather than dropping it
+        let waves = rows(&buffer)[3].chars().position(|c| c == 'w').unwrap() as u16;
+        assert!(buffer
+            .get(waves, 3)
+            .style()
+            .add_modifier
+            .contains(Modifier::CROSSED_OUT));
+        assert!(!buffer
+            .get(waves, 2)
+            .style()
+            .add_modifier
+            .contains(Modifier::CROSSED_OUT));
+    }
+
+    #[test]
+    fn a_short_view_follows_the_newest_messages() {
+        let rows = rows(&replayed(40, 4));
+        assert!(rows[1].contains("Alice: hi [Kappa]"), "{:?}", rows);
+        assert!(rows[2].contains("Bob waves"), "{:?}", rows);
+    }
+}
@@ -0,0 +1,279 @@
+use chrono::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::convert::TryFrom;
+
+use crate::age;
+use crate::categories::{self, Category};
+use crate::{Error, Pet};
+
+/// The `db.json` layout this build writes. Older files are upgraded by `MIGRATIONS`.
+pub const SCHEMA_VERSION: u64 = 4;
+
+type Migration = fn(Value) -> Result<Value, Error>;
+
+/// `MIGRATIONS[n]` turns a version `n` file into a version `n + 1` one.
+const MIGRATIONS: &[Migration] = &[v0_to_v1, v1_to_v2, v2_to_v3, v3_to_v4];
+
+/// Everything in `db.json`.
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct Db {
+    pub pets: Vec<Pet>,
+    pub categories: Vec<Category>,
+    /// deleted pets, until they're restored or purged
+    pub trash: Vec<Pet>,
+}
+
+/// The contents of a `db.json` of any version, and the version the file was at.
+pub fn load(content: &str) -> Result<(Db, u64), Error> {
+    let mut db: Value = serde_json::from_str(content)?;
+    let found = version_of(&db)?;
+    if found > SCHEMA_VERSION {
+        return Err(Error::SchemaError(format!(
+            "db.json is version {}, this build only reads up to {}",
+            found, SCHEMA_VERSION
+        )));
+    }
+    for migrate in &MIGRATIONS[found as usize..] {
+        db = migrate(db)?;
+    }
+    Ok((serde_json::from_value(db)?, found))
+}
+
+/// `db` as the current version of `db.json`.
+pub fn save(db: &Db) -> Result<Vec<u8>, Error> {
+    Ok(serde_json::to_vec(&json!({
+        "version": SCHEMA_VERSION,
+        "pets": db.pets,
+        "categories": db.categories,
+        "trash": db.trash,
+    }))?)
+}
+
+fn version_of(db: &Value) -> Result<u64, Error> {
+    match db {
+        // before versioning the file was just the list of pets
+        Value::Array(_) => Ok(0),
+        Value::Object(fields) => fields
+            .get("version")
+            .and_then(Value::as_u64)
+            .ok_or_else(|| Error::SchemaError(String::from("db.json has no version"))),
+        _ => Err(Error::SchemaError(String::from(
+            "db.json is neither a list of pets nor a versioned file",
+        ))),
+    }
+}
+
+/// Wraps the bare list in a versioned file; pets start out as last updated when they were added.
+fn v0_to_v1(db: Value) -> Result<Value, Error> {
+    let mut pets = match db {
+        Value::Array(pets) => pets,
+        _ => return Err(Error::SchemaError(String::from("version 0 is a list"))),
+    };
+    for pet in &mut pets {
+        if let Value::Object(fields) = pet {
+            if let Some(created_at) = fields.get("created_at").cloned() {
+                fields.entry("updated_at").or_insert(created_at);
+            }
+        }
+    }
+    Ok(json!({ "version": 1, "pets": pets }))
+}
+
+/// Replaces the static `age` with a birthdate estimated from it, counting from
+/// when the pet was added since that's when the age was right.
+fn v1_to_v2(mut db: Value) -> Result<Value, Error> {
+    let pets = db
+        .get_mut("pets")
+        .and_then(Value::as_array_mut)
+        .ok_or_else(|| Error::SchemaError(String::from("version 1 has a list of pets")))?;
+    for pet in pets {
+        let fields = match pet.as_object_mut() {
+            Some(fields) => fields,
+            None => continue,
+        };
+        let age = fields
+            .remove("age")
+            .and_then(|age| age.as_u64())
+            .unwrap_or(0);
+        let added = fields
+            .get("created_at")
+            .and_then(Value::as_str)
+            .and_then(|at| DateTime::parse_from_rfc3339(at).ok())
+            .ok_or_else(|| Error::SchemaError(String::from("a pet has no valid created_at")))?;
+        let added = added.naive_utc().date();
+        // an age too large for a date is as good as unknown
+        let birthdate = u32::try_from(age)
+            .ok()
+            .and_then(|age| age::estimated_birthdate(age, added))
+            .unwrap_or(added);
+        fields.insert(String::from("birthdate"), json!(birthdate));
+        fields.insert(String::from("birthdate_estimated"), json!(true));
+    }
+    db["version"] = json!(2);
+    Ok(db)
+}
+
+/// Registers every category the pets use, named after the free text they had,
+/// and points the pets at the new keys.
+fn v2_to_v3(mut db: Value) -> Result<Value, Error> {
+    let pets = db
+        .get_mut("pets")
+        .and_then(Value::as_array_mut)
+        .ok_or_else(|| Error::SchemaError(String::from("version 2 has a list of pets")))?;
+    let mut names = vec![];
+    for pet in pets {
+        if let Some(name) = pet.get("category").and_then(Value::as_str) {
+            let name = name.to_string();
+            pet["category"] = json!(categories::key_for(&name));
+            names.push(name);
+        }
+    }
+    names.sort_unstable();
+
+    let mut registered: Vec<Category> = vec![];
+    for name in names {
+        let mut category = Category::new(&name, registered.len());
+        if registered.iter().any(|c| c.key == category.key) {
+            continue;
+        }
+        // "cats" reads better as "Cats" now that it's a display name
+        let mut chars = category.name.chars();
+        if let Some(first) = chars.next() {
+            category.name = first.to_uppercase().chain(chars).collect();
+        }
+        registered.push(category);
+    }
+    db["categories"] = json!(registered);
+    db["version"] = json!(3);
+    Ok(db)
+}
+
+/// Adds the trash that deleted pets now go to.
+fn v3_to_v4(mut db: Value) -> Result<Value, Error> {
+    db["trash"] = json!([]);
+    db["version"] = json!(4);
+    Ok(db)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    fn created(text: &str) -> DateTime<Utc> {
+        text.parse().unwrap()
+    }
+
+    #[test]
+    fn v0_bare_list() {
+        let (db, version) = load(
+            r#"[{"id":1,"name":"Chip","category":"cats","age":4,"created_at":"2020-09-01T12:00:00Z"},
+                {"id":3,"name":"Taco","category":"Big Dogs","age":0,"created_at":"2019-03-16T12:00:00Z"}]"#,
+        )
+        .unwrap();
+        assert_eq!(version, 0);
+        assert_eq!(db.pets.len(), 2);
+        let chip = &db.pets[0];
+        assert_eq!(chip.id, 1);
+        assert_eq!(chip.name, "Chip");
+        // four years before it was added, not before today
+        assert_eq!(chip.birthdate, date(2016, 9, 1));
+        assert!(chip.birthdate_estimated);
+        assert_eq!(chip.updated_at, Some(created("2020-09-01T12:00:00Z")));
+        assert_eq!(db.pets[1].birthdate, date(2019, 3, 16));
+        assert_eq!(chip.category, "cats");
+        assert_eq!(db.pets[1].category, "big-dogs");
+        let names: Vec<_> = db.categories.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, ["Big Dogs", "Cats"]);
+        assert!(db.trash.is_empty());
+    }
+
+    #[test]
+    fn v1_keeps_updated_at() {
+        let (db, version) = load(
+            r#"{"version":1,"pets":[{"id":2,"name":"Nacho","category":"cats","age":6,
+                "created_at":"2020-08-20T12:00:00Z","updated_at":"2021-01-02T00:00:00Z"}]}"#,
+        )
+        .unwrap();
+        assert_eq!(version, 1);
+        let nacho = &db.pets[0];
+        assert_eq!(nacho.birthdate, date(2014, 8, 20));
+        assert!(nacho.birthdate_estimated);
+        assert_eq!(nacho.updated_at, Some(created("2021-01-02T00:00:00Z")));
+        assert_eq!(db.categories.len(), 1);
+        assert_eq!(db.categories[0].key, "cats");
+    }
+
+    #[test]
+    fn v2_registers_categories() {
+        let (db, version) = load(
+            r#"{"version":2,"pets":[
+                {"id":4,"name":"Pepper","category":"cats","birthdate":"2017-05-22","birthdate_estimated":false,"created_at":"2019-05-22T12:00:00Z"},
+                {"id":5,"name":"Rex","category":"Dogs","birthdate":"2018-01-01","created_at":"2019-05-22T12:00:00Z"},
+                {"id":6,"name":"Minka","category":"Cats","birthdate":"2016-11-23","created_at":"2020-11-23T12:00:00Z"}]}"#,
+        )
+        .unwrap();
+        assert_eq!(version, 2);
+        let pepper = &db.pets[0];
+        assert_eq!(pepper.birthdate, date(2017, 5, 22));
+        assert!(!pepper.birthdate_estimated);
+        let keys: Vec<_> = db.pets.iter().map(|p| p.category.as_str()).collect();
+        assert_eq!(keys, ["cats", "dogs", "cats"]);
+        // "Cats" and "cats" are one category
+        let names: Vec<_> = db.categories.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, ["Cats", "Dogs"]);
+        assert!(db.trash.is_empty());
+    }
+
+    #[test]
+    fn v3_gets_a_trash() {
+        let (db, version) = load(
+            r##"{"version":3,"pets":[{"id":7,"name":"Bo","category":"birds","birthdate":"2020-02-02","created_at":"2021-01-01T00:00:00Z","tags":["loud"]}],
+                "categories":[{"key":"birds","name":"Birds","color":"#00ff00","icon":"▲"}]}"##,
+        )
+        .unwrap();
+        assert_eq!(version, 3);
+        assert_eq!(db.pets[0].tags, ["loud"]);
+        assert_eq!(db.categories[0].color, "#00ff00");
+        assert!(db.trash.is_empty());
+    }
+
+    #[test]
+    fn v4_is_read_as_is() {
+        let (db, version) = load(
+            r##"{"version":4,"pets":[],
+                "categories":[{"key":"birds","name":"Birds","color":"#00ff00","icon":"▲"}],
+                "trash":[{"id":8,"name":"Kiwi","category":"birds","birthdate":"2020-02-02","created_at":"2021-01-01T00:00:00Z","deleted_at":"2022-03-03T00:00:00Z"}]}"##,
+        )
+        .unwrap();
+        assert_eq!(version, 4);
+        assert!(db.pets.is_empty());
+        assert_eq!(db.trash[0].name, "Kiwi");
+        assert_eq!(
+            db.trash[0].deleted_at,
+            Some(created("2022-03-03T00:00:00Z"))
+        );
+    }
+
+    #[test]
+    fn save_then_load_is_current() {
+        let (db, _) = load(r#"[{"id":1,"name":"Chip","category":"cats","age":4,"created_at":"2020-09-01T12:00:00Z"}]"#).unwrap();
+        let saved = String::from_utf8(save(&db).unwrap()).unwrap();
+        let (again, version) = load(&saved).unwrap();
+        assert_eq!(version, SCHEMA_VERSION);
+        assert_eq!(again.pets[0].birthdate, db.pets[0].birthdate);
+    }
+
+    #[test]
+    fn newer_versions_are_refused() {
+        assert!(matches!(
+            load(r#"{"version":99,"pets":[]}"#),
+            Err(Error::SchemaError(_))
+        ));
+        assert!(matches!(load(r#"{"pets":[]}"#), Err(Error::SchemaError(_))));
+    }
+}
@@ -0,0 +1,414 @@
+use chrono::prelude::*;
+use std::collections::HashSet;
+use std::path::PathBuf;
+use tui::{
+    style::{Color, Modifier, Style},
+    text::{Span, Spans},
+    widgets::{Block, BorderType, Borders, List, ListItem, ListState, Paragraph},
+};
+use tui_input::Input;
+
+use crate::chat::{self, Channel, ChatMessage, MessageKind};
+use crate::logs::{self, LogIndex};
+use crate::Error;
+
+const MAX_HITS: usize = 200;
+/// messages shown either side of a hit
+const CONTEXT_RADIUS: usize = 5;
+
+/// `words from:<user> since:<YYYY-MM-DD> until:<YYYY-MM-DD>`, every part optional.
+/// A message matches when each word starts one of its words, in any order, the same
+/// rule for the scrollback as for the log index.
+#[derive(Debug, Default, PartialEq)]
+pub struct SearchQuery {
+    /// lowercased, split the way `logs::words` splits messages
+    pub terms: Vec<String>,
+    pub author: Option<String>,
+    pub since: Option<NaiveDate>,
+    pub until: Option<NaiveDate>,
+}
+
+impl SearchQuery {
+    pub fn parse(line: &str) -> Result<SearchQuery, Error> {
+        let mut query = SearchQuery::default();
+        let mut text = vec![];
+        let date = |value: &str| {
+            NaiveDate::parse_from_str(value, "%Y-%m-%d")
+                .map_err(|_| Error::CommandError(format!("'{}' isn't a YYYY-MM-DD date", value)))
+        };
+        for word in line.split_whitespace() {
+            if let Some(user) = word.strip_prefix("from:") {
+                query.author = Some(user.trim_start_matches('@').to_lowercase());
+            } else if let Some(value) = word.strip_prefix("since:") {
+                query.since = Some(date(value)?);
+            } else if let Some(value) = word.strip_prefix("until:") {
+                query.until = Some(date(value)?);
+            } else {
+                text.push(word);
+            }
+        }
+        query.terms = logs::words(&text.join(" "));
+        if query.terms.is_empty() && query.author.is_none() {
+            return Err(Error::CommandError(String::from(
+                "search for some text or from:<user>",
+            )));
+        }
+        Ok(query)
+    }
+
+    fn in_range(&self, date: NaiveDate) -> bool {
+        self.since.is_none_or(|since| date >= since) && self.until.is_none_or(|until| date <= until)
+    }
+
+    pub fn matches(&self, msg: &ChatMessage) -> bool {
+        msg.kind == MessageKind::Chat
+            && self.in_range(msg.sent_at.naive_utc().date())
+            && self.author.as_ref().is_none_or(|author| {
+                msg.login == *author || msg.display_name.eq_ignore_ascii_case(author)
+            })
+            && {
+                let words = logs::words(&msg.text);
+                self.terms
+                    .iter()
+                    .all(|term| words.iter().any(|word| word.starts_with(term.as_str())))
+            }
+    }
+}
+
+pub enum HitLocation {
+    /// still in the scrollback, looked up again by `SearchHit::find_in` since the buffer moves on
+    Scrollback,
+    Log {
+        path: PathBuf,
+        offset: u64,
+    },
+}
+
+pub struct SearchHit {
+    pub msg: ChatMessage,
+    pub location: HitLocation,
+}
+
+impl SearchHit {
+    /// The channel and index of the hit in the scrollback, unless it has scrolled out of it.
+    pub fn find_in(&self, channels: &[Channel]) -> Option<(usize, usize)> {
+        let channel = channels
+            .iter()
+            .position(|channel| channel.name == self.msg.channel)?;
+        let index =
+            channels[channel]
+                .messages
+                .iter()
+                .position(|msg| match (&msg.id, &self.msg.id) {
+                    (Some(id), Some(hit)) => id == hit,
+                    _ => {
+                        msg.sent_at == self.msg.sent_at
+                            && msg.login == self.msg.login
+                            && msg.text == self.msg.text
+                    }
+                })?;
+        Some((channel, index))
+    }
+}
+
+/// The search popup on the Channels tab: the query line, its hits, and the
+/// context around the hit being looked at.
+pub struct Search {
+    pub input: Input,
+    // the query `hits` are for, Enter opens a hit instead of searching again while it's current
+    searched: Option<String>,
+    pub hits: Vec<SearchHit>,
+    pub list: ListState,
+    /// messages around the opened hit, and which of them it is
+    pub context: Option<(Vec<ChatMessage>, usize)>,
+    pub status: String,
+}
+
+impl Search {
+    pub fn new() -> Search {
+        Search {
+            input: Input::default(),
+            searched: None,
+            hits: vec![],
+            list: ListState::default(),
+            context: None,
+            status: String::from("text, from:<user>, since:/until:<YYYY-MM-DD>"),
+        }
+    }
+
+    pub fn is_current(&self) -> bool {
+        self.searched.as_deref() == Some(self.input.value()) && !self.hits.is_empty()
+    }
+
+    /// Looks through the scrollback first and then the logs, newest hits first.
+    pub fn run(&mut self, channels: &[Channel], index: &mut LogIndex) {
+        self.searched = Some(self.input.value().to_string());
+        self.hits.clear();
+        self.list.select(None);
+        self.status = match self.find(channels, index) {
+            Ok(()) if self.hits.is_empty() => String::from("nothing found"),
+            Ok(()) => {
+                self.list.select(Some(0));
+                match self.hits.len() {
+                    MAX_HITS => format!("first {} hits", MAX_HITS),
+                    n => format!("{} hits", n),
+                }
+            }
+            Err(e) => e.to_string(),
+        };
+    }
+
+    fn find(&mut self, channels: &[Channel], index: &mut LogIndex) -> Result<(), Error> {
+        let query = SearchQuery::parse(self.input.value())?;
+        let mut seen = HashSet::new();
+        for channel in channels {
+            if channel.kind != chat::ChannelKind::Joined {
+                continue;
+            }
+            for msg in &channel.messages {
+                if query.matches(msg) {
+                    seen.extend(msg.id.clone());
+                    self.hits.push(SearchHit {
+                        msg: msg.clone(),
+                        location: HitLocation::Scrollback,
+                    });
+                }
+            }
+        }
+
+        index.update()?;
+        let mut candidates = index.candidates(&query.terms, query.author.as_deref(), |path| {
+            logs::log_date(path).is_some_and(|date| query.in_range(date))
+        });
+        // newest day first, then file by file so each is opened once
+        candidates.sort_by(|(a, a_offset), (b, b_offset)| {
+            logs::log_date(b)
+                .cmp(&logs::log_date(a))
+                .then_with(|| a.cmp(b))
+                .then(a_offset.cmp(b_offset))
+        });
+        let mut log_hits = 0;
+        for day in candidates.chunk_by(|(a, _), (b, _)| logs::log_date(a) == logs::log_date(b)) {
+            // every message of a later day is newer than this one's, so older days can't make the cut
+            if log_hits >= MAX_HITS {
+                break;
+            }
+            for file in day.chunk_by(|(a, _), (b, _)| a == b) {
+                let path = &file[0].0;
+                let offsets: Vec<_> = file.iter().map(|(_, offset)| *offset).collect();
+                for (msg, offset) in logs::read_messages(path, &offsets)?
+                    .into_iter()
+                    .zip(offsets)
+                {
+                    // the scrollback already has today's messages
+                    if msg.id.as_ref().is_some_and(|id| seen.contains(id)) || !query.matches(&msg) {
+                        continue;
+                    }
+                    log_hits += 1;
+                    self.hits.push(SearchHit {
+                        msg,
+                        location: HitLocation::Log {
+                            path: path.clone(),
+                            offset,
+                        },
+                    });
+                }
+            }
+        }
+
+        self.hits
+            .sort_by_key(|hit| std::cmp::Reverse(hit.msg.sent_at));
+        self.hits.truncate(MAX_HITS);
+        Ok(())
+    }
+
+    pub fn selected_hit(&self) -> Option<&SearchHit> {
+        self.hits.get(self.list.selected()?)
+    }
+
+    pub fn select_next(&mut self, forward: bool) {
+        if let Some(selected) = self.list.selected() {
+            self.list.select(Some(match forward {
+                true => (selected + 1).min(self.hits.len().saturating_sub(1)),
+                false => selected.saturating_sub(1),
+            }));
+        }
+    }
+
+    /// Shows the messages around the selected hit.
+    pub fn open_context(&mut self, channels: &[Channel]) {
+        let hit = match self.selected_hit() {
+            Some(hit) => hit,
+            None => return,
+        };
+        let context = match &hit.location {
+            HitLocation::Scrollback => match hit.find_in(channels) {
+                Some((channel, index)) => {
+                    let messages = &channels[channel].messages;
+                    let start = index.saturating_sub(CONTEXT_RADIUS);
+                    let end = (index + CONTEXT_RADIUS + 1).min(messages.len());
+                    Ok((messages.range(start..end).cloned().collect(), index - start))
+                }
+                None => Err(Error::CommandError(String::from(
+                    "the message has left the scrollback since the search",
+                ))),
+            },
+            HitLocation::Log { path, offset } => logs::read_context(path, *offset, CONTEXT_RADIUS),
+        };
+        match context {
+            // a rewritten log can have some other message at the hit's offset
+            Ok((messages, at)) if messages[at].id != hit.msg.id => {
+                self.status = String::from("the log has changed since the search, search again")
+            }
+            Ok(context) => self.context = Some(context),
+            Err(e) => self.status = e.to_string(),
+        }
+    }
+}
+
+pub fn render_search_input<'a>(search: &Search, width: u16) -> Paragraph<'a> {
+    let scroll = search.input.visual_scroll(width.saturating_sub(3) as usize);
+    Paragraph::new(Spans::from(vec![
+        Span::styled("? ", Style::default().fg(Color::Yellow)),
+        Span::raw(
+            search
+                .input
+                .value()
+                .chars()
+                .skip(scroll)
+                .collect::<String>(),
+        ),
+    ]))
+    .block(
+        Block::default()
+            .borders(Borders::ALL)
+            .style(Style::default().fg(Color::White).bg(Color::Black))
+            .title(format!("Search - {}", search.status))
+            .border_type(BorderType::Rounded),
+    )
+}
+
+/// The hits as `#channel date time` followed by the message.
+pub fn render_hits<'a>(search: &Search) -> List<'a> {
+    let items: Vec<_> = search
+        .hits
+        .iter()
+        .map(|hit| {
+            let source = match hit.location {
+                HitLocation::Scrollback => "",
+                HitLocation::Log { .. } => " (log)",
+            };
+            let mut line = chat::render_message(&hit.msg, false);
+            line.0.insert(
+                0,
+                Span::styled(
+                    format!(
+                        "#{} {}{} ",
+                        hit.msg.channel,
+                        hit.msg
+                            .sent_at
+                            .with_timezone(&Local)
+                            .format("%Y-%m-%d %H:%M"),
+                        source
+                    ),
+                    Style::default().fg(Color::Gray),
+                ),
+            );
+            ListItem::new(line)
+        })
+        .collect();
+
+    List::new(items)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .style(Style::default().fg(Color::White).bg(Color::Black))
+                .title("Hits (Enter shows the context)")
+                .border_type(BorderType::Rounded),
+        )
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+}
+
+/// The opened hit between its neighbours, which are tinted so the conversation around it stands out.
+pub fn render_context<'a>(search: &Search) -> List<'a> {
+    let (messages, hit) = match &search.context {
+        Some((messages, hit)) => (messages.as_slice(), *hit),
+        None => (&[][..], 0),
+    };
+    let items: Vec<_> = messages
+        .iter()
+        .enumerate()
+        .map(|(index, msg)| {
+            let style = if index == hit {
+                Style::default().add_modifier(Modifier::REVERSED)
+            } else {
+                Style::default().bg(Color::DarkGray)
+            };
+            ListItem::new(chat::render_message(msg, false)).style(style)
+        })
+        .collect();
+
+    let title = match search.selected_hit().map(|hit| &hit.location) {
+        Some(HitLocation::Scrollback) => "Context (Enter jumps to it, Esc goes back)",
+        _ => "Context from the logs (Esc goes back)",
+    };
+    List::new(items).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .style(Style::default().fg(Color::White).bg(Color::Black))
+            .title(title)
+            .border_type(BorderType::Rounded),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn query(line: &str) -> SearchQuery {
+        SearchQuery::parse(line).unwrap()
+    }
+
+    fn message(login: &str, text: &str) -> ChatMessage {
+        ChatMessage::chat(
+            login,
+            text,
+            Utc.with_ymd_and_hms(2024, 3, 1, 12, 0, 0).unwrap(),
+        )
+    }
+
+    #[test]
+    fn parsing() {
+        assert_eq!(
+            query("Hello, World from:@Ann since:2024-01-01 until:2024-02-01"),
+            SearchQuery {
+                terms: vec![String::from("hello"), String::from("world")],
+                author: Some(String::from("ann")),
+                since: NaiveDate::from_ymd_opt(2024, 1, 1),
+                until: NaiveDate::from_ymd_opt(2024, 2, 1),
+            }
+        );
+        assert_eq!(query("from:bob").terms, Vec::<String>::new());
+        assert!(SearchQuery::parse("since:yesterday hello").is_err());
+        assert!(SearchQuery::parse("since:2024-01-01").is_err());
+        assert!(SearchQuery::parse("  ?! ").is_err());
+    }
+
+    #[test]
+    fn matching_follows_the_log_index() {
+        let hello = message("ann", "well, Hello there");
+        assert!(query("hel").matches(&hello));
+        assert!(query("there hello").matches(&hello));
+        // not the middle of a word, the index couldn't find that
+        assert!(!query("ello").matches(&hello));
+        assert!(!query("hello friend").matches(&hello));
+        assert!(query("hello from:ann").matches(&hello));
+        assert!(!query("hello from:bob").matches(&hello));
+        assert!(query("hello since:2024-03-01 until:2024-03-01").matches(&hello));
+        assert!(!query("hello since:2024-03-02").matches(&hello));
+
+        let mut notice = message("ann", "hello");
+        notice.kind = MessageKind::Notice;
+        assert!(!query("hello").matches(&notice));
+    }
+}
@@ -0,0 +1,37 @@
+use std::fs;
+use std::time::SystemTime;
+
+use crate::schema::Db;
+use crate::{Error, DB_PATH};
+
+/// `db.json` as it was last read, so tabs drawn every frame only go to disk when the
+/// file changes.
+#[derive(Default)]
+pub struct Store {
+    // `None` until the first read
+    modified: Option<Option<SystemTime>>,
+    pub db: Db,
+}
+
+impl Store {
+    /// A store starting out with `db`, as if it had been read.
+    pub fn new(db: Db) -> Store {
+        Store {
+            modified: Some(None),
+            db,
+        }
+    }
+
+    /// Reads the file again if it changed since the last look, returning whether it did.
+    /// A file that can't be read keeps the previous contents and isn't tried again
+    /// until it changes.
+    pub fn refresh(&mut self) -> Result<bool, Error> {
+        let modified = fs::metadata(DB_PATH).and_then(|meta| meta.modified()).ok();
+        if self.modified == Some(modified) {
+            return Ok(false);
+        }
+        self.modified = Some(modified);
+        self.db = crate::read_store()?;
+        Ok(true)
+    }
+}
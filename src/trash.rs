@@ -0,0 +1,202 @@
+use chrono::prelude::*;
+use std::convert::TryFrom;
+use tui::{
+    style::{Color, Modifier, Style},
+    text::{Span, Spans},
+    widgets::{Block, BorderType, Borders, List, ListItem, Paragraph},
+};
+
+use crate::categories;
+use crate::schema::Db;
+use crate::{Error, Pet};
+
+/// Moves the pet with `id` to the trash, stamped with `now`.
+pub fn trash(db: &mut Db, id: usize, now: DateTime<Utc>) -> Result<Pet, Error> {
+    let at = db
+        .pets
+        .iter()
+        .position(|pet| pet.id == id)
+        .ok_or_else(|| Error::CommandError(format!("no pet {}", id)))?;
+    let mut pet = db.pets.remove(at);
+    pet.deleted_at = Some(now);
+    db.trash.push(pet.clone());
+    Ok(pet)
+}
+
+/// Takes the pet with `id` out of the trash and back into the store.
+pub fn restore(db: &mut Db, id: usize, now: DateTime<Utc>) -> Result<Pet, Error> {
+    let at = db
+        .trash
+        .iter()
+        .position(|pet| pet.id == id)
+        .ok_or_else(|| Error::CommandError(format!("pet {} isn't in the trash", id)))?;
+    if db.pets.iter().any(|pet| pet.id == id) {
+        return Err(Error::CommandError(format!(
+            "another pet in the store already has id {}",
+            id
+        )));
+    }
+    if categories::find(&db.categories, &db.trash[at].category).is_none() {
+        return Err(Error::CommandError(format!(
+            "{} was a {}, add that category again first",
+            db.trash[at].name, db.trash[at].category
+        )));
+    }
+    let mut pet = db.trash.remove(at);
+    pet.deleted_at = None;
+    pet.updated_at = Some(now);
+    db.pets.push(pet.clone());
+    Ok(pet)
+}
+
+/// Drops the pet with `id` from the trash for good.
+pub fn purge(db: &mut Db, id: usize) -> Result<Pet, Error> {
+    let at = db
+        .trash
+        .iter()
+        .position(|pet| pet.id == id)
+        .ok_or_else(|| Error::CommandError(format!("pet {} isn't in the trash", id)))?;
+    Ok(db.trash.remove(at))
+}
+
+/// When a trashed pet goes for good, `None` if `retention_days` is 0 or so large
+/// there's no such date (never).
+pub fn purge_date(pet: &Pet, retention_days: u64) -> Option<DateTime<Utc>> {
+    match retention_days {
+        0 => None,
+        days => {
+            let kept = chrono::Duration::try_days(i64::try_from(days).ok()?)?;
+            pet.deleted_at?.checked_add_signed(kept)
+        }
+    }
+}
+
+/// Purges everything that's been in the trash longer than `retention_days`.
+pub fn purge_expired(db: &mut Db, now: DateTime<Utc>, retention_days: u64) -> Vec<Pet> {
+    let (expired, kept) = db
+        .trash
+        .drain(..)
+        .partition(|pet| purge_date(pet, retention_days).is_some_and(|date| date <= now));
+    db.trash = kept;
+    expired
+}
+
+/// The trash as the Trash tab lists it.
+pub fn sorted(db: &Db) -> Vec<Pet> {
+    let mut trash = db.trash.clone();
+    trash.sort_by_key(|pet| std::cmp::Reverse(pet.deleted_at));
+    trash
+}
+
+/// The trashed pets, most recently deleted first, with when each will be purged.
+pub fn render_trash<'a>(trash: &[Pet], now: DateTime<Utc>, retention_days: u64) -> List<'a> {
+    let items: Vec<_> = trash
+        .iter()
+        .map(|pet| {
+            let deleted = pet
+                .deleted_at
+                .map(|at| {
+                    at.with_timezone(&Local)
+                        .format("%Y-%m-%d %H:%M")
+                        .to_string()
+                })
+                .unwrap_or_default();
+            let purge = match purge_date(pet, retention_days) {
+                Some(date) => format!("purged in {} days", (date - now).num_days().max(0)),
+                None => String::from("kept until purged"),
+            };
+            ListItem::new(Spans::from(vec![
+                Span::styled(
+                    pet.name.clone(),
+                    Style::default().add_modifier(Modifier::BOLD),
+                ),
+                Span::raw(format!("  {}", pet.category)),
+                Span::styled(
+                    format!("  deleted {} · {}", deleted, purge),
+                    Style::default().fg(Color::DarkGray),
+                ),
+            ]))
+        })
+        .collect();
+
+    List::new(items)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .style(Style::default().fg(Color::White))
+                .title(format!("Trash ({})", trash.len()))
+                .border_type(BorderType::Plain),
+        )
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+}
+
+/// The key help, or the result of the last action.
+pub fn render_help<'a>(status: &str) -> Paragraph<'a> {
+    let text = if status.is_empty() {
+        "r restore, p purge for good"
+    } else {
+        status
+    };
+    Paragraph::new(Spans::from(vec![Span::raw(text.to_string())])).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .style(Style::default().fg(Color::White))
+            .border_type(BorderType::Plain),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::categories::Category;
+
+    fn pet(id: usize, name: &str) -> Pet {
+        Pet::new(
+            id,
+            name,
+            "dogs",
+            NaiveDate::from_ymd_opt(2020, 1, 1).unwrap(),
+            false,
+        )
+    }
+
+    #[test]
+    fn huge_retentions_never_purge() {
+        let now = Utc::now();
+        let mut db = Db {
+            categories: vec![Category::new("Dogs", 0)],
+            pets: vec![pet(1, "Rex")],
+            ..Db::default()
+        };
+        trash(&mut db, 1, now - chrono::Duration::days(40)).unwrap();
+        for days in [u64::MAX, i64::MAX as u64, 1 << 40, 200_000_000] {
+            assert_eq!(purge_date(&db.trash[0], days), None, "{} days", days);
+            assert!(purge_expired(&mut db, now, days).is_empty());
+        }
+        assert_eq!(
+            purge_date(&db.trash[0], 30),
+            Some(now - chrono::Duration::days(10))
+        );
+        assert_eq!(purge_expired(&mut db, now, 30).len(), 1);
+    }
+
+    #[test]
+    fn restore_keeps_ids_unique() {
+        let now = Utc::now();
+        let mut db = Db {
+            categories: vec![Category::new("Dogs", 0)],
+            pets: vec![pet(1, "Rex"), pet(2, "Bo")],
+            ..Db::default()
+        };
+        trash(&mut db, 1, now).unwrap();
+        db.pets.push(pet(1, "Ada"));
+        assert!(restore(&mut db, 1, now).is_err());
+        assert_eq!(db.trash.len(), 1);
+
+        db.pets.retain(|pet| pet.name != "Ada");
+        let restored = restore(&mut db, 1, now).unwrap();
+        assert_eq!(restored.name, "Rex");
+        assert_eq!(restored.deleted_at, None);
+        assert!(db.trash.is_empty());
+    }
+}
@@ -0,0 +1,340 @@
+use chrono::prelude::*;
+use rand::prelude::*;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::time::SystemTime;
+
+use crate::bot::BotAction;
+use crate::chat::{ChatMessage, MessageKind};
+use crate::{read_db, Error};
+
+const TRIGGERS_PATH: &str = "./data/triggers.json";
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct TriggerConfig {
+    /// log what would be sent instead of sending it
+    #[serde(default)]
+    pub dry_run: bool,
+    #[serde(default)]
+    pub triggers: Vec<TriggerRule>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct TriggerRule {
+    pub name: String,
+    #[serde(flatten)]
+    pub on: TriggerMatch,
+    /// template, see `render_template`
+    pub response: String,
+    /// channels this applies to, all of them when empty
+    #[serde(default)]
+    pub channels: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(tag = "on", content = "value", rename_all = "snake_case")]
+pub enum TriggerMatch {
+    Prefix(String),
+    Regex(String),
+    Event(TriggerEvent),
+}
+
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum TriggerEvent {
+    Sub,
+    Raid,
+    FirstMessage,
+}
+
+/// Config-driven auto-responses, reloaded whenever `data/triggers.json` changes.
+pub struct Triggers {
+    config: TriggerConfig,
+    // starts out as the file says and is toggled with `/dryrun`; a reload only changes
+    // it when the file's own setting changed
+    dry_run: bool,
+    // the compiled `TriggerMatch::Regex` of each rule, by index
+    regexes: Vec<Option<Regex>>,
+    // times each rule fired, by name
+    counts: HashMap<String, u64>,
+    modified: Option<SystemTime>,
+}
+
+impl Triggers {
+    pub fn load() -> Result<Triggers, Error> {
+        let mut triggers = Triggers {
+            config: TriggerConfig::default(),
+            dry_run: false,
+            regexes: vec![],
+            counts: HashMap::new(),
+            modified: None,
+        };
+        triggers.modified = modified_at();
+        triggers.apply(read_triggers()?)?;
+        Ok(triggers)
+    }
+
+    /// Re-reads the file if it changed since the last look, `None` when it didn't.
+    ///
+    /// A broken file is reported once and the previous triggers stay active.
+    pub fn reload_if_changed(&mut self) -> Option<Result<(), Error>> {
+        let modified = modified_at();
+        if modified == self.modified {
+            return None;
+        }
+        self.modified = modified;
+        Some(read_triggers().and_then(|config| self.apply(config)))
+    }
+
+    fn apply(&mut self, config: TriggerConfig) -> Result<(), Error> {
+        self.regexes = config
+            .triggers
+            .iter()
+            .map(|rule| match &rule.on {
+                TriggerMatch::Regex(pattern) => Regex::new(pattern).map(Some),
+                _ => Ok(None),
+            })
+            .collect::<Result<_, _>>()?;
+        if config.dry_run != self.config.dry_run {
+            self.dry_run = config.dry_run;
+        }
+        self.config = config;
+        Ok(())
+    }
+
+    pub fn toggle_dry_run(&mut self) -> bool {
+        self.dry_run = !self.dry_run;
+        self.dry_run
+    }
+
+    pub fn handle(&mut self, msg: &ChatMessage) -> Vec<BotAction> {
+        let mut actions = vec![];
+        for (rule, regex) in self.config.triggers.iter().zip(&self.regexes) {
+            if !rule.channels.is_empty() && !rule.channels.contains(&msg.channel) {
+                continue;
+            }
+            let args = match matches(rule, regex.as_ref(), msg) {
+                Some(args) => args,
+                None => continue,
+            };
+            let count = self.counts.entry(rule.name.clone()).or_default();
+            *count += 1;
+            let reply = match render_template(&rule.response, msg, args, *count) {
+                Ok(reply) => reply,
+                Err(e) => {
+                    actions.push(BotAction {
+                        reply: None,
+                        log: format!("#{} trigger {} failed: {}", msg.channel, rule.name, e),
+                    });
+                    continue;
+                }
+            };
+            actions.push(if self.dry_run {
+                BotAction {
+                    reply: None,
+                    log: format!(
+                        "#{} trigger {} would send: {}",
+                        msg.channel, rule.name, reply
+                    ),
+                }
+            } else {
+                BotAction {
+                    log: format!("#{} trigger {} -> {}", msg.channel, rule.name, reply),
+                    reply: Some(reply),
+                }
+            });
+        }
+        actions
+    }
+}
+
+/// Whether `rule` fires for `msg`, with the text after the prefix for `{args}`.
+fn matches<'a>(rule: &TriggerRule, regex: Option<&Regex>, msg: &'a ChatMessage) -> Option<&'a str> {
+    let event = match (msg.kind, msg.tags.get("msg-id").map(String::as_str)) {
+        (MessageKind::Notice, Some("sub" | "resub" | "subgift")) => Some(TriggerEvent::Sub),
+        (MessageKind::Notice, Some("raid")) => Some(TriggerEvent::Raid),
+        (MessageKind::Chat, _) if msg.tags.get("first-msg").map(String::as_str) == Some("1") => {
+            Some(TriggerEvent::FirstMessage)
+        }
+        _ => None,
+    };
+
+    match &rule.on {
+        TriggerMatch::Prefix(prefix) if msg.kind == MessageKind::Chat => {
+            // `!hi` answers "!hi" and "!hi there" but not "!hidden"
+            let rest = msg.text.strip_prefix(prefix.as_str())?;
+            let boundary = rest.is_empty()
+                || rest.starts_with(char::is_whitespace)
+                || prefix.ends_with(char::is_whitespace);
+            Some(rest.trim()).filter(|_| boundary)
+        }
+        TriggerMatch::Regex(_) if msg.kind == MessageKind::Chat => {
+            regex.filter(|regex| regex.is_match(&msg.text)).map(|_| "")
+        }
+        TriggerMatch::Event(on) if event == Some(*on) => Some(""),
+        _ => None,
+    }
+}
+
+/// Fills in `{user}`, `{channel}`, `{args}`, `{count}`, `{tag:<name>}` and
+/// `{pet.name}`/`{pet.category}`/`{pet.age}`/`{pet.id}` from a random pet.
+/// Unknown placeholders are left as they are.
+pub fn render_template(
+    template: &str,
+    msg: &ChatMessage,
+    args: &str,
+    count: u64,
+) -> Result<String, Error> {
+    let pet = if template.contains("{pet.") {
+        read_db()?.choose(&mut rand::thread_rng()).cloned()
+    } else {
+        None
+    };
+
+    let mut out = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        out.push_str(&rest[..start]);
+        let end = match rest[start..].find('}') {
+            Some(end) => start + end,
+            None => {
+                // an unclosed brace is just text
+                out.push_str(&rest[start..]);
+                rest = "";
+                break;
+            }
+        };
+        let key = &rest[start + 1..end];
+        let value = match key {
+            "user" => Some(msg.display_name.clone()),
+            "channel" => Some(msg.channel.clone()),
+            "args" => Some(args.to_string()),
+            "count" => Some(count.to_string()),
+            "pet.name" => pet.as_ref().map(|pet| pet.name.clone()),
+            "pet.category" => pet.as_ref().map(|pet| pet.category.clone()),
+            "pet.age" => pet
+                .as_ref()
+                .map(|pet| pet.age_label(Local::now().date_naive())),
+            "pet.id" => pet.as_ref().map(|pet| pet.id.to_string()),
+            _ => key
+                .strip_prefix("tag:")
+                .and_then(|tag| msg.tags.get(tag).cloned()),
+        };
+        match value {
+            Some(value) => out.push_str(&value),
+            None => out.push_str(&rest[start..=end]),
+        }
+        rest = &rest[end + 1..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+fn modified_at() -> Option<SystemTime> {
+    fs::metadata(TRIGGERS_PATH)
+        .and_then(|meta| meta.modified())
+        .ok()
+}
+
+fn read_triggers() -> Result<TriggerConfig, Error> {
+    match fs::read_to_string(TRIGGERS_PATH) {
+        Ok(content) => Ok(serde_json::from_str(&content)?),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(TriggerConfig::default()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(text: &str) -> ChatMessage {
+        let mut msg = ChatMessage::chat("ann", text, Utc::now());
+        msg.display_name = String::from("Ann");
+        msg
+    }
+
+    fn triggers(config: TriggerConfig) -> Triggers {
+        let mut triggers = Triggers {
+            config: TriggerConfig::default(),
+            dry_run: false,
+            regexes: vec![],
+            counts: HashMap::new(),
+            modified: None,
+        };
+        triggers.apply(config).unwrap();
+        triggers
+    }
+
+    fn prefix_rule(prefix: &str, response: &str) -> TriggerRule {
+        TriggerRule {
+            name: prefix.to_string(),
+            on: TriggerMatch::Prefix(prefix.to_string()),
+            response: response.to_string(),
+            channels: vec![],
+        }
+    }
+
+    #[test]
+    fn dry_run_survives_a_reload() {
+        let config = TriggerConfig {
+            dry_run: false,
+            triggers: vec![prefix_rule("!hi", "hello {user}")],
+        };
+        let mut triggers = triggers(config.clone());
+        assert!(triggers.toggle_dry_run());
+        triggers.apply(config.clone()).unwrap();
+        let actions = triggers.handle(&message("!hi"));
+        assert_eq!(actions[0].reply, None);
+        assert!(actions[0].log.contains("would send: hello Ann"));
+
+        // a change to the file itself still applies
+        let mut dry = config.clone();
+        dry.dry_run = true;
+        triggers.apply(dry).unwrap();
+        triggers.apply(config).unwrap();
+        let actions = triggers.handle(&message("!hi"));
+        assert_eq!(actions[0].reply.as_deref(), Some("hello Ann"));
+    }
+
+    #[test]
+    fn prefixes_end_at_a_word() {
+        let mut triggers = triggers(TriggerConfig {
+            dry_run: false,
+            triggers: vec![prefix_rule("!hi", "{args}"), prefix_rule("hey ", "{args}")],
+        });
+        let mut reply = |text| {
+            triggers
+                .handle(&message(text))
+                .into_iter()
+                .filter_map(|action| action.reply)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(reply("!hi"), [""]);
+        assert_eq!(reply("!hi  there "), ["there"]);
+        assert!(reply("!hidden").is_empty());
+        assert!(reply("say !hi").is_empty());
+        assert_eq!(reply("hey you"), ["you"]);
+    }
+
+    #[test]
+    fn templates() {
+        let msg = message("!hi there");
+        let render = |template| render_template(template, &msg, "there", 3).unwrap();
+        assert_eq!(
+            render("hi {user}, #{count} in {channel}"),
+            "hi Ann, #3 in petshop"
+        );
+        assert_eq!(render("you said {args}"), "you said there");
+        assert_eq!(
+            render("{nope} and {tag:missing}"),
+            "{nope} and {tag:missing}"
+        );
+        assert_eq!(render("hi {user"), "hi {user");
+        assert_eq!(render("{user} {"), "Ann {");
+        assert_eq!(render("{{user}}"), "{{user}}");
+    }
+}
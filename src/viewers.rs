@@ -0,0 +1,162 @@
+use std::collections::BTreeMap;
+use tui::{
+    style::{Color, Modifier, Style},
+    text::{Span, Spans},
+    widgets::{Block, BorderType, Borders, List, ListItem, Paragraph, Wrap},
+};
+
+use crate::chat::{self, Badge, Channel, ChatMessage};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Role {
+    Broadcaster,
+    Moderator,
+    Vip,
+    Viewer,
+}
+
+impl Role {
+    pub fn from_badges(badges: &[Badge]) -> Role {
+        if badges.contains(&Badge::Broadcaster) {
+            Role::Broadcaster
+        } else if badges.contains(&Badge::Moderator) {
+            Role::Moderator
+        } else if badges.contains(&Badge::Vip) {
+            Role::Vip
+        } else {
+            Role::Viewer
+        }
+    }
+
+    fn heading(self) -> &'static str {
+        match self {
+            Role::Broadcaster => "Broadcaster",
+            Role::Moderator => "Moderators",
+            Role::Vip => "VIPs",
+            Role::Viewer => "Viewers",
+        }
+    }
+}
+
+/// Chatters currently in a channel, keyed by login.
+///
+/// NAMES and JOIN only tell us a login, so everyone starts as a `Viewer` and gets
+/// their real role once they send a message with badges.
+#[derive(Default)]
+pub struct Viewers {
+    users: BTreeMap<String, Role>,
+}
+
+impl Viewers {
+    pub fn join(&mut self, login: &str) {
+        self.users
+            .entry(login.to_lowercase())
+            .or_insert(Role::Viewer);
+    }
+
+    pub fn part(&mut self, login: &str) {
+        self.users.remove(&login.to_lowercase());
+    }
+
+    pub fn seen(&mut self, msg: &ChatMessage) {
+        let role = match Role::from_badges(&msg.badges) {
+            Role::Viewer if msg.login == msg.channel => Role::Broadcaster,
+            role => role,
+        };
+        self.users.insert(msg.login.clone(), role);
+    }
+
+    pub fn count(&self) -> usize {
+        self.users.len()
+    }
+
+    /// Logins containing `query`, ordered by role and then name.
+    pub fn search(&self, query: &str) -> Vec<(Role, &str)> {
+        let query = query.to_lowercase();
+        let mut found: Vec<_> = self
+            .users
+            .iter()
+            .filter(|(login, _)| login.contains(&query))
+            .map(|(login, role)| (*role, login.as_str()))
+            .collect();
+        found.sort();
+        found
+    }
+}
+
+/// The grouped viewer list, plus the list row of the `selected`th search hit.
+/// `typing` is whether the search line is open.
+pub fn render_viewers<'a>(
+    channel: &Channel,
+    query: &str,
+    typing: bool,
+    selected: Option<usize>,
+) -> (List<'a>, Option<usize>) {
+    let mut items = vec![];
+    let mut selected_row = None;
+    let mut current_role = None;
+    for (index, (role, login)) in channel.viewers.search(query).into_iter().enumerate() {
+        if current_role != Some(role) {
+            current_role = Some(role);
+            items.push(ListItem::new(Spans::from(vec![Span::styled(
+                role.heading(),
+                Style::default()
+                    .fg(Color::Yellow)
+                    .add_modifier(Modifier::BOLD),
+            )])));
+        }
+        if selected == Some(index) {
+            selected_row = Some(items.len());
+        }
+        items.push(ListItem::new(Spans::from(vec![Span::raw(format!(
+            " {}",
+            login
+        ))])));
+    }
+
+    let title = if typing {
+        format!("Chatters /{}_", query)
+    } else if query.is_empty() {
+        format!("Chatters ({}, / searches)", channel.viewers.count())
+    } else {
+        format!("Chatters /{}", query)
+    };
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .style(Style::default().fg(Color::White))
+                .title(title)
+                .border_type(BorderType::Plain),
+        )
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    (list, selected_row)
+}
+
+/// The last messages `login` sent that are still in the channel's scrollback.
+pub fn render_user_popup<'a>(channel: &Channel, login: &str, limit: usize) -> Paragraph<'a> {
+    let recent: Vec<_> = channel
+        .messages
+        .iter()
+        .filter(|msg| msg.login == login)
+        .collect();
+    let mut lines: Vec<Spans> = recent
+        .iter()
+        .skip(recent.len().saturating_sub(limit))
+        .map(|msg| chat::render_message(msg, false))
+        .collect();
+    if lines.is_empty() {
+        lines.push(Spans::from(vec![Span::styled(
+            "no messages in the scrollback",
+            Style::default().fg(Color::DarkGray),
+        )]));
+    }
+
+    Paragraph::new(lines).wrap(Wrap { trim: false }).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .style(Style::default().fg(Color::White).bg(Color::Black))
+            .title(format!("{} in {}", login, channel.title()))
+            .border_type(BorderType::Rounded),
+    )
+}
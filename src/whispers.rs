@@ -0,0 +1,64 @@
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+
+use crate::chat::{Channel, ChatMessage};
+use crate::Error;
+
+const WHISPERS_DIR: &str = "./data/whispers";
+
+/// Appends `msg` to `data/whispers/<login>.jsonl`, `msg.channel` being who the conversation is with.
+pub fn append(msg: &ChatMessage) -> Result<(), Error> {
+    fs::create_dir_all(WHISPERS_DIR)?;
+    let mut line = serde_json::to_vec(msg)?;
+    line.push(b'\n');
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(Path::new(WHISPERS_DIR).join(format!("{}.jsonl", msg.channel)))?
+        .write_all(&line)?;
+    Ok(())
+}
+
+/// Conversations from earlier sessions, with the end of their history as scrollback,
+/// and how many unreadable lines and files were skipped.
+pub fn load_conversations() -> Result<(Vec<Channel>, usize), Error> {
+    let entries = match fs::read_dir(WHISPERS_DIR) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok((vec![], 0)),
+        Err(e) => return Err(e.into()),
+    };
+    let mut paths = vec![];
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.extension().is_some_and(|ext| ext == "jsonl") {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+
+    let mut conversations = vec![];
+    let mut skipped = 0;
+    for path in paths {
+        let login = match path.file_stem().and_then(|stem| stem.to_str()) {
+            Some(login) => login.to_string(),
+            None => continue,
+        };
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(_) => {
+                skipped += 1;
+                continue;
+            }
+        };
+        let mut channel = Channel::whisper(&login);
+        for line in content.lines() {
+            match serde_json::from_str(line) {
+                Ok(msg) => channel.push(msg),
+                Err(_) => skipped += 1,
+            }
+        }
+        conversations.push(channel);
+    }
+    Ok((conversations, skipped))
+}